@@ -0,0 +1,80 @@
+//! Deterministic corpora shared by the benchmarks.
+
+// Each benchmark uses only some of these
+#![allow(dead_code)]
+
+use std::path::PathBuf;
+
+use snapfind::search::{MAX_CONTENT_LENGTH, SearchEngine};
+
+pub const WORDS: [&str; 8] = [
+    "deploy", "service", "config", "parser", "index", "report", "network", "storage",
+];
+
+/// `count` words starting at `seed`, cycling through [`WORDS`].
+pub fn words(seed: usize, count: usize) -> String {
+    (0..count)
+        .map(|j| WORDS[(seed + j) % WORDS.len()])
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// A query of `terms` four-letter word stems. Ten of them still fit the
+/// query length limit.
+pub fn query(terms: usize) -> String {
+    (0..terms)
+        .map(|j| &WORDS[j % WORDS.len()][..4])
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Content filling the default per-document limit exactly.
+pub fn max_length_content(seed: usize) -> String {
+    let mut content = words(seed, MAX_CONTENT_LENGTH);
+    content.truncate(MAX_CONTENT_LENGTH);
+    content
+}
+
+/// An engine holding `count` documents of default maximum length.
+pub fn engine_with(count: usize) -> SearchEngine {
+    let mut engine = SearchEngine::new();
+    for i in 0..count {
+        let path = PathBuf::from(format!("src/{}_{i}.rs", WORDS[i % WORDS.len()]));
+        engine.add_document(&path, &max_length_content(i)).unwrap();
+    }
+    engine
+}
+
+pub fn plain_text() -> Vec<u8> {
+    (0..40)
+        .map(|i| format!("{}\n", words(i, 8)))
+        .collect::<String>()
+        .into_bytes()
+}
+
+pub fn markdown() -> Vec<u8> {
+    (0..10)
+        .map(|i| {
+            format!(
+                "# {}\n\n- {}\n- `{}`\n\n",
+                words(i, 2),
+                words(i, 6),
+                WORDS[i % 8]
+            )
+        })
+        .collect::<String>()
+        .into_bytes()
+}
+
+/// A PNG header followed by pseudo-random bytes.
+pub fn binary() -> Vec<u8> {
+    let mut state = 0x2545_f491_u32;
+    let mut bytes = b"\x89PNG\r\n\x1a\n".to_vec();
+    bytes.extend((0..4096).map(|_| {
+        state ^= state << 13;
+        state ^= state >> 17;
+        state ^= state << 5;
+        state.to_le_bytes()[0]
+    }));
+    bytes
+}
@@ -0,0 +1,30 @@
+mod common;
+
+use criterion::{Criterion, criterion_group, criterion_main};
+use snapfind::search::{INDEX_FILE_NAME, MAX_DOCUMENTS, SearchEngine};
+use tempfile::TempDir;
+
+use common::engine_with;
+
+fn bench_round_trip(c: &mut Criterion) {
+    let engine = engine_with(MAX_DOCUMENTS);
+    let temp_dir = TempDir::new().unwrap();
+    let path = temp_dir.path().join(INDEX_FILE_NAME);
+
+    let mut group = c.benchmark_group("index");
+    group.bench_function("save", |b| b.iter(|| engine.save(&path).unwrap()));
+    group.bench_function("load", |b| {
+        engine.save(&path).unwrap();
+        b.iter(|| SearchEngine::load(&path).unwrap());
+    });
+    group.bench_function("round_trip", |b| {
+        b.iter(|| {
+            engine.save(&path).unwrap();
+            SearchEngine::load(&path).unwrap()
+        });
+    });
+    group.finish();
+}
+
+criterion_group!(benches, bench_round_trip);
+criterion_main!(benches);
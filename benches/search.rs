@@ -0,0 +1,83 @@
+mod common;
+
+use std::hint::black_box;
+use std::path::Path;
+
+use criterion::{BenchmarkId, Criterion, criterion_group, criterion_main};
+use snapfind::search::{MAX_DOCUMENTS, SearchEngine};
+
+use common::{engine_with, max_length_content, query};
+
+fn bench_search(c: &mut Criterion) {
+    let mut group = c.benchmark_group("search");
+    for count in [10, 50, MAX_DOCUMENTS] {
+        let engine = engine_with(count);
+
+        #[cfg(not(feature = "rayon"))]
+        group.bench_with_input(
+            BenchmarkId::new("sequential", count),
+            &engine,
+            |b, engine| {
+                b.iter(|| engine.search(black_box("deploy config")).unwrap());
+            },
+        );
+
+        #[cfg(feature = "rayon")]
+        {
+            // A one-thread pool stands in for the sequential build
+            let single = rayon::ThreadPoolBuilder::new()
+                .num_threads(1)
+                .build()
+                .unwrap();
+            group.bench_with_input(BenchmarkId::new("single", count), &engine, |b, engine| {
+                b.iter(|| single.install(|| engine.search(black_box("deploy config")).unwrap()));
+            });
+            group.bench_with_input(BenchmarkId::new("rayon", count), &engine, |b, engine| {
+                b.iter(|| engine.search(black_box("deploy config")).unwrap());
+            });
+        }
+    }
+    group.finish();
+}
+
+fn bench_query_terms(c: &mut Criterion) {
+    let engine = engine_with(MAX_DOCUMENTS);
+    let mut group = c.benchmark_group("search_terms");
+    for terms in [1, 3, 10] {
+        let query = query(terms);
+        group.bench_with_input(BenchmarkId::from_parameter(terms), &query, |b, query| {
+            b.iter(|| engine.search(black_box(query)).unwrap());
+        });
+    }
+    group.finish();
+}
+
+fn bench_term_matching(c: &mut Criterion) {
+    let content = max_length_content(0);
+    let mut group = c.benchmark_group("term_matches");
+    for (name, term) in [("hit", "storage"), ("miss", "absent")] {
+        group.bench_function(name, |b| {
+            b.iter(|| SearchEngine::term_matches(black_box(term.as_bytes()), content.as_bytes()));
+        });
+    }
+    group.finish();
+
+    let path = Path::new("src/deploy_0.rs");
+    let mut engine = SearchEngine::new();
+    engine.add_document(path, &content).unwrap();
+    c.bench_function("explain_score", |b| {
+        b.iter(|| {
+            engine
+                .explain_score(black_box("deploy network report"), path)
+                .unwrap()
+        });
+    });
+}
+
+criterion_group!(
+    benches,
+    bench_search,
+    bench_query_terms,
+    bench_term_matching
+);
+criterion_main!(benches);
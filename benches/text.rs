@@ -0,0 +1,26 @@
+mod common;
+
+use std::hint::black_box;
+
+use criterion::{BenchmarkId, Criterion, criterion_group, criterion_main};
+use snapfind::text::TextDetector;
+
+fn bench_validate(c: &mut Criterion) {
+    let samples = [
+        ("plain", common::plain_text()),
+        ("markdown", common::markdown()),
+        ("binary", common::binary()),
+    ];
+
+    let mut detector = TextDetector::new();
+    let mut group = c.benchmark_group("validate");
+    for (name, sample) in &samples {
+        group.bench_with_input(BenchmarkId::from_parameter(name), sample, |b, sample| {
+            b.iter(|| detector.validate(black_box(sample)));
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_validate);
+criterion_main!(benches);
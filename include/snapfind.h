@@ -0,0 +1,99 @@
+#ifndef SNAPFIND_H
+#define SNAPFIND_H
+
+/* Generated with cbindgen from src/snapfind/ffi.rs; do not edit by hand. */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+#define FFI_PATH_BYTES 1025
+
+/**
+ * Stable numeric codes carried by [`SnapError`]. Hundreds group the source:
+ * 1xx general, 2xx crawling, 3xx search and index files, 4xx input
+ * splitting. The `ERROR_*` constants in each module are defined from these
+ * variants, and [`exit_code`] turns them into process exit statuses.
+ */
+enum ErrorCode
+#if __STDC_VERSION__ >= 202311L
+  : int32_t
+#endif // __STDC_VERSION__ >= 202311L
+ {
+  ErrorCode_Other = 101,
+  ErrorCode_JsonUnsupported = 102,
+  ErrorCode_DepthExceeded = 201,
+  ErrorCode_FileCountExceeded = 202,
+  ErrorCode_FileSizeExceeded = 203,
+  ErrorCode_CrawlPathTooLong = 204,
+  ErrorCode_TooManyExclusions = 205,
+  ErrorCode_InvalidCrawlConfig = 206,
+  ErrorCode_PendingDirsExceeded = 207,
+  ErrorCode_InvalidQuery = 301,
+  ErrorCode_InvalidIndex = 302,
+  ErrorCode_TooManyDocuments = 303,
+  ErrorCode_ContentTooLarge = 304,
+  ErrorCode_IndexPathTooLong = 305,
+  ErrorCode_InvalidSearchConfig = 306,
+  ErrorCode_IndexLocked = 307,
+  ErrorCode_DocumentNotFound = 308,
+  ErrorCode_InvalidSplit = 401,
+};
+#if __STDC_VERSION__ >= 202311L
+typedef enum ErrorCode ErrorCode;
+#else
+typedef int32_t ErrorCode;
+#endif // __STDC_VERSION__ >= 202311L
+
+typedef struct SearchEngine SearchEngine;
+
+/**
+ * One search hit. `path` is NUL-terminated and relative to the indexed root.
+ */
+typedef struct SearchResultFFI {
+  uint8_t path[FFI_PATH_BYTES];
+  float score;
+} SearchResultFFI;
+
+/**
+ * Creates an empty engine. Release it with [`snapfind_engine_free`].
+ * Returns null if the engine could not be created.
+ */
+struct SearchEngine *snapfind_engine_new(void);
+
+/**
+ * Frees an engine created by [`snapfind_engine_new`]. Null is ignored.
+ *
+ * # Safety
+ *
+ * `engine` must be null or a pointer returned by [`snapfind_engine_new`]
+ * that has not been freed yet.
+ */
+void snapfind_engine_free(struct SearchEngine *engine);
+
+/**
+ * Adds a document. Returns 0 on success, otherwise an error code.
+ *
+ * # Safety
+ *
+ * `engine` must come from [`snapfind_engine_new`]; `path` and `content` must
+ * be null or NUL-terminated strings.
+ */
+int32_t snapfind_add_document(struct SearchEngine *engine, const char *path, const char *content);
+
+/**
+ * Searches the engine and writes at most `max` hits to `out`, best first.
+ * Returns the number of hits written, or a negated error code.
+ *
+ * # Safety
+ *
+ * `engine` must come from [`snapfind_engine_new`], `query` must be null or a
+ * NUL-terminated string, and `out` must point to `max` writable results.
+ */
+int32_t snapfind_search(struct SearchEngine *engine,
+                        const char *query,
+                        struct SearchResultFFI *out,
+                        uintptr_t max);
+
+#endif  /* SNAPFIND_H */
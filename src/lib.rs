@@ -0,0 +1,9 @@
+mod snapfind;
+
+#[cfg(feature = "serde")]
+pub use snapfind::export;
+#[cfg(feature = "ffi")]
+pub use snapfind::ffi;
+#[cfg(feature = "unicode")]
+pub use snapfind::normalize;
+pub use snapfind::{crawler, error, indexer, lock, progress, search, split, text, watch};
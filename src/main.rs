@@ -1,19 +1,29 @@
-mod snapfind;
-
+use std::io::{IsTerminal, Read, Write};
 use std::path::{Path, PathBuf};
-use std::{fs, process};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+use std::{fs, io, process, thread};
 
-use clap::{Parser, Subcommand};
+use clap::{Args, CommandFactory, Parser, Subcommand, ValueEnum, ValueHint};
 use clap_cargo::style::CLAP_STYLING;
 use snapfind::error::{SnapError, SnapResult};
+use snapfind::progress::{NoProgress, ProgressSink};
+use snapfind::split::{self, SplitMode};
 use snapfind::text::TextDetector;
-use snapfind::{crawler, search};
+use snapfind::{crawler, indexer, lock, search, watch};
+
+mod output;
+
+use output::OutputFormat;
 
 #[derive(Debug, Parser)]
 #[command(author, version, about, display_name="", styles = CLAP_STYLING)]
 struct Cli {
     #[command(subcommand)]
     command: Command,
+    /// When to color scores and highlight the parts of paths that matched
+    #[arg(long, global = true, value_name = "WHEN", default_value = "auto")]
+    color: ColorChoice,
 }
 
 #[derive(Debug, Subcommand)]
@@ -21,112 +31,663 @@ enum Command {
     /// Index a directory for searching
     Index {
         /// Directory to index
-        #[arg(default_value = ".")]
+        #[arg(default_value = ".", value_hint = ValueHint::DirPath)]
         dir: PathBuf,
+        /// Maximum bytes of content indexed per file
+        #[arg(long, value_name = "BYTES", default_value_t = search::MAX_CONTENT_LENGTH)]
+        max_content: usize,
+        /// How to print the indexing summary
+        #[arg(long, value_name = "FORMAT", default_value = "text")]
+        output_format: OutputFormat,
+        /// Number of threads reading files
+        #[arg(short, long, default_value_t = 1, value_parser = clap::value_parser!(u8).range(1..))]
+        jobs: u8,
+        /// Only index files with this extension (repeatable)
+        #[arg(long = "ext", value_name = "EXT")]
+        extensions: Vec<String>,
+        #[command(flatten)]
+        limits: CrawlLimits,
     },
     /// Search for files
     Search {
         /// Search query
         query: String,
-        /// Directory to search in (must be indexed first)
-        #[arg(default_value = ".")]
+        /// Directories to search in (must be indexed first)
+        #[arg(default_value = ".", value_hint = ValueHint::DirPath)]
+        dirs: Vec<PathBuf>,
+        #[command(flatten)]
+        flags: SearchFlags,
+    },
+    /// Explain how a file scores against a query
+    Explain {
+        /// Search query
+        query: String,
+        /// Indexed file to explain, relative to the directory
+        #[arg(value_hint = ValueHint::FilePath)]
+        file: PathBuf,
+        /// Directory the file was indexed from
+        #[arg(long, default_value = ".", value_hint = ValueHint::DirPath)]
+        dir: PathBuf,
+    },
+    /// Search text piped on stdin without indexing a directory
+    Grep {
+        /// Search query
+        query: String,
+        /// How to break the input into documents: lines, paragraphs or bytes:N
+        #[arg(long, default_value = "lines")]
+        split: SplitMode,
+        /// Tolerate small typos in query terms
+        #[arg(long)]
+        fuzzy: bool,
+    },
+    /// Index a directory, then keep the index up to date as files change
+    Watch {
+        /// Directory to watch
+        #[arg(default_value = ".", value_hint = ValueHint::DirPath)]
+        dir: PathBuf,
+        /// Maximum bytes of content indexed per file
+        #[arg(long, value_name = "BYTES", default_value_t = search::MAX_CONTENT_LENGTH)]
+        max_content: usize,
+        /// Milliseconds without changes to wait before saving the index
+        #[arg(long, value_name = "MS", default_value_t = 1000)]
+        debounce: u64,
+        #[command(flatten)]
+        limits: CrawlLimits,
+    },
+    /// Re-read only the files that changed since a directory was indexed
+    Update {
+        /// Indexed directory
+        #[arg(default_value = ".", value_hint = ValueHint::DirPath)]
+        dir: PathBuf,
+        #[command(flatten)]
+        limits: CrawlLimits,
+    },
+    /// Show what an existing index contains
+    Stats {
+        /// Indexed directory
+        #[arg(default_value = ".", value_hint = ValueHint::DirPath)]
+        dir: PathBuf,
+        /// How to print the statistics
+        #[arg(long, value_name = "FORMAT", default_value = "text")]
+        output_format: OutputFormat,
+    },
+    /// List the files stored in an index, sorted by path
+    List {
+        /// Indexed directory
+        #[arg(default_value = ".", value_hint = ValueHint::DirPath)]
+        dir: PathBuf,
+        /// Also print the stored content size of each file and its
+        /// modification time (UTC) when indexed
+        #[arg(short, long)]
+        long: bool,
+        /// Only list files with this extension
+        #[arg(long, value_name = "EXT")]
+        filter: Option<String>,
+    },
+    /// Write an index as JSON, for debugging or other tools
+    Export {
+        /// Indexed directory
+        #[arg(default_value = ".", value_hint = ValueHint::DirPath)]
+        dir: PathBuf,
+        /// File to write instead of stdout
+        #[arg(short, long, value_name = "FILE", value_hint = ValueHint::FilePath)]
+        out: Option<PathBuf>,
+    },
+    /// Rebuild an index from JSON written by `snap export`
+    Import {
+        /// JSON file to read
+        #[arg(value_hint = ValueHint::FilePath)]
+        file: PathBuf,
+        /// Directory to save the index in
+        #[arg(default_value = ".", value_hint = ValueHint::DirPath)]
+        dir: PathBuf,
+    },
+    /// Print a shell completion script to stdout
+    Completions {
+        /// Shell to generate completions for
+        shell: clap_complete::Shell,
+    },
+    /// Remove index files
+    Clean {
+        /// Directory to clean
+        #[arg(default_value = ".", value_hint = ValueHint::DirPath)]
         dir: PathBuf,
+        /// Also remove indexes in subdirectories
+        #[arg(short, long)]
+        recursive: bool,
+        /// List the indexes that would be removed without deleting them
+        #[arg(long)]
+        dry_run: bool,
     },
 }
 
-fn get_index_path(dir: &Path) -> PathBuf {
-    dir.join(".snapfind_index")
+impl Command {
+    const fn output_format(&self) -> OutputFormat {
+        match self {
+            Self::Index { output_format, .. } | Self::Stats { output_format, .. } => *output_format,
+            Self::Search { flags, .. } => flags.output_format,
+            _ => OutputFormat::Text,
+        }
+    }
 }
 
-fn index_directory(dir: &Path) -> SnapResult<()> {
-    println!("Indexing directory: {}", dir.display());
+#[derive(Debug, Args)]
+struct SearchFlags {
+    /// Tolerate small typos in query terms
+    #[arg(long)]
+    fuzzy: bool,
+    /// Score common words like "the" and "to" like any other term
+    #[arg(long)]
+    keep_stop_words: bool,
+    /// Treat the query as a glob pattern matched against file paths
+    #[arg(long)]
+    glob: bool,
+    /// Show how each query term contributed to a result's score
+    #[arg(long)]
+    explain: bool,
+    /// Page of results to show, starting at 1
+    #[arg(long, default_value_t = 1, value_parser = clap::value_parser!(u16).range(1..))]
+    page: u16,
+    /// Number of results per page
+    #[arg(long, default_value_t = search::MAX_RESULTS)]
+    page_size: usize,
+    /// Print only matching paths, each terminated by a NUL byte (for xargs -0)
+    #[arg(short = '0', long, conflicts_with = "explain")]
+    print0: bool,
+    /// Print `path:line:column: line` for the first content match, like grep
+    /// tools do for editors; path-only matches print just the path
+    #[arg(long, conflicts_with_all = ["explain", "print0", "output_format"])]
+    vimgrep: bool,
+    /// How to print results; json and jsonl print only the matches
+    #[arg(
+        long,
+        value_name = "FORMAT",
+        default_value = "text",
+        conflicts_with_all = ["explain", "print0"]
+    )]
+    output_format: OutputFormat,
+}
 
-    let mut engine = search::SearchEngine::new();
-    let mut crawler = crawler::Crawler::new(dir)?;
-    let mut detector = TextDetector::new();
-    let mut total_files = 0;
-    let mut last_progress = 0;
-    let mut had_errors = false;
-
-    let mut last_processed = 0;
-    let mut last_dirs = 0;
-
-    while let Some(files) = crawler.process_next()? {
-        let (processed, max_files, dirs) = crawler.progress();
-
-        assert!(processed >= last_processed, "File count must not decrease");
-        assert!(dirs >= last_dirs, "Directory count must not decrease");
-        last_processed = processed;
-        last_dirs = dirs;
-
-        for file in files {
-            match fs::read(&file) {
-                Ok(content) => {
-                    let validation = detector.validate(&content);
-                    if validation.is_valid_text() {
-                        if processed >= last_progress + 100 {
-                            println!(
-                                "Progress: {processed}/{max_files} files indexed ({dirs} \
-                                 directories found)"
-                            );
-                            println!(
-                                "Last file: {} ({:?}, confidence: {}%)",
-                                file.display(),
-                                validation.mime_type(),
-                                validation.confidence()
-                            );
-                            last_progress = processed;
-                        }
-
-                        match engine
-                            .add_document(&file, std::str::from_utf8(&content).unwrap_or(""))
-                        {
-                            Ok(()) => {
-                                total_files += 1;
-                            }
-                            Err(e) => {
-                                eprintln!("\nIndexing stopped due to error.");
-                                return Err(e);
-                            }
-                        }
-                    }
-                }
-                Err(e) => {
-                    had_errors = true;
-                    eprintln!("Error: Failed to read {}: {e}", file.display());
-                }
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum ColorChoice {
+    /// Color when stdout is a terminal, TERM is not dumb and NO_COLOR is not set
+    Auto,
+    Always,
+    Never,
+}
+
+impl ColorChoice {
+    fn enabled(self) -> bool {
+        match self {
+            Self::Auto => {
+                io::stdout().is_terminal()
+                    && std::env::var_os("NO_COLOR").is_none_or(|v| v.is_empty())
+                    && std::env::var_os("TERM").is_some_and(|v| v != "dumb")
             }
+            Self::Always => true,
+            Self::Never => false,
+        }
+    }
+}
+
+const HIGHLIGHT_START: &str = "\x1b[1;31m";
+const HIGHLIGHT_END: &str = "\x1b[0m";
+
+/// Formats a score for the score column, green from 80%, yellow from 50%
+/// and red below that when `color` is set.
+fn format_score(score: f32, color: bool) -> String {
+    let text = format!("{score:>5.1}%");
+    if !color {
+        return text;
+    }
+    let escape = if score >= 80.0 {
+        "\x1b[32m"
+    } else if score >= 50.0 {
+        "\x1b[33m"
+    } else {
+        "\x1b[31m"
+    };
+    format!("{escape}{text}{HIGHLIGHT_END}")
+}
+
+/// Wraps the byte ranges of `text` that start at `offset` or later in ANSI
+/// highlight escapes.
+fn highlight(text: &str, offset: usize, ranges: &[std::ops::Range<usize>]) -> String {
+    let mut out = String::with_capacity(
+        text.len() + ranges.len() * (HIGHLIGHT_START.len() + HIGHLIGHT_END.len()),
+    );
+    let mut pos = 0;
+    for range in ranges {
+        let (start, end) = (range.start + offset, range.end + offset);
+        let (Some(before), Some(matched)) = (text.get(pos..start), text.get(start..end)) else {
+            continue;
+        };
+        out.push_str(before);
+        out.push_str(HIGHLIGHT_START);
+        out.push_str(matched);
+        out.push_str(HIGHLIGHT_END);
+        pos = end;
+    }
+    out.push_str(&text[pos..]);
+    out
+}
+
+#[derive(Debug, Args)]
+struct CrawlLimits {
+    /// Stop with an error after this many files
+    #[arg(long, default_value_t = crawler::MAX_FILES)]
+    max_files: usize,
+    /// Skip files larger than this, with a warning
+    #[arg(long, value_name = "BYTES", default_value_t = crawler::MAX_FILE_SIZE)]
+    max_file_size: u64,
+    /// Skip files smaller than this, such as empty ones
+    #[arg(long = "min-size", value_name = "BYTES", default_value_t = 0)]
+    min_file_size: u64,
+    /// Stop with an error on directories nested deeper than this
+    #[arg(long, default_value_t = crawler::MAX_DEPTH)]
+    max_depth: usize,
+    /// Stop with an error when more directories than this wait to be crawled
+    #[arg(long, value_name = "DIRS", default_value_t = crawler::MAX_PENDING_DIRS)]
+    max_pending_dirs: usize,
+    /// Fail instead of skipping directories that cannot be read
+    #[arg(long)]
+    strict: bool,
+    /// Include hidden files and directories, such as `.git`
+    #[arg(long)]
+    hidden: bool,
+}
+
+impl CrawlLimits {
+    fn config(&self) -> crawler::CrawlerConfig {
+        crawler::CrawlerConfig {
+            max_files: self.max_files,
+            max_depth: self.max_depth,
+            max_pending_dirs: self.max_pending_dirs,
+            max_file_size: self.max_file_size,
+            min_file_size: self.min_file_size,
+            strict: self.strict,
+            skip_hidden: !self.hidden,
+            ..crawler::CrawlerConfig::default()
         }
     }
+}
+
+fn get_index_path(dir: &Path) -> PathBuf {
+    dir.join(search::INDEX_FILE_NAME)
+}
+
+struct ConsoleProgress {
+    last_progress: usize,
+}
 
-    if total_files == 0 {
-        if had_errors {
-            return Err(anyhow::Error::from(SnapError::with_code(
-                "Failed to index any files due to errors. Check file permissions and try again.",
-                search::ERROR_INVALID_INDEX,
-            )));
+impl ProgressSink for ConsoleProgress {
+    fn on_batch(&mut self, processed: usize, max: usize, dirs: usize, last_file: &Path) {
+        if processed >= self.last_progress + 100 {
+            println!("Progress: {processed}/{max} files indexed ({dirs} directories found)");
+            println!("Last file: {}", last_file.display());
+            self.last_progress = processed;
         }
+    }
+
+    fn on_read_error(&mut self, path: &Path, error: &io::Error) {
+        eprintln!("Error: Failed to read {}: {error}", path.display());
+    }
+
+    fn on_truncated(&mut self, path: &Path, original_len: usize, stored_len: usize) {
+        eprintln!(
+            "Warning: {} truncated to {stored_len} of {original_len} bytes",
+            path.display()
+        );
+    }
+}
+
+fn report_crawl_errors(crawler: &mut crawler::Crawler) {
+    let errors = crawler.take_errors();
+    let dropped = crawler.dropped_errors();
+    if errors.is_empty() && dropped == 0 {
+        return;
+    }
+
+    eprintln!(
+        "\nWarning: {} paths skipped due to errors",
+        errors.len() + dropped
+    );
+    for (path, error) in &errors {
+        eprintln!("  {}: {error}", path.display());
+    }
+    if dropped > 0 {
+        eprintln!("  ... and {dropped} more");
+    }
+}
+
+fn index_directory(
+    dir: &Path,
+    max_content: usize,
+    jobs: u8,
+    extensions: Vec<String>,
+    limits: &CrawlLimits,
+    format: OutputFormat,
+) -> SnapResult<search::SearchEngine> {
+    format.check()?;
+    let text = format.is_text();
+    if text {
+        println!("Indexing directory: {}", dir.display());
+    }
+
+    let config = indexer::IndexConfig {
+        crawler: limits.config(),
+        max_content_length: max_content,
+        jobs: jobs.into(),
+        index_path: Some(get_index_path(dir)),
+        extensions,
+    };
+    let mut console = ConsoleProgress { last_progress: 0 };
+    let sink: &mut dyn ProgressSink = if text { &mut console } else { &mut NoProgress };
+
+    let indexer::IndexRun {
+        engine,
+        mut crawler,
+        report,
+    } = indexer::index_directory_with(dir, &config, sink).inspect_err(|_| {
+        if text {
+            eprintln!("\nIndexing stopped due to error.");
+        }
+    })?;
+    if !text {
+        output::write_object(&report)?;
+        return Ok(engine);
+    }
+    report_crawl_errors(&mut crawler);
+
+    let Some(index_path) = &report.index_path else {
         println!("No files were indexed. Make sure the directory contains text files.");
+        return Ok(engine);
+    };
+
+    println!("\nIndexing completed:");
+    println!("- Files indexed: {}", report.files_indexed);
+    println!("- Files found: {}", report.files_found);
+    println!("- Directories processed: {}", report.directories);
+    println!("- Deepest level: {}", report.max_depth);
+    println!("- Bytes visited: {}", report.bytes_visited);
+    if report.errors_skipped > 0 {
+        println!("- Skipped (errors): {}", report.errors_skipped);
+    }
+    let stats = crawler.stats();
+    println!("- Files by extension:");
+    for (ext, count) in stats.extensions() {
+        println!("  .{ext}: {count}");
+    }
+    if stats.other_extensions > 0 {
+        println!("  other: {}", stats.other_extensions);
+    }
+    println!("- Skipped (non-text): {}", report.skipped_non_text);
+    if report.skipped_too_large > 0 {
+        println!("- Skipped (too large): {}", report.skipped_too_large);
+    }
+    if report.skipped_too_small > 0 {
+        println!("- Skipped (too small): {}", report.skipped_too_small);
+    }
+    if report.truncated > 0 {
+        println!("- Truncated: {}", report.truncated);
+    }
+    println!("- Index saved to {}", index_path.display());
+    println!("- Took {:.2?}", report.elapsed);
+
+    Ok(engine)
+}
+
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+#[cfg(unix)]
+fn catch_interrupt() {
+    extern "C" fn on_interrupt(_signal: libc::c_int) {
+        INTERRUPTED.store(true, Ordering::SeqCst);
+    }
+
+    // SAFETY: the handler only stores to an atomic, which is async-signal-safe
+    unsafe {
+        libc::signal(
+            libc::SIGINT,
+            on_interrupt as *const () as libc::sighandler_t,
+        );
+    }
+}
+
+// Elsewhere Ctrl-C ends the process directly; changes are saved after every
+// quiet period, so at most the last window of changes is lost
+#[cfg(not(unix))]
+fn catch_interrupt() {}
+
+/// What [`apply_change`] did to the index.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Applied {
+    Added,
+    Updated,
+    Removed,
+    /// A new file left out because the index already holds `MAX_DOCUMENTS`.
+    Skipped,
+    Unchanged,
+}
+
+fn apply_change(
+    engine: &mut search::SearchEngine,
+    dir: &Path,
+    detector: &mut TextDetector,
+    change: &watch::Change,
+) -> SnapResult<Applied> {
+    let path = change.path();
+    let relative = path.strip_prefix(dir).unwrap_or(path);
+
+    if let watch::Change::Removed(_) = change {
+        if engine.remove_document(relative) {
+            println!("Removed: {}", path.display());
+            return Ok(Applied::Removed);
+        }
+        return Ok(Applied::Unchanged);
+    }
+
+    if engine.document_count() >= search::MAX_DOCUMENTS && !engine.contains_document(relative) {
+        eprintln!("Warning: Index is full, skipping {}", path.display());
+        return Ok(Applied::Skipped);
+    }
+
+    // Stamped first, so a write during the read shows up as a change
+    let stamp = search::FileStamp::of(path);
+    match indexer::read_text(detector, path) {
+        Ok(Some(text)) => {
+            let existed = engine.contains_document(relative);
+            let outcome = engine.update_document_truncating(relative, &text)?;
+            if let Some(stamp) = stamp {
+                engine.set_document_stamp(relative, stamp);
+            }
+            println!(
+                "{}: {}",
+                if existed { "Updated" } else { "Added" },
+                path.display()
+            );
+            if let search::AddOutcome::Truncated {
+                original_len,
+                stored_len,
+            } = outcome
+            {
+                eprintln!(
+                    "Warning: {} truncated to {stored_len} of {original_len} bytes",
+                    path.display()
+                );
+            }
+            Ok(if existed {
+                Applied::Updated
+            } else {
+                Applied::Added
+            })
+        }
+        // A file that stopped being text no longer belongs in the index
+        Ok(None) => {
+            if engine.remove_document(relative) {
+                println!("Removed: {} (no longer text)", path.display());
+                return Ok(Applied::Removed);
+            }
+            Ok(Applied::Unchanged)
+        }
+        Err(e) => {
+            eprintln!("Error: Failed to read {}: {e}", path.display());
+            Ok(Applied::Unchanged)
+        }
+    }
+}
+
+fn apply_changes(
+    engine: &mut search::SearchEngine,
+    dir: &Path,
+    detector: &mut TextDetector,
+    changes: &[watch::Change],
+) -> SnapResult<()> {
+    if changes.is_empty() {
         return Ok(());
     }
 
-    println!("\nIndexing completed:");
-    println!("- Files indexed: {total_files}");
-    let (_, _, dirs) = crawler.progress();
-    println!("- Directories processed: {dirs}");
+    for change in changes {
+        apply_change(engine, dir, detector, change)?;
+    }
+    engine.save(&get_index_path(dir))
+}
 
+fn update_directory(dir: &Path, limits: &CrawlLimits) -> SnapResult<()> {
     let index_path = get_index_path(dir);
-    engine.save(&index_path)?;
-    println!("- Index saved to {}", index_path.display());
+    if !index_path.exists() {
+        return Err(anyhow::Error::from(SnapError::with_code(
+            format!(
+                "No index found in {0}. Create one with `snap index {0}`.",
+                dir.display()
+            ),
+            search::ERROR_INVALID_INDEX,
+        )));
+    }
+    let mut engine = search::SearchEngine::load(&index_path)?;
+
+    // Crawl what `snap index` did; `--hidden` and `--min-size` still widen
+    // or replace the stored filters
+    let mut config = limits.config();
+    let mut extensions = Vec::new();
+    if let Some(filters) = &engine.metadata().filters {
+        config.skip_hidden &= filters.skip_hidden;
+        if limits.min_file_size == 0 {
+            config.min_file_size = filters.min_file_size;
+        }
+        extensions.extend(filters.extensions.iter().map(String::as_str));
+    }
+    let mut crawler = crawler::Crawler::with_config(dir, config)?
+        .with_error_recovery(true)
+        .with_extensions(&extensions)?;
+    indexer::exclude_index_files(&mut crawler)?;
+    let mut files = crawler.collect_all()?;
+    report_crawl_errors(&mut crawler);
+    files.sort_unstable();
+    let filters = crawler.filters();
+    let filters_changed = engine.metadata().filters.as_ref() != Some(&filters);
+    engine.set_crawl_filters(filters);
+
+    // Removals first so their slots can go to new files
+    let mut changes = Vec::new();
+    for (path, _) in engine.documents() {
+        let path = dir.join(path);
+        if files.binary_search(&path).is_err() {
+            changes.push(watch::Change::Removed(path));
+        }
+    }
+    for path in files {
+        let relative = path.strip_prefix(dir).unwrap_or(&path);
+        let Some(stamp) = search::FileStamp::of(&path) else {
+            continue;
+        };
+        if !engine.is_stale(relative, stamp) {
+            continue;
+        }
+        if engine.contains_document(relative) {
+            changes.push(watch::Change::Modified(path));
+        } else {
+            changes.push(watch::Change::Added(path));
+        }
+    }
 
+    let (mut added, mut updated, mut removed, mut skipped) = (0, 0, 0, 0);
+    let mut detector = TextDetector::new();
+    for change in &changes {
+        match apply_change(&mut engine, dir, &mut detector, change)? {
+            Applied::Added => added += 1,
+            Applied::Updated => updated += 1,
+            Applied::Removed => removed += 1,
+            Applied::Skipped => skipped += 1,
+            Applied::Unchanged => {}
+        }
+    }
+
+    if added + updated + removed > 0 || filters_changed {
+        engine.save(&index_path)?;
+    }
+    println!("Added {added}, updated {updated}, removed {removed} documents.");
+    if skipped > 0 {
+        println!("Skipped {skipped} new files because the index is full");
+    }
     Ok(())
 }
 
-fn search_files(query: &str, dir: &Path) -> SnapResult<()> {
-    println!("Searching for: {query} in {}", dir.display());
+fn watch_directory(
+    dir: &Path,
+    max_content: usize,
+    debounce: Duration,
+    limits: &CrawlLimits,
+) -> SnapResult<()> {
+    // Snapshot first so changes made while indexing are picked up afterwards
+    let index_name = search::INDEX_FILE_NAME;
+    let lock_name = format!("{index_name}{}", lock::LOCK_SUFFIX);
+    let tmp_name = format!("{index_name}{}", lock::TMP_SUFFIX);
+    let mut watcher =
+        watch::Watcher::new(dir, limits.config(), &[index_name, &lock_name, &tmp_name])?
+            .with_debounce(debounce);
 
-    search::validate_query(query)?;
+    let mut engine = index_directory(dir, max_content, 1, Vec::new(), limits, OutputFormat::Text)?;
+    let mut detector = TextDetector::new();
+
+    catch_interrupt();
+    println!("\nWatching {} for changes (Ctrl-C to stop)", dir.display());
+
+    while !INTERRUPTED.load(Ordering::SeqCst) {
+        thread::sleep(watch::POLL_INTERVAL);
+        let now = Instant::now();
+        watcher.poll(now)?;
+        let changes = watcher.take_ready(now);
+        apply_changes(&mut engine, dir, &mut detector, &changes)?;
+    }
 
+    let changes = watcher.take_pending();
+    apply_changes(&mut engine, dir, &mut detector, &changes)?;
+    println!("\nStopped watching {}", dir.display());
+
+    Ok(())
+}
+
+fn open_engine(dir: &Path) -> SnapResult<search::SearchEngine> {
+    let index_path = get_index_path(dir);
+    match search::SearchEngine::load(&index_path) {
+        Ok(loaded) => return Ok(loaded),
+        Err(e) if index_path.exists() => {
+            eprintln!(
+                "Warning: Ignoring unreadable index {}: {e}",
+                index_path.display()
+            );
+        }
+        Err(_) => {}
+    }
+
+    // Same pipeline as `snap index`, so live and indexed searches agree
+    eprintln!("No index found, searching live (slower)");
+    let run = indexer::build_index(dir, &indexer::IndexConfig::default(), &mut NoProgress)?;
+    Ok(run.engine)
+}
+
+fn check_dir(dir: &Path) -> SnapResult<()> {
     if !dir.exists() {
         return Err(anyhow::Error::from(SnapError::with_code(
             format!("Directory not found: {}", dir.display()),
@@ -140,39 +701,676 @@ fn search_files(query: &str, dir: &Path) -> SnapResult<()> {
         )));
     }
 
-    let engine = if let Ok(loaded) = search::SearchEngine::load(&get_index_path(dir)) {
-        loaded
+    Ok(())
+}
+
+/// Opens the engine for each directory. A lone directory is indexed on the fly
+/// if needed; with several, directories without a usable index are skipped.
+fn open_engines(dirs: &[PathBuf]) -> SnapResult<Vec<(PathBuf, search::SearchEngine)>> {
+    if let [dir] = dirs {
+        check_dir(dir)?;
+        return Ok(vec![(dir.clone(), open_engine(dir)?)]);
+    }
+
+    let mut engines = Vec::with_capacity(dirs.len());
+    for dir in dirs {
+        let loaded = check_dir(dir).and_then(|()| search::SearchEngine::load(&get_index_path(dir)));
+        match loaded {
+            Ok(engine) => engines.push((dir.clone(), engine)),
+            Err(e) => eprintln!("Warning: Skipping {}: {e}", dir.display()),
+        }
+    }
+
+    if engines.is_empty() {
+        return Err(anyhow::Error::from(SnapError::with_code(
+            "None of the directories could be searched. Index them first with `snap index`.",
+            search::ERROR_INVALID_INDEX,
+        )));
+    }
+
+    Ok(engines)
+}
+
+const STALE_INDEX_AGE: Duration = Duration::from_secs(30 * 24 * 60 * 60);
+
+fn warn_if_stale(dir: &Path, metadata: &search::IndexMetadata) {
+    if let Some(age) = metadata
+        .age(std::time::SystemTime::now())
+        .filter(|age| *age >= STALE_INDEX_AGE)
+    {
+        let days = age.as_secs() / (24 * 60 * 60);
+        eprintln!(
+            "Warning: index for {} is {days} days old, consider re-indexing",
+            dir.display()
+        );
+    }
+}
+
+/// The 1-based `line` of `content`, without surrounding whitespace.
+fn line_text(content: &[u8], line: usize) -> String {
+    let text = content
+        .split(|&b| b == b'\n')
+        .nth(line.saturating_sub(1))
+        .unwrap_or_default();
+    String::from_utf8_lossy(text).trim().to_string()
+}
+
+/// Writes each path followed by a NUL byte, as the raw OS bytes so names
+/// that are not valid UTF-8 reach xargs unchanged.
+fn write_print0(out: &mut impl Write, paths: &[PathBuf]) -> io::Result<()> {
+    for path in paths {
+        #[cfg(unix)]
+        out.write_all(std::os::unix::ffi::OsStrExt::as_bytes(path.as_os_str()))?;
+        #[cfg(not(unix))]
+        out.write_all(path.as_os_str().as_encoded_bytes())?;
+        out.write_all(b"\0")?;
+    }
+    Ok(())
+}
+
+fn search_files(
+    query: &str,
+    dirs: &[PathBuf],
+    flags: &SearchFlags,
+    color: ColorChoice,
+) -> SnapResult<()> {
+    flags.output_format.check()?;
+    if flags.output_format.is_text() {
+        let names: Vec<_> = dirs.iter().map(|dir| dir.display().to_string()).collect();
+        // Keep stdout to bare results when it feeds another tool
+        if flags.print0 || flags.vimgrep {
+            eprintln!("Searching for: {query} in {}", names.join(", "));
+        } else {
+            println!("Searching for: {query} in {}", names.join(", "));
+        }
+    }
+
+    search::validate_query(query)?;
+
+    let options = search::SearchOptions {
+        use_stop_words: !flags.keep_stop_words,
+        ..if flags.fuzzy {
+            search::SearchOptions::fuzzy()
+        } else {
+            search::SearchOptions::default()
+        }
+    };
+    let mode = if flags.glob {
+        search::SearchMode::Glob
     } else {
-        let mut new_engine = search::SearchEngine::new();
-        let mut crawler = crawler::Crawler::new(dir)?;
+        search::SearchMode::Text
+    };
+    let engines: Vec<_> = open_engines(dirs)?
+        .into_iter()
+        .map(|(dir, engine)| (dir, engine.with_options(options).with_mode(mode)))
+        .collect();
+    for (dir, engine) in &engines {
+        warn_if_stale(dir, engine.metadata());
+    }
 
-        while let Some(files) = crawler.process_next()? {
-            for file in files {
-                if let Ok(content) = fs::read_to_string(&file) {
-                    new_engine.add_document(&file, &content)?;
-                }
+    let page = usize::from(flags.page) - 1;
+    let (results, total): (Vec<search::RootedResult>, usize) =
+        if let [(dir, engine)] = engines.as_slice() {
+            let (results, total) = engine.search_page(query, page, flags.page_size)?;
+            let results = results
+                .into_iter()
+                .map(|result| search::RootedResult {
+                    root: dir.clone(),
+                    result,
+                })
+                .collect();
+            (results, total)
+        } else {
+            search::validate_page(page, flags.page_size)?;
+            let mut sets = Vec::with_capacity(engines.len());
+            for (dir, engine) in &engines {
+                sets.push((dir.clone(), engine.search(query)?));
+            }
+            let merged = search::merge_results(&sets);
+            let total = merged.len();
+            let results = merged
+                .into_iter()
+                .skip(page * flags.page_size)
+                .take(flags.page_size)
+                .collect();
+            (results, total)
+        };
+    let mut explained = Vec::new();
+    if flags.explain {
+        for (dir, engine) in &engines {
+            for e in engine.search_explain(query)? {
+                explained.push((dir, e));
             }
         }
-        new_engine
-    };
+    }
+
+    if !flags.output_format.is_text() {
+        let matches: Vec<_> = results
+            .iter()
+            .map(|result| search::SearchResult {
+                path: result.resolved_path(),
+                score: result.result.score,
+            })
+            .collect();
+        return output::write_list(flags.output_format, &matches);
+    }
+
+    if flags.print0 {
+        let paths: Vec<_> = results
+            .iter()
+            .map(search::RootedResult::resolved_path)
+            .collect();
+        let mut out = io::stdout().lock();
+        write_print0(&mut out, &paths)?;
+        out.flush()?;
+        return Ok(());
+    }
 
-    let results = engine.search(query)?;
+    if flags.vimgrep {
+        let mut out = io::stdout().lock();
+        for result in &results {
+            let path = result.resolved_path();
+            let engine = engines.iter().find(|(dir, _)| *dir == result.root);
+            let located = engine.and_then(|(_, engine)| {
+                let location = engine.locate(query, &result.result.path)?;
+                let doc = engine.get_document(&result.result.path)?;
+                Some((location, doc))
+            });
+            match located {
+                Some((location, doc)) => writeln!(
+                    out,
+                    "{}:{}:{}: {}",
+                    path.display(),
+                    location.line,
+                    location.column,
+                    line_text(&doc.content, location.line)
+                )?,
+                None => writeln!(out, "{}", path.display())?,
+            }
+        }
+        out.flush()?;
+        return Ok(());
+    }
 
-    if results.is_empty() {
+    if total == 0 {
         println!("\nNo matches found for query: {query}");
         println!("Tips:");
         println!("  - Try using simpler search terms");
         println!("  - Check if the files exist in the directory");
         println!("  - Make sure you have read permissions for the files");
+        if flags.glob && !query.contains('/') && !query.contains('.') {
+            println!("  - --glob matches file paths; drop it to search file contents");
+        }
         return Ok(());
     }
 
-    println!("\nFound {} matches:", results.len());
+    println!("\nFound {total} matches:");
+    if results.len() < total {
+        let first = page * flags.page_size + 1;
+        if results.is_empty() {
+            println!("Page {} is past the last result", flags.page);
+            return Ok(());
+        }
+        println!(
+            "Showing {first}-{} (page {})",
+            first + results.len() - 1,
+            flags.page
+        );
+    }
     println!("Score | Path");
     println!("------|------");
 
+    let color = color.enabled();
     for result in results {
-        println!("{:>5.1}% | {}", result.score, result.path.display());
+        let shown = result.resolved_path().display().to_string();
+        let shown = match engines.iter().find(|(dir, _)| *dir == result.root) {
+            Some((_, engine)) if color => {
+                // The stored path is the tail of what is shown, after the root
+                let stored = result.result.path.to_string_lossy();
+                let ranges = engine.path_match_ranges(query, &result.result.path);
+                match shown.len().checked_sub(stored.len()) {
+                    Some(offset) if shown.ends_with(&*stored) => highlight(&shown, offset, &ranges),
+                    _ => shown,
+                }
+            }
+            _ => shown,
+        };
+        println!("{} | {shown}", format_score(result.result.score, color));
+        let explained = explained
+            .iter()
+            .find(|(dir, e)| **dir == result.root && e.result.path == result.result.path)
+            .map(|(_, e)| e);
+        if let Some(explained) = explained {
+            for term in &explained.terms {
+                let text = query.split_whitespace().nth(term.term_index).unwrap_or("");
+                let location = match (term.matched_in_path, term.matched_in_content) {
+                    (true, true) => "path+content",
+                    (true, false) => "path",
+                    (false, true) => "content",
+                    (false, false) => "no match",
+                };
+                println!(
+                    "      |   {text:<12} {location:<12} +{:.1}",
+                    term.contribution
+                );
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn explain_file(query: &str, file: &Path, dir: &Path) -> SnapResult<()> {
+    search::validate_query(query)?;
+
+    let engine = open_engine(dir)?;
+    let relative = file.strip_prefix(dir).unwrap_or(file);
+    let explanation = engine.explain_score(query, relative)?;
+
+    println!("Query: {query}");
+    println!("File:  {}", dir.join(relative).display());
+    println!("Score: {:.1}%", explanation.total_score);
+
+    if explanation.term_scores.is_empty() {
+        println!("\nNo scorable terms in the query (all were wildcards).");
+        return Ok(());
+    }
+
+    println!("\nTerm         | Path | Content | Contribution");
+    println!("-------------|------|---------|-------------");
+    for term in &explanation.term_scores {
+        let hit = |matched: bool| if matched { "yes" } else { "no" };
+        println!(
+            "{:<12} | {:<4} | {:<7} | +{:.1}",
+            term.term.as_str(),
+            hit(term.path_hit),
+            hit(term.content_hit),
+            term.contribution
+        );
+    }
+
+    Ok(())
+}
+
+fn grep_stdin(query: &str, mode: SplitMode, fuzzy: bool) -> SnapResult<()> {
+    search::validate_query(query)?;
+
+    let mut input = Vec::new();
+    io::stdin()
+        .lock()
+        .take(crawler::MAX_FILE_SIZE + 1)
+        .read_to_end(&mut input)?;
+    if input.len() as u64 > crawler::MAX_FILE_SIZE {
+        return Err(anyhow::Error::from(SnapError::with_code(
+            format!("Input exceeds the {} byte limit", crawler::MAX_FILE_SIZE),
+            crawler::ERROR_FILE_SIZE_EXCEEDED,
+        )));
+    }
+    let text = String::from_utf8_lossy(&input);
+
+    let distance = if fuzzy {
+        search::SearchOptions::fuzzy().max_edit_distance
+    } else {
+        0
+    };
+
+    // The engine holds a bounded number of documents, so search in batches.
+    // Labels are not part of the input, so only content is scored.
+    let mut matches = Vec::new();
+    for batch in split::split(&text, mode).chunks(search::MAX_DOCUMENTS) {
+        let mut engine = search::SearchEngine::builder()
+            .weights(0.0, 1.0)?
+            .fuzzy_distance(distance.into())
+            .max_content_length(search::MAX_CONTENT_LIMIT)?
+            .build();
+        let mut offsets = Vec::with_capacity(batch.len());
+        for chunk in batch {
+            let label = match mode {
+                SplitMode::Bytes(_) => format!("<stdin>@{}", chunk.offset),
+                SplitMode::Lines | SplitMode::Paragraphs => format!("<stdin>:{}", chunk.line),
+            };
+            engine.add_document_truncating(Path::new(&label), chunk.text)?;
+            offsets.push((PathBuf::from(label), chunk.offset));
+        }
+
+        for result in engine.search(query)? {
+            let offset = offsets
+                .iter()
+                .find(|(label, _)| *label == result.path)
+                .map_or(0, |(_, offset)| *offset);
+            matches.push((result, offset));
+        }
+    }
+
+    if matches.is_empty() {
+        println!("No matches found for query: {query}");
+        return Ok(());
+    }
+
+    matches.sort_by(|a, b| {
+        b.0.score
+            .partial_cmp(&a.0.score)
+            .unwrap_or(std::cmp::Ordering::Equal)
+    });
+    matches.truncate(search::MAX_RESULTS);
+
+    println!("Score | Offset | Location");
+    println!("------|--------|---------");
+    for (result, offset) in matches {
+        println!(
+            "{:>5.1}% | {offset:>6} | {}",
+            result.score,
+            result.path.display()
+        );
+    }
+
+    Ok(())
+}
+
+/// What `snap stats` reports, printed as text or emitted as JSON.
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+struct StatsReport<'a> {
+    index_path: &'a Path,
+    index_file_bytes: u64,
+    #[cfg_attr(feature = "serde", serde(flatten))]
+    stats: &'a search::IndexStats,
+    metadata: &'a search::IndexMetadata,
+}
+
+fn show_stats(dir: &Path, format: OutputFormat) -> SnapResult<()> {
+    format.check()?;
+    let index_path = get_index_path(dir);
+    // A missing index is an answer, not a failure
+    let Ok(index_file) = fs::metadata(&index_path) else {
+        let message = format!(
+            "No index found in {0}. Create one with `snap index {0}`.",
+            dir.display()
+        );
+        if format.is_text() {
+            println!("{message}");
+        } else {
+            eprintln!("{message}");
+        }
+        return Ok(());
+    };
+    let engine = search::SearchEngine::load(&index_path)?;
+    let stats = engine.stats();
+    let report = StatsReport {
+        index_path: &index_path,
+        index_file_bytes: index_file.len(),
+        stats: &stats,
+        metadata: engine.metadata(),
+    };
+    if !format.is_text() {
+        return output::write_object(&report);
+    }
+
+    let (stats, metadata) = (report.stats, report.metadata);
+    println!("Index: {}", report.index_path.display());
+    println!("- Format version: {}", stats.index_version);
+    println!("- Index file size: {} bytes", report.index_file_bytes);
+    if !metadata.tool_version.is_empty() {
+        println!("- Written by: snapfind {}", metadata.tool_version);
+    }
+    if let Some(root) = &metadata.root {
+        println!("- Root: {}", root.display());
+    }
+    if let Some(age) = metadata.age(std::time::SystemTime::now()) {
+        println!("- Created: {} days ago", age.as_secs() / (24 * 60 * 60));
+    }
+    println!(
+        "- Documents: {} of {}",
+        stats.document_count,
+        search::MAX_DOCUMENTS
+    );
+    if stats.is_near_capacity() {
+        println!("  Warning: the index is nearly full");
+    }
+    if stats.document_count == 0 {
+        return Ok(());
+    }
+
+    let mut min = usize::MAX;
+    let mut max = 0;
+    let mut longest_path = Path::new("");
+    for (path, len) in engine.document_lengths() {
+        min = min.min(len);
+        max = max.max(len);
+        if path.as_os_str().len() > longest_path.as_os_str().len() {
+            longest_path = path;
+        }
+    }
+
+    println!("- Stored content: {} bytes", stats.total_content_bytes);
+    println!(
+        "- Content length: avg {:.0}, min {min}, max {max} bytes (limit {})",
+        stats.avg_content_bytes,
+        engine.max_content_length()
+    );
+    if let Some(largest) = &stats.largest_document {
+        println!("- Largest document: {}", largest.display());
+    }
+    println!("- Longest path: {}", longest_path.display());
+
+    Ok(())
+}
+
+/// `time` as `YYYY-MM-DD HH:MM` in UTC; times before the epoch print as `-`.
+fn format_utc(time: std::time::SystemTime) -> String {
+    let Ok(elapsed) = time.duration_since(std::time::UNIX_EPOCH) else {
+        return "-".to_string();
+    };
+    let secs = elapsed.as_secs();
+    let (days, secs) = (secs / 86_400, secs % 86_400);
+
+    // Civil date from days since 1970-01-01, after Howard Hinnant
+    let z = days + 719_468;
+    let era = z / 146_097;
+    let doe = z % 146_097;
+    let yoe = (doe - doe / 1_460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + u64::from(month <= 2);
+
+    format!(
+        "{year:04}-{month:02}-{day:02} {:02}:{:02}",
+        secs / 3_600,
+        secs % 3_600 / 60
+    )
+}
+
+fn list_documents(dir: &Path, long: bool, filter: Option<&str>) -> SnapResult<()> {
+    let index_path = get_index_path(dir);
+    if !index_path.exists() {
+        return Err(anyhow::Error::from(SnapError::with_code(
+            format!(
+                "No index found in {0}. Create one with `snap index {0}`.",
+                dir.display()
+            ),
+            search::ERROR_INVALID_INDEX,
+        )));
+    }
+    let engine = search::SearchEngine::load(&index_path)?;
+
+    let filter = filter.map(|ext| ext.trim_start_matches('.'));
+    let mut documents: Vec<_> = engine
+        .documents()
+        .filter(|(path, _)| filter.is_none_or(|ext| path.extension().is_some_and(|e| e == ext)))
+        .collect();
+    documents.sort_unstable_by_key(|(path, _)| *path);
+
+    let mut out = io::stdout().lock();
+    for (path, content) in documents {
+        if long {
+            let modified = engine
+                .get_document(path)
+                .and_then(|doc| doc.stamp)
+                .map_or_else(|| "-".to_string(), |stamp| format_utc(stamp.modified));
+            writeln!(
+                out,
+                "{:>8} {modified:>16} {}",
+                content.len(),
+                path.display()
+            )?;
+        } else {
+            writeln!(out, "{}", path.display())?;
+        }
+    }
+    out.flush()?;
+    Ok(())
+}
+
+#[cfg(feature = "serde")]
+fn export_index(dir: &Path, out: Option<&Path>) -> SnapResult<()> {
+    use snapfind::export::ExportedIndex;
+
+    let index_path = get_index_path(dir);
+    if !index_path.exists() {
+        return Err(anyhow::Error::from(SnapError::with_code(
+            format!(
+                "No index found in {0}. Create one with `snap index {0}`.",
+                dir.display()
+            ),
+            search::ERROR_INVALID_INDEX,
+        )));
+    }
+    let exported = ExportedIndex::from_engine(&search::SearchEngine::load(&index_path)?);
+
+    match out {
+        Some(out) => {
+            let mut file = io::BufWriter::new(fs::File::create(out)?);
+            exported.write_json(&mut file)?;
+            writeln!(file)?;
+            file.flush()?;
+            println!(
+                "Exported {} documents to {}",
+                exported.documents.len(),
+                out.display()
+            );
+        }
+        None => {
+            let mut stdout = io::stdout().lock();
+            exported.write_json(&mut stdout)?;
+            writeln!(stdout)?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(feature = "serde")]
+fn import_index(file: &Path, dir: &Path) -> SnapResult<()> {
+    use snapfind::export::ExportedIndex;
+
+    let reader = fs::File::open(file).map_err(|e| {
+        SnapError::with_code(
+            format!("Failed to open {}: {e}", file.display()),
+            search::ERROR_INVALID_INDEX,
+        )
+    })?;
+    let import = ExportedIndex::read_json(io::BufReader::new(reader))?.into_engine()?;
+
+    for rejection in &import.rejected {
+        eprintln!(
+            "Warning: rejected record {} ({}): {} (error {})",
+            rejection.record, rejection.path, rejection.reason, rejection.code
+        );
+    }
+    let index_path = get_index_path(dir);
+    import.engine.save(&index_path)?;
+    println!(
+        "Imported {} documents into {} ({} rejected)",
+        import.engine.document_count(),
+        index_path.display(),
+        import.rejected.len()
+    );
+    Ok(())
+}
+
+#[cfg(not(feature = "serde"))]
+fn export_index(_dir: &Path, _out: Option<&Path>) -> SnapResult<()> {
+    output::require_serde("export")
+}
+
+#[cfg(not(feature = "serde"))]
+fn import_index(_file: &Path, _dir: &Path) -> SnapResult<()> {
+    output::require_serde("import")
+}
+
+/// Every index file under `dir`, sorted. Unlike an indexing crawl this has
+/// no file count or size limits, reads hidden and ignored directories, and
+/// skips what it cannot read.
+fn find_index_files(dir: &Path) -> Vec<PathBuf> {
+    let mut found = Vec::new();
+    let mut pending = vec![dir.to_path_buf()];
+    while let Some(current) = pending.pop() {
+        let entries = match fs::read_dir(&current) {
+            Ok(entries) => entries,
+            Err(e) => {
+                eprintln!("Warning: Skipping {}: {e}", current.display());
+                continue;
+            }
+        };
+        for entry in entries.flatten() {
+            // Symlinks are not followed, as in a crawl
+            let Ok(file_type) = entry.file_type() else {
+                continue;
+            };
+            if file_type.is_dir() {
+                pending.push(entry.path());
+            } else if file_type.is_file() && entry.file_name() == search::INDEX_FILE_NAME {
+                found.push(entry.path());
+            }
+        }
+    }
+    found.sort_unstable();
+    found
+}
+
+fn clean_indexes(dir: &Path, recursive: bool, dry_run: bool) -> SnapResult<()> {
+    let candidates: Vec<PathBuf> = if recursive {
+        find_index_files(dir)
+    } else {
+        let index_path = get_index_path(dir);
+        index_path
+            .is_file()
+            .then_some(index_path)
+            .into_iter()
+            .collect()
+    };
+
+    let mut removed = 0;
+    let mut reclaimed = 0;
+    let mut failed = 0;
+
+    for path in candidates {
+        if !search::has_index_magic(&path) {
+            println!("Skipped: {} (not a snapfind index)", path.display());
+            continue;
+        }
+
+        let size = fs::metadata(&path).map_or(0, |m| m.len());
+        if dry_run {
+            println!("Would remove: {} ({size} bytes)", path.display());
+        } else if let Err(e) = fs::remove_file(&path) {
+            eprintln!("Error: Failed to remove {}: {e}", path.display());
+            failed += 1;
+            continue;
+        } else {
+            println!("Removed: {} ({size} bytes)", path.display());
+        }
+        removed += 1;
+        reclaimed += size;
+    }
+
+    if dry_run {
+        println!("\nDry run: {removed} index files, {reclaimed} bytes would be reclaimed");
+    } else {
+        println!("\nRemoved {removed} index files, reclaimed {reclaimed} bytes");
+    }
+    if failed > 0 {
+        println!("Failed to remove {failed} index files");
     }
 
     Ok(())
@@ -180,51 +1378,218 @@ fn search_files(query: &str, dir: &Path) -> SnapResult<()> {
 
 fn main() {
     let cli = Cli::parse();
+    let format = cli.command.output_format();
 
     let result = match cli.command {
-        Command::Index { dir } => {
-            if !dir.exists() {
-                Err(anyhow::Error::from(SnapError::with_code(
-                    format!("Directory not found: {}", dir.display()),
-                    search::ERROR_INVALID_INDEX,
-                )))
-            } else if !dir.is_dir() {
-                Err(anyhow::Error::from(SnapError::with_code(
-                    format!("Not a directory: {}", dir.display()),
-                    search::ERROR_INVALID_INDEX,
-                )))
-            } else {
-                index_directory(&dir)
-            }
+        Command::Index {
+            dir,
+            max_content,
+            output_format,
+            jobs,
+            extensions,
+            limits,
+        } => check_dir(&dir).and_then(|()| {
+            index_directory(&dir, max_content, jobs, extensions, &limits, output_format).map(|_| ())
+        }),
+        Command::Watch {
+            dir,
+            max_content,
+            debounce,
+            limits,
+        } => check_dir(&dir).and_then(|()| {
+            watch_directory(&dir, max_content, Duration::from_millis(debounce), &limits)
+        }),
+        Command::Search { query, dirs, flags } => search::validate_query(&query)
+            .and_then(|()| search_files(&query, &dirs, &flags, cli.color)),
+        Command::Explain { query, file, dir } => {
+            check_dir(&dir).and_then(|()| explain_file(&query, &file, &dir))
         }
-        Command::Search { query, dir } => {
-            if !dir.exists() {
-                Err(anyhow::Error::from(SnapError::with_code(
-                    format!("Directory not found: {}", dir.display()),
-                    search::ERROR_INVALID_INDEX,
-                )))
-            } else if !dir.is_dir() {
-                Err(anyhow::Error::from(SnapError::with_code(
-                    format!("Not a directory: {}", dir.display()),
-                    search::ERROR_INVALID_INDEX,
-                )))
-            } else if query.is_empty() {
-                Err(anyhow::Error::from(SnapError::with_code(
-                    "Search query cannot be empty",
-                    search::ERROR_INVALID_QUERY,
-                )))
-            } else {
-                search_files(&query, &dir)
-            }
+        Command::Grep {
+            query,
+            split,
+            fuzzy,
+        } => grep_stdin(&query, split, fuzzy),
+        Command::Update { dir, limits } => {
+            check_dir(&dir).and_then(|()| update_directory(&dir, &limits))
+        }
+        Command::Stats { dir, output_format } => {
+            check_dir(&dir).and_then(|()| show_stats(&dir, output_format))
         }
+        Command::List { dir, long, filter } => {
+            check_dir(&dir).and_then(|()| list_documents(&dir, long, filter.as_deref()))
+        }
+        Command::Export { dir, out } => {
+            check_dir(&dir).and_then(|()| export_index(&dir, out.as_deref()))
+        }
+        Command::Import { file, dir } => check_dir(&dir).and_then(|()| import_index(&file, &dir)),
+        Command::Completions { shell } => {
+            // Generated into memory so a closed stdout is an error, not a panic
+            let mut script = Vec::new();
+            clap_complete::generate(
+                shell,
+                &mut Cli::command(),
+                env!("CARGO_BIN_NAME"),
+                &mut script,
+            );
+            io::stdout()
+                .lock()
+                .write_all(&script)
+                .map_err(anyhow::Error::from)
+        }
+        Command::Clean {
+            dir,
+            recursive,
+            dry_run,
+        } => check_dir(&dir).and_then(|()| clean_indexes(&dir, recursive, dry_run)),
     };
 
     if let Err(e) = result {
-        eprintln!("{}", e);
-        if let Some(err) = e.downcast_ref::<snapfind::error::SnapError>() {
-            process::exit(err.code());
+        let snap_error = e.downcast_ref::<SnapError>();
+        if format.is_text() {
+            match snap_error {
+                Some(snap_error) => eprintln!("{}", snap_error.user_message()),
+                None => eprintln!("{e}"),
+            }
         } else {
-            process::exit(1);
+            output::write_error(&e, snap_error.map_or(1, SnapError::code));
+        }
+        process::exit(snap_error.map_or(1, SnapError::exit_code));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tempfile::TempDir;
+
+    use super::*;
+
+    #[test]
+    fn test_print0_parses_with_page_size() {
+        let cli =
+            Cli::try_parse_from(["snapfind", "search", "-0", "--page-size", "2", "log"]).unwrap();
+        let Command::Search { flags, .. } = cli.command else {
+            panic!("expected the search command");
+        };
+        assert!(flags.print0);
+        assert_eq!(flags.page_size, 2);
+    }
+
+    #[test]
+    fn test_print0_respects_page_size() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut engine = search::SearchEngine::new();
+        for name in ["a.log", "b.log", "c.log"] {
+            engine.add_document(Path::new(name), "log").unwrap();
+        }
+
+        let (results, total) = engine.search_page("log", 0, 2).unwrap();
+        assert_eq!(total, 3);
+        let paths: Vec<_> = results
+            .iter()
+            .map(|result| result.resolved_path(temp_dir.path()))
+            .collect();
+
+        let mut out = Vec::new();
+        write_print0(&mut out, &paths).unwrap();
+        assert_eq!(out.iter().filter(|&&b| b == 0).count(), 2);
+        assert!(out.ends_with(b"\0"));
+    }
+
+    #[test]
+    fn test_color_is_global() {
+        for args in [
+            ["snapfind", "--color", "never", "search", "log"],
+            ["snapfind", "search", "log", "--color", "never"],
+        ] {
+            assert_eq!(Cli::try_parse_from(args).unwrap().color, ColorChoice::Never);
         }
     }
+
+    #[test]
+    fn test_score_colors() {
+        assert_eq!(format_score(92.5, false), " 92.5%");
+        assert_eq!(format_score(80.0, true), "\x1b[32m 80.0%\x1b[0m");
+        assert_eq!(format_score(50.0, true), "\x1b[33m 50.0%\x1b[0m");
+        assert_eq!(format_score(49.9, true), "\x1b[31m 49.9%\x1b[0m");
+    }
+
+    #[test]
+    fn test_format_utc() {
+        let at = |secs| std::time::UNIX_EPOCH + Duration::from_secs(secs);
+        assert_eq!(format_utc(at(0)), "1970-01-01 00:00");
+        assert_eq!(format_utc(at(951_782_400)), "2000-02-29 00:00");
+        assert_eq!(format_utc(at(1_700_000_000)), "2023-11-14 22:13");
+        assert_eq!(format_utc(at(4_107_542_399)), "2100-02-28 23:59");
+        assert_eq!(
+            format_utc(std::time::UNIX_EPOCH - Duration::from_secs(1)),
+            "-"
+        );
+    }
+
+    #[test]
+    fn test_line_text() {
+        let content = b"intro\r\n  then deploy it  \nlast";
+        assert_eq!(line_text(content, 1), "intro");
+        assert_eq!(line_text(content, 2), "then deploy it");
+        assert_eq!(line_text(content, 3), "last");
+        assert_eq!(line_text(content, 4), "");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_print0_keeps_invalid_utf8() {
+        use std::ffi::OsStr;
+        use std::os::unix::ffi::OsStrExt;
+
+        let name = OsStr::from_bytes(b"bad\xffname.log");
+        let mut out = Vec::new();
+        write_print0(&mut out, &[PathBuf::from(name)]).unwrap();
+        assert_eq!(out, b"bad\xffname.log\0");
+    }
+
+    #[test]
+    fn test_apply_change_skips_new_files_when_full() {
+        let temp_dir = TempDir::new().unwrap();
+        let dir = temp_dir.path();
+        let mut engine = search::SearchEngine::new();
+        for i in 0..search::MAX_DOCUMENTS {
+            engine
+                .add_document(Path::new(&format!("doc{i}.txt")), "old")
+                .unwrap();
+        }
+        let mut detector = TextDetector::new();
+
+        let new_file = dir.join("new.txt");
+        fs::write(&new_file, "fresh text\nmore\n").unwrap();
+        let change = watch::Change::Added(new_file);
+        assert_eq!(
+            apply_change(&mut engine, dir, &mut detector, &change).unwrap(),
+            Applied::Skipped
+        );
+        assert_eq!(engine.document_count(), search::MAX_DOCUMENTS);
+
+        let existing = dir.join("doc0.txt");
+        fs::write(&existing, "new content\nhere\n").unwrap();
+        let change = watch::Change::Modified(existing);
+        assert_eq!(
+            apply_change(&mut engine, dir, &mut detector, &change).unwrap(),
+            Applied::Updated
+        );
+    }
+
+    #[test]
+    fn test_apply_change_ignores_binary_files() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut engine = search::SearchEngine::new();
+        let mut detector = TextDetector::new();
+
+        let file = temp_dir.path().join("archive.gz");
+        fs::write(&file, b"\x1F\x8B\x08\x00text-like\n").unwrap();
+        let change = watch::Change::Added(file);
+        assert_eq!(
+            apply_change(&mut engine, temp_dir.path(), &mut detector, &change).unwrap(),
+            Applied::Unchanged
+        );
+        assert!(engine.is_empty());
+    }
 }
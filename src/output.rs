@@ -0,0 +1,92 @@
+use clap::ValueEnum;
+use snapfind::error::{ErrorCode, SnapError, SnapResult};
+
+pub const ERROR_JSON_UNSUPPORTED: i32 = ErrorCode::JsonUnsupported.code();
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum)]
+pub enum OutputFormat {
+    /// Human-readable text
+    #[default]
+    Text,
+    /// A single JSON document
+    Json,
+    /// One JSON object per line
+    Jsonl,
+}
+
+impl OutputFormat {
+    pub const fn is_text(self) -> bool {
+        matches!(self, Self::Text)
+    }
+
+    /// Fails up front when JSON is requested from a build without serde, so
+    /// no work is done before the output could be written.
+    pub fn check(self) -> SnapResult<()> {
+        if self.is_text() || cfg!(feature = "serde") {
+            return Ok(());
+        }
+        Err(anyhow::Error::from(SnapError::with_code(
+            "JSON output requires snapfind to be built with `--features serde`",
+            ERROR_JSON_UNSUPPORTED,
+        )))
+    }
+}
+
+/// Fails for a command that reads or writes JSON in a build without serde.
+#[cfg(not(feature = "serde"))]
+pub fn require_serde(command: &str) -> SnapResult<()> {
+    Err(anyhow::Error::from(SnapError::with_code(
+        format!("`snap {command}` requires snapfind to be built with `--features serde`"),
+        ERROR_JSON_UNSUPPORTED,
+    )))
+}
+
+/// Writes `items` as one JSON array, or as one object per line for `jsonl`.
+#[cfg(feature = "serde")]
+pub fn write_list<T: serde::Serialize>(format: OutputFormat, items: &[T]) -> SnapResult<()> {
+    use std::io::Write;
+
+    let mut out = std::io::stdout().lock();
+    if format == OutputFormat::Jsonl {
+        for item in items {
+            serde_json::to_writer(&mut out, item)?;
+            writeln!(out)?;
+        }
+    } else {
+        serde_json::to_writer(&mut out, items)?;
+        writeln!(out)?;
+    }
+    out.flush()?;
+    Ok(())
+}
+
+#[cfg(not(feature = "serde"))]
+pub fn write_list<T>(format: OutputFormat, _items: &[T]) -> SnapResult<()> {
+    format.check()
+}
+
+/// Writes a single JSON object on its own line.
+#[cfg(feature = "serde")]
+pub fn write_object<T: serde::Serialize>(value: &T) -> SnapResult<()> {
+    let line = serde_json::to_string(value)?;
+    println!("{line}");
+    Ok(())
+}
+
+#[cfg(not(feature = "serde"))]
+pub fn write_object<T>(_value: &T) -> SnapResult<()> {
+    OutputFormat::Json.check()
+}
+
+/// Reports a failure as `{"error": ..., "code": ...}` on stdout, where JSON
+/// consumers are reading.
+#[cfg(feature = "serde")]
+pub fn write_error(error: &anyhow::Error, code: i32) {
+    let object = serde_json::json!({ "error": error.to_string(), "code": code });
+    println!("{object}");
+}
+
+#[cfg(not(feature = "serde"))]
+pub fn write_error(error: &anyhow::Error, _code: i32) {
+    eprintln!("{error}");
+}
@@ -0,0 +1,147 @@
+use arrayvec::ArrayVec;
+
+use super::error::{SnapError, SnapResult};
+use super::search::ERROR_INVALID_INDEX;
+
+pub const MIN_MATCH: usize = 3;
+pub const MAX_MATCH: usize = MIN_MATCH + 0x7F;
+pub const MAX_LITERAL_RUN: usize = 0x80;
+
+const MATCH_FLAG: u8 = 0x80;
+const HASH_SIZE: usize = 1024;
+
+#[must_use]
+pub fn compress<const N: usize>(input: &[u8]) -> Option<ArrayVec<u8, N>> {
+    assert!(input.len() <= usize::from(u16::MAX), "Input too large");
+
+    let mut out = ArrayVec::new();
+    let mut table = [usize::MAX; HASH_SIZE];
+    let mut literal_start = 0;
+    let mut i = 0;
+
+    while i + MIN_MATCH <= input.len() {
+        let slot = hash(&input[i..i + MIN_MATCH]);
+        let candidate = table[slot];
+        table[slot] = i;
+
+        if candidate == usize::MAX
+            || input[candidate..candidate + MIN_MATCH] != input[i..i + MIN_MATCH]
+        {
+            i += 1;
+            continue;
+        }
+
+        let mut len = MIN_MATCH;
+        while i + len < input.len() && len < MAX_MATCH && input[candidate + len] == input[i + len] {
+            len += 1;
+        }
+
+        push_literals(&mut out, &input[literal_start..i])?;
+        let offset = u16::try_from(i - candidate).ok()?;
+        out.try_push(MATCH_FLAG | u8::try_from(len - MIN_MATCH).ok()?)
+            .ok()?;
+        out.try_extend_from_slice(&offset.to_le_bytes()).ok()?;
+
+        i += len;
+        literal_start = i;
+    }
+
+    push_literals(&mut out, &input[literal_start..])?;
+    Some(out)
+}
+
+pub fn decompress<const N: usize>(input: &[u8]) -> SnapResult<ArrayVec<u8, N>> {
+    let corrupt = || {
+        anyhow::Error::from(SnapError::with_code(
+            "Corrupt compressed block",
+            ERROR_INVALID_INDEX,
+        ))
+    };
+
+    let mut out = ArrayVec::<u8, N>::new();
+    let mut i = 0;
+
+    while i < input.len() {
+        let tag = input[i];
+        i += 1;
+
+        if tag & MATCH_FLAG == 0 {
+            let run = usize::from(tag) + 1;
+            let literals = input.get(i..i + run).ok_or_else(corrupt)?;
+            out.try_extend_from_slice(literals).map_err(|_| corrupt())?;
+            i += run;
+        } else {
+            let len = usize::from(tag & !MATCH_FLAG) + MIN_MATCH;
+            let offset = input.get(i..i + 2).ok_or_else(corrupt)?;
+            let offset = usize::from(u16::from_le_bytes([offset[0], offset[1]]));
+            i += 2;
+
+            if offset == 0 || offset > out.len() {
+                return Err(corrupt());
+            }
+            for _ in 0..len {
+                let byte = out[out.len() - offset];
+                out.try_push(byte).map_err(|_| corrupt())?;
+            }
+        }
+    }
+
+    Ok(out)
+}
+
+fn push_literals<const N: usize>(out: &mut ArrayVec<u8, N>, literals: &[u8]) -> Option<()> {
+    for chunk in literals.chunks(MAX_LITERAL_RUN) {
+        out.try_push(u8::try_from(chunk.len() - 1).ok()?).ok()?;
+        out.try_extend_from_slice(chunk).ok()?;
+    }
+    Some(())
+}
+
+fn hash(bytes: &[u8]) -> usize {
+    let value = u32::from(bytes[0]) << 16 | u32::from(bytes[1]) << 8 | u32::from(bytes[2]);
+    (value.wrapping_mul(2_654_435_761) >> 22) as usize % HASH_SIZE
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CAP: usize = 1_000;
+
+    #[test]
+    fn test_round_trip_repetitive() {
+        let input = b"the quick brown fox the quick brown fox the quick brown fox".repeat(10);
+        let packed = compress::<CAP>(&input).unwrap();
+        assert!(packed.len() < input.len() / 4);
+
+        let unpacked = decompress::<CAP>(&packed).unwrap();
+        assert_eq!(&unpacked[..], &input[..]);
+    }
+
+    #[test]
+    fn test_round_trip_short_and_empty() {
+        for input in [&b""[..], b"a", b"ab", b"abc", b"aaaa"] {
+            let packed = compress::<CAP>(input).unwrap();
+            assert_eq!(&decompress::<CAP>(&packed).unwrap()[..], input);
+        }
+    }
+
+    #[test]
+    fn test_incompressible_overflows_capacity() {
+        let input: Vec<u8> = (0..=255).collect();
+        assert!(compress::<256>(&input).is_none());
+        assert!(compress::<CAP>(&input).is_some());
+    }
+
+    #[test]
+    fn test_decompress_rejects_bad_offset() {
+        let block = [0x00, b'a', MATCH_FLAG, 0x05, 0x00];
+        assert!(decompress::<CAP>(&block).is_err());
+    }
+
+    #[test]
+    fn test_decompress_rejects_truncated_literals() {
+        let block = [0x05, b'a', b'b'];
+        assert!(decompress::<CAP>(&block).is_err());
+    }
+}
@@ -1,130 +1,815 @@
+use std::collections::VecDeque;
+use std::ffi::OsString;
 use std::fs;
+use std::io;
 use std::path::{Path, PathBuf};
+#[cfg(feature = "async")]
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use std::time::SystemTime;
 
-use arrayvec::ArrayVec;
+use arrayvec::{ArrayString, ArrayVec};
 
-use super::error::{SnapError, SnapResult};
+use super::error::{ErrorCode, SnapError, SnapResult};
 
 pub const MAX_DEPTH: usize = 1_000;
 pub const MAX_FILES: usize = 1_000;
+/// Largest `max_depth` a crawler accepts; its buffers are sized from it.
+pub const MAX_DEPTH_LIMIT: usize = 10_000;
+/// Largest `max_files` a crawler accepts; its buffers are sized from it.
+pub const MAX_FILES_LIMIT: usize = 1_000_000;
+pub const MAX_PENDING_DIRS: usize = 10_000;
+/// Largest `max_pending_dirs` a crawler accepts; its queue is sized from it.
+pub const MAX_PENDING_DIRS_LIMIT: usize = 1_000_000;
 pub const MAX_FILE_SIZE: u64 = 10 * 1024 * 1024;
 pub const MAX_PATH_LENGTH: usize = 255;
+pub const MAX_EXTENSIONS: usize = 32;
+pub const MAX_EXTENSION_LENGTH: usize = 8;
+pub const MAX_EXCLUDED_NAMES: usize = 16;
+pub const MAX_ERRORS: usize = 100;
+pub const MAX_IGNORE_PATTERNS: usize = 64;
+pub const MAX_IGNORE_FILE_SIZE: u64 = 64 * 1024;
+/// Read from the crawl root; holds gitignore-style patterns to skip.
+pub const IGNORE_FILE_NAME: &str = ".snapfindignore";
 
-pub const ERROR_DEPTH_EXCEEDED: i32 = 201;
-pub const ERROR_FILE_COUNT_EXCEEDED: i32 = 202;
-pub const ERROR_FILE_SIZE_EXCEEDED: i32 = 203;
-pub const ERROR_PATH_TOO_LONG: i32 = 204;
+pub const ERROR_DEPTH_EXCEEDED: i32 = ErrorCode::DepthExceeded.code();
+pub const ERROR_FILE_COUNT_EXCEEDED: i32 = ErrorCode::FileCountExceeded.code();
+pub const ERROR_FILE_SIZE_EXCEEDED: i32 = ErrorCode::FileSizeExceeded.code();
+pub const ERROR_PATH_TOO_LONG: i32 = ErrorCode::CrawlPathTooLong.code();
+pub const ERROR_TOO_MANY_EXCLUSIONS: i32 = ErrorCode::TooManyExclusions.code();
+pub const ERROR_INVALID_CONFIG: i32 = ErrorCode::InvalidCrawlConfig.code();
+pub const ERROR_PENDING_DIRS_EXCEEDED: i32 = ErrorCode::PendingDirsExceeded.code();
+
+/// Limits applied while walking a directory tree.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CrawlerConfig {
+    pub max_files: usize,
+    pub max_depth: usize,
+    /// Directories found but not crawled yet. Breadth-first order holds a
+    /// whole level at once, so this bounds how wide a tree can be.
+    pub max_pending_dirs: usize,
+    pub max_file_size: u64,
+    /// Files smaller than this are skipped without an error. 0 keeps them all.
+    pub min_file_size: u64,
+    pub max_path_length: usize,
+    /// Fail on the first directory that cannot be read instead of skipping it.
+    pub strict: bool,
+    /// Skip files and directories whose name starts with `.`, such as `.git`.
+    /// The root itself is always crawled.
+    pub skip_hidden: bool,
+    /// Sort each directory's entries by name so the crawl order, and which
+    /// files fill the budget, does not depend on the filesystem.
+    pub sort_entries: bool,
+}
+
+/// Counters kept while crawling, readable at any point through
+/// [`Crawler::stats`].
+#[derive(Debug, Clone, Default)]
+pub struct CrawlStats {
+    extensions: ArrayVec<(ArrayString<MAX_EXTENSION_LENGTH>, usize), MAX_EXTENSIONS>,
+    pub other_extensions: usize,
+    pub skipped_too_large: usize,
+    pub skipped_too_small: usize,
+    pub skipped_non_text: usize,
+    /// Files returned in batches so far.
+    pub files_processed: usize,
+    /// Directories whose entries have been read.
+    pub dirs_processed: usize,
+    /// Combined size of the files returned so far.
+    pub bytes_visited: u64,
+    /// Entries and directories skipped because of recoverable errors.
+    pub errors_skipped: usize,
+    /// Deepest directory read, with the root at depth 0.
+    pub max_depth_reached: usize,
+}
+
+/// Which files a crawl keeps, saved with an index so that updating it
+/// crawls the same files. See [`Crawler::filters`].
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CrawlFilters {
+    /// Lowercase extensions without the dot; empty keeps every file.
+    pub extensions: Vec<String>,
+    pub skip_hidden: bool,
+    pub min_file_size: u64,
+}
+
+/// Patterns from the [`IGNORE_FILE_NAME`] file at the crawl root, in
+/// gitignore syntax. The last pattern matching a path decides, so `!pattern`
+/// re-includes what an earlier line excluded.
+#[derive(Debug, Clone, Default)]
+struct IgnoreRules {
+    rules: ArrayVec<IgnoreRule, MAX_IGNORE_PATTERNS>,
+}
+
+#[derive(Debug, Clone)]
+struct IgnoreRule {
+    matcher: globset::GlobMatcher,
+    negate: bool,
+    dir_only: bool,
+}
 
 #[derive(Debug)]
 pub struct Crawler {
-    queue: ArrayVec<(PathBuf, usize), MAX_DEPTH>,
-    file_count: usize,
+    root: PathBuf,
+    config: CrawlerConfig,
+    queue: VecDeque<(PathBuf, usize)>,
+    entries: Vec<fs::DirEntry>,
+    batch: Vec<PathBuf>,
     dir_count: usize,
+    stats: CrawlStats,
+    excluded_names: ArrayVec<OsString, MAX_EXCLUDED_NAMES>,
+    /// Lowercase extensions files must have; empty allows every file.
+    allowed_extensions: ArrayVec<ArrayString<MAX_EXTENSION_LENGTH>, MAX_EXTENSIONS>,
+    modified_after: Option<SystemTime>,
+    ignore: IgnoreRules,
+    error_recovery: bool,
+    errors: ArrayVec<(PathBuf, anyhow::Error), MAX_ERRORS>,
+    dropped_errors: usize,
+}
+
+impl Default for CrawlerConfig {
+    fn default() -> Self {
+        Self {
+            max_files: MAX_FILES,
+            max_depth: MAX_DEPTH,
+            max_pending_dirs: MAX_PENDING_DIRS,
+            max_file_size: MAX_FILE_SIZE,
+            min_file_size: 0,
+            max_path_length: MAX_PATH_LENGTH,
+            strict: false,
+            skip_hidden: true,
+            sort_entries: true,
+        }
+    }
+}
+
+impl CrawlerConfig {
+    fn validate(&self) -> SnapResult<()> {
+        let limits = [
+            ("max_files", self.max_files == 0),
+            ("max_depth", self.max_depth == 0),
+            ("max_pending_dirs", self.max_pending_dirs == 0),
+            ("max_file_size", self.max_file_size == 0),
+            ("max_path_length", self.max_path_length == 0),
+        ];
+        if let Some((name, _)) = limits.iter().find(|(_, is_zero)| *is_zero) {
+            return Err(anyhow::Error::from(SnapError::with_code(
+                format!("Crawler limit {name} must be greater than zero"),
+                ERROR_INVALID_CONFIG,
+            )));
+        }
+        let bounds = [
+            ("max_files", self.max_files, MAX_FILES_LIMIT),
+            ("max_depth", self.max_depth, MAX_DEPTH_LIMIT),
+            (
+                "max_pending_dirs",
+                self.max_pending_dirs,
+                MAX_PENDING_DIRS_LIMIT,
+            ),
+        ];
+        if let Some((name, value, limit)) = bounds.iter().find(|(_, value, limit)| value > limit) {
+            return Err(anyhow::Error::from(SnapError::with_code(
+                format!("Crawler limit {name} is {value}, at most {limit} is allowed"),
+                ERROR_INVALID_CONFIG,
+            )));
+        }
+        if self.min_file_size > self.max_file_size {
+            return Err(anyhow::Error::from(SnapError::with_code(
+                "Crawler limit min_file_size must not exceed max_file_size",
+                ERROR_INVALID_CONFIG,
+            )));
+        }
+        Ok(())
+    }
+
+    /// Entries of one directory that can be accepted: every remaining file plus
+    /// every directory the queue could still hold.
+    const fn entries_capacity(&self) -> usize {
+        self.max_files.saturating_add(self.max_pending_dirs)
+    }
+}
+
+impl CrawlStats {
+    #[must_use]
+    pub fn extension_count(&self, ext: &str) -> usize {
+        let key = Self::extension_key(ext);
+        self.extensions
+            .iter()
+            .find(|(e, _)| *e == key)
+            .map_or(0, |(_, count)| *count)
+    }
+
+    pub fn extensions(&self) -> impl Iterator<Item = (&str, usize)> {
+        self.extensions
+            .iter()
+            .map(|(e, count)| (e.as_str(), *count))
+    }
+
+    fn record_file(&mut self, path: &Path) {
+        let Some(ext) = path.extension().and_then(|e| e.to_str()) else {
+            self.other_extensions += 1;
+            return;
+        };
+
+        let key = Self::extension_key(ext);
+        if let Some((_, count)) = self.extensions.iter_mut().find(|(e, _)| *e == key) {
+            *count += 1;
+        } else if self.extensions.try_push((key, 1)).is_err() {
+            self.other_extensions += 1;
+        }
+    }
+
+    fn extension_key(ext: &str) -> ArrayString<MAX_EXTENSION_LENGTH> {
+        let mut key = ArrayString::new();
+        for c in ext.chars().map(|c| c.to_ascii_lowercase()) {
+            if key.try_push(c).is_err() {
+                break;
+            }
+        }
+        key
+    }
 }
 
 impl Crawler {
     pub fn new(start_path: &Path) -> SnapResult<Self> {
-        Self::validate_path(start_path)?;
+        Self::with_config(start_path, CrawlerConfig::default())
+    }
 
-        let mut queue = ArrayVec::new();
-        queue.try_push((start_path.to_path_buf(), 0)).map_err(|_| {
-            anyhow::Error::from(SnapError::with_code(
-                format!("Maximum directory depth of {MAX_DEPTH} exceeded"),
-                ERROR_DEPTH_EXCEEDED,
-            ))
-        })?;
+    /// Creates a crawler for `start_path`, reading its [`IGNORE_FILE_NAME`]
+    /// file if there is one.
+    pub fn with_config(start_path: &Path, config: CrawlerConfig) -> SnapResult<Self> {
+        config.validate()?;
+        Self::check_path_length(start_path, config.max_path_length)?;
+        let ignore = IgnoreRules::load(start_path)?;
+
+        // Buffers are sized once here and never grow while crawling
+        let mut queue = VecDeque::with_capacity(config.max_pending_dirs);
+        queue.push_back((start_path.to_path_buf(), 0));
 
         Ok(Self {
+            root: start_path.to_path_buf(),
+            config,
             queue,
-            file_count: 0,
+            entries: Vec::with_capacity(config.entries_capacity()),
+            batch: Vec::with_capacity(config.max_files),
             dir_count: 1,
+            stats: CrawlStats::default(),
+            excluded_names: ArrayVec::new(),
+            allowed_extensions: ArrayVec::new(),
+            modified_after: None,
+            ignore,
+            error_recovery: false,
+            errors: ArrayVec::new(),
+            dropped_errors: 0,
+        })
+    }
+
+    /// Records unreadable entries, oversized files and overlong paths instead
+    /// of failing the whole crawl on the first one.
+    #[must_use]
+    pub fn with_error_recovery(mut self, enabled: bool) -> Self {
+        self.error_recovery = enabled;
+        self
+    }
+
+    /// Skips files last modified before `time`. Files dated in the future, as
+    /// after clock skew, are still returned, as are files whose modification
+    /// time cannot be read.
+    #[must_use]
+    pub fn with_modified_after(mut self, time: Option<SystemTime>) -> Self {
+        self.modified_after = time;
+        self
+    }
+
+    /// Only returns files with one of `extensions`, compared without case.
+    /// Files without an extension are skipped. A leading `.` is ignored, so
+    /// `"rs"` and `".rs"` are the same.
+    pub fn with_extensions(mut self, extensions: &[&str]) -> SnapResult<Self> {
+        for ext in extensions {
+            let ext = ext.strip_prefix('.').unwrap_or(ext);
+            let invalid = |msg: &str| {
+                anyhow::Error::from(SnapError::with_code(
+                    format!("{msg}: {ext:?}"),
+                    ERROR_INVALID_CONFIG,
+                ))
+            };
+            if ext.is_empty() {
+                return Err(invalid("Extension must not be empty"));
+            }
+            let key = ArrayString::from(&ext.to_ascii_lowercase()).map_err(|_| {
+                invalid(&format!(
+                    "Extension is longer than {MAX_EXTENSION_LENGTH} bytes"
+                ))
+            })?;
+            if self.allowed_extensions.contains(&key) {
+                continue;
+            }
+            self.allowed_extensions
+                .try_push(key)
+                .map_err(|_| invalid(&format!("More than {MAX_EXTENSIONS} extensions")))?;
+        }
+        Ok(self)
+    }
+
+    /// Drains the errors collected since the last call: entries skipped with
+    /// error recovery, and directories that could not be read unless strict.
+    pub fn take_errors(&mut self) -> ArrayVec<(PathBuf, anyhow::Error), MAX_ERRORS> {
+        std::mem::take(&mut self.errors)
+    }
+
+    /// Errors that did not fit in the buffer and were only counted.
+    #[must_use]
+    pub const fn dropped_errors(&self) -> usize {
+        self.dropped_errors
+    }
+
+    pub fn exclude_name(&mut self, name: &str) -> SnapResult<()> {
+        if self.excluded_names.iter().any(|n| n == name) {
+            return Ok(());
+        }
+        self.excluded_names.try_push(name.into()).map_err(|_| {
+            anyhow::Error::from(SnapError::with_code(
+                format!("Maximum of {MAX_EXCLUDED_NAMES} excluded names exceeded"),
+                ERROR_TOO_MANY_EXCLUSIONS,
+            ))
         })
     }
 
+    #[must_use]
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    #[must_use]
+    pub const fn config(&self) -> &CrawlerConfig {
+        &self.config
+    }
+
+    /// The filters this crawler applies, for storing with an index.
+    #[must_use]
+    pub fn filters(&self) -> CrawlFilters {
+        CrawlFilters {
+            extensions: self
+                .allowed_extensions
+                .iter()
+                .map(|ext| ext.to_string())
+                .collect(),
+            skip_hidden: self.config.skip_hidden,
+            min_file_size: self.config.min_file_size,
+        }
+    }
+
     #[must_use = "Progress information should be used for monitoring"]
     pub const fn progress(&self) -> (usize, usize, usize) {
-        (self.file_count, MAX_FILES, self.dir_count)
+        (
+            self.stats.files_processed,
+            self.config.max_files,
+            self.dir_count,
+        )
     }
 
-    pub fn process_next(&mut self) -> SnapResult<Option<ArrayVec<PathBuf, MAX_FILES>>> {
-        let Some((dir, current_depth)) = self.queue.pop() else {
-            return Ok(None);
-        };
+    /// Files found by the most recent call to [`Crawler::process_next`].
+    #[must_use]
+    pub fn batch(&self) -> &[PathBuf] {
+        &self.batch
+    }
 
-        assert!(dir.exists(), "Directory in queue must exist");
-        assert!(dir.is_dir(), "Path in queue must be a directory");
+    /// Snapshot of the counters so far.
+    #[must_use]
+    pub fn stats(&self) -> CrawlStats {
+        self.stats.clone()
+    }
 
-        let mut files = ArrayVec::new();
+    pub const fn record_non_text(&mut self, count: usize) {
+        self.stats.skipped_non_text += count;
+    }
 
-        for entry in fs::read_dir(&dir)? {
-            let entry = entry?;
-            let path = entry.path();
+    /// Returns the files of the next directory, or `None` once the crawl is done.
+    ///
+    /// Directories are visited breadth-first in the order they were found. With
+    /// [`CrawlerConfig::sort_entries`] each directory's entries are sorted by
+    /// name, which makes the order deterministic; otherwise it is whatever the
+    /// filesystem returns.
+    pub fn process_next(&mut self) -> SnapResult<Option<&[PathBuf]>> {
+        let Some((dir, current_depth)) = self.queue.pop_front() else {
+            return Ok(None);
+        };
 
-            Self::validate_path(&path)?;
+        self.batch.clear();
 
-            if entry.file_type()?.is_dir() {
-                let new_depth = current_depth + 1;
-                if new_depth >= MAX_DEPTH {
-                    return Err(anyhow::Error::from(SnapError::with_code(
-                        format!("Maximum directory depth of {MAX_DEPTH} exceeded"),
-                        ERROR_DEPTH_EXCEEDED,
-                    )));
-                }
-                self.queue.try_push((path, new_depth)).map_err(|_| {
-                    anyhow::Error::from(SnapError::with_code(
-                        format!("Maximum directory depth of {MAX_DEPTH} exceeded"),
-                        ERROR_DEPTH_EXCEEDED,
-                    ))
-                })?;
-                self.dir_count += 1;
-            } else {
-                if self.file_count >= MAX_FILES {
-                    return Err(anyhow::Error::from(SnapError::with_code(
-                        format!("Maximum file count of {MAX_FILES} exceeded"),
-                        ERROR_FILE_COUNT_EXCEEDED,
-                    )));
+        let read_dir = match fs::read_dir(&dir) {
+            Ok(read_dir) => read_dir,
+            Err(e) if self.config.strict => return Err(e.into()),
+            Err(e) => {
+                self.record_error(dir, e.into());
+                return Ok(Some(&self.batch));
+            }
+        };
+        self.stats.dirs_processed += 1;
+        self.stats.max_depth_reached = self.stats.max_depth_reached.max(current_depth);
+
+        // Taken out so entries can be processed while `self` is borrowed mutably;
+        // the buffer keeps its capacity and is put back below
+        let mut entries = std::mem::take(&mut self.entries);
+        entries.clear();
+        for entry in read_dir {
+            let entry = match entry {
+                Ok(entry) => entry,
+                Err(e) => {
+                    if let Err(e) = self.recover_or_fail(dir.clone(), e.into()) {
+                        self.entries = entries;
+                        return Err(e);
+                    }
+                    continue;
                 }
-                let size = entry.metadata()?.len();
-                if size > MAX_FILE_SIZE {
-                    return Err(anyhow::Error::from(SnapError::with_code(
-                        "Maximum file size of 10MB exceeded".to_string(),
-                        ERROR_FILE_SIZE_EXCEEDED,
-                    )));
+            };
+            let name = entry.file_name();
+            if self.excluded_names.contains(&name)
+                || (self.config.skip_hidden && name.as_encoded_bytes().starts_with(b"."))
+            {
+                continue;
+            }
+            if entries.len() == self.config.entries_capacity() {
+                self.entries = entries;
+                return Err(anyhow::Error::from(SnapError::with_code(
+                    format!(
+                        "{} has more than {} entries",
+                        dir.display(),
+                        self.config.entries_capacity()
+                    ),
+                    ERROR_FILE_COUNT_EXCEEDED,
+                )));
+            }
+            entries.push(entry);
+        }
+        if self.config.sort_entries {
+            entries.sort_unstable_by_key(fs::DirEntry::file_name);
+        }
+
+        let mut result = Ok(());
+        for entry in entries.drain(..) {
+            if let Err(e) = self.process_entry(&entry, current_depth) {
+                result = self.recover_or_fail(entry.path(), e);
+                if result.is_err() {
+                    break;
                 }
-                files.try_push(path).map_err(|_| {
-                    anyhow::Error::from(SnapError::with_code(
-                        format!("Maximum file count of {MAX_FILES} exceeded"),
-                        ERROR_FILE_COUNT_EXCEEDED,
-                    ))
-                })?;
-                self.file_count += 1;
             }
         }
+        self.entries = entries;
+        result?;
 
         assert!(
-            self.file_count <= MAX_FILES,
+            self.stats.files_processed <= self.config.max_files,
             "File count must not exceed maximum"
         );
 
-        Ok(Some(files))
+        Ok(Some(&self.batch))
+    }
+
+    /// Crawls everything that is left and returns every file found.
+    pub fn collect_all(&mut self) -> SnapResult<Vec<PathBuf>> {
+        let mut files = Vec::new();
+        while let Some(batch) = self.process_next()? {
+            files.extend_from_slice(batch);
+        }
+        Ok(files)
     }
 
-    fn validate_path(path: &Path) -> SnapResult<()> {
+    fn process_entry(&mut self, entry: &fs::DirEntry, current_depth: usize) -> SnapResult<()> {
+        let max_depth = self.config.max_depth;
+        let max_files = self.config.max_files;
+        let path = entry.path();
+
+        Self::check_path_length(&path, self.config.max_path_length)?;
+
+        let is_dir = entry.file_type()?.is_dir();
+        let relative = path.strip_prefix(&self.root).unwrap_or(&path);
+        if self.ignore.is_ignored(relative, is_dir) {
+            return Ok(());
+        }
+
+        if !is_dir && !self.extension_allowed(&path) {
+            return Ok(());
+        }
+
+        if is_dir {
+            let new_depth = current_depth + 1;
+            if new_depth >= max_depth {
+                return Err(anyhow::Error::from(SnapError::with_code(
+                    format!("Maximum directory depth of {max_depth} exceeded"),
+                    ERROR_DEPTH_EXCEEDED,
+                )));
+            }
+            if self.queue.len() == self.config.max_pending_dirs {
+                return Err(anyhow::Error::from(SnapError::with_code(
+                    format!(
+                        "More than {} directories waiting to be crawled",
+                        self.config.max_pending_dirs
+                    ),
+                    ERROR_PENDING_DIRS_EXCEEDED,
+                )));
+            }
+            self.queue.push_back((path, new_depth));
+            self.dir_count += 1;
+        } else {
+            if self.stats.files_processed >= max_files {
+                return Err(anyhow::Error::from(SnapError::with_code(
+                    format!("Maximum file count of {max_files} exceeded"),
+                    ERROR_FILE_COUNT_EXCEEDED,
+                )));
+            }
+            let metadata = entry.metadata()?;
+            if let (Some(after), Ok(modified)) = (self.modified_after, metadata.modified())
+                && modified < after
+            {
+                return Ok(());
+            }
+            let size = metadata.len();
+            if size > self.config.max_file_size {
+                return Err(anyhow::Error::from(
+                    SnapError::with_code(
+                        format!(
+                            "Maximum file size of {} bytes exceeded",
+                            self.config.max_file_size
+                        ),
+                        ERROR_FILE_SIZE_EXCEEDED,
+                    )
+                    .with_path(&path),
+                ));
+            }
+            if size < self.config.min_file_size {
+                self.stats.skipped_too_small += 1;
+                return Ok(());
+            }
+            self.stats.record_file(&path);
+            self.batch.push(path);
+            self.stats.files_processed += 1;
+            self.stats.bytes_visited += size;
+        }
+
+        Ok(())
+    }
+
+    fn extension_allowed(&self, path: &Path) -> bool {
+        if self.allowed_extensions.is_empty() {
+            return true;
+        }
+        path.extension()
+            .and_then(|ext| ext.to_str())
+            .is_some_and(|ext| {
+                self.allowed_extensions
+                    .iter()
+                    .any(|allowed| allowed.eq_ignore_ascii_case(ext))
+            })
+    }
+
+    /// Keeps crawling past a per-entry failure when recovery is enabled.
+    ///
+    /// Depth and file count limits always stop the crawl, since they exist to
+    /// bound the work done rather than to describe a single bad entry.
+    fn recover_or_fail(&mut self, path: PathBuf, error: anyhow::Error) -> SnapResult<()> {
+        let recoverable = error.downcast_ref::<io::Error>().is_some()
+            || error.downcast_ref::<SnapError>().is_some_and(|e| {
+                matches!(e.code(), ERROR_FILE_SIZE_EXCEEDED | ERROR_PATH_TOO_LONG)
+            });
+        if !self.error_recovery || !recoverable {
+            return Err(error);
+        }
+
+        if error
+            .downcast_ref::<SnapError>()
+            .is_some_and(|e| e.code() == ERROR_FILE_SIZE_EXCEEDED)
+        {
+            self.stats.skipped_too_large += 1;
+        }
+        self.record_error(path, error);
+        Ok(())
+    }
+
+    fn record_error(&mut self, path: PathBuf, error: anyhow::Error) {
+        self.stats.errors_skipped += 1;
+        if self.errors.try_push((path, error)).is_err() {
+            self.dropped_errors += 1;
+        }
+    }
+
+    fn check_path_length(path: &Path, max_path_length: usize) -> SnapResult<()> {
         let path_len = path.as_os_str().len();
-        if path_len > MAX_PATH_LENGTH {
-            return Err(anyhow::Error::from(SnapError::with_code(
-                format!("Path length exceeded {MAX_PATH_LENGTH} characters"),
-                ERROR_PATH_TOO_LONG,
-            )));
+        if path_len > max_path_length {
+            return Err(anyhow::Error::from(
+                SnapError::with_code(
+                    format!("Path length exceeded {max_path_length} characters"),
+                    ERROR_PATH_TOO_LONG,
+                )
+                .with_path(path),
+            ));
         }
         Ok(())
     }
 }
 
+impl IgnoreRules {
+    /// Reads the ignore file in `root`. A missing file ignores nothing.
+    fn load(root: &Path) -> SnapResult<Self> {
+        let path = root.join(IGNORE_FILE_NAME);
+        let config_error = |msg: String| {
+            anyhow::Error::from(SnapError::with_code(msg, ERROR_INVALID_CONFIG).with_path(&path))
+        };
+
+        match fs::metadata(&path) {
+            Ok(metadata) if metadata.len() > MAX_IGNORE_FILE_SIZE => {
+                return Err(config_error(format!(
+                    "Ignore file is larger than {MAX_IGNORE_FILE_SIZE} bytes"
+                )));
+            }
+            Ok(_) => {}
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Self::default()),
+            Err(e) => return Err(config_error(format!("Failed to read ignore file: {e}"))),
+        }
+        let text = fs::read_to_string(&path)
+            .map_err(|e| config_error(format!("Failed to read ignore file: {e}")))?;
+        Self::parse(&text).map_err(|e| match e.downcast::<SnapError>() {
+            Ok(e) => anyhow::Error::from(e.with_path(&path)),
+            Err(e) => e,
+        })
+    }
+
+    fn parse(text: &str) -> SnapResult<Self> {
+        let mut ignore = Self::default();
+        for line in text.lines().map(str::trim_end) {
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (line, negate) = match line.strip_prefix('!') {
+                Some(rest) => (rest, true),
+                None => (line.strip_prefix('\\').unwrap_or(line), false),
+            };
+            let (line, dir_only) = match line.strip_suffix('/') {
+                Some(rest) => (rest, true),
+                None => (line, false),
+            };
+            if line.is_empty() {
+                return Err(anyhow::Error::from(SnapError::with_code(
+                    "Ignore pattern must not be empty",
+                    ERROR_INVALID_CONFIG,
+                )));
+            }
+
+            // No `/` matches the name at any depth, otherwise the pattern is
+            // anchored at the crawl root
+            let pattern = match line.strip_prefix('/') {
+                Some(anchored) => anchored.to_string(),
+                None if line.contains('/') => line.to_string(),
+                None => format!("**/{line}"),
+            };
+            let glob = globset::GlobBuilder::new(&pattern)
+                .literal_separator(true)
+                .build()
+                .map_err(|e| {
+                    SnapError::with_code(
+                        format!("Invalid ignore pattern: {e}"),
+                        ERROR_INVALID_CONFIG,
+                    )
+                })?;
+
+            ignore
+                .rules
+                .try_push(IgnoreRule {
+                    matcher: glob.compile_matcher(),
+                    negate,
+                    dir_only,
+                })
+                .map_err(|_| {
+                    SnapError::with_code(
+                        format!("Ignore file has more than {MAX_IGNORE_PATTERNS} patterns"),
+                        ERROR_INVALID_CONFIG,
+                    )
+                })?;
+        }
+        Ok(ignore)
+    }
+
+    fn is_ignored(&self, relative: &Path, is_dir: bool) -> bool {
+        let Some(path) = relative.to_str() else {
+            return false;
+        };
+        self.rules
+            .iter()
+            .rev()
+            .find(|rule| (is_dir || !rule.dir_only) && rule.matcher.is_match(path))
+            .is_some_and(|rule| !rule.negate)
+    }
+}
+
+/// Runs a [`Crawler`] on tokio's blocking thread pool, so reading
+/// directories never stalls the async runtime. The crawler keeps its limits,
+/// exclusions and error handling; only where the work runs changes.
+#[cfg(feature = "async")]
+#[derive(Debug)]
+pub struct AsyncCrawler {
+    /// Shared with the blocking pool, which may still be crawling a batch
+    /// for a call that was cancelled.
+    state: Arc<Mutex<AsyncCrawlState>>,
+}
+
+#[cfg(feature = "async")]
+#[derive(Debug)]
+struct AsyncCrawlState {
+    crawler: Crawler,
+    /// A batch whose caller was cancelled, returned by the next call.
+    unclaimed: Option<SnapResult<Option<Vec<PathBuf>>>>,
+}
+
+#[cfg(feature = "async")]
+impl AsyncCrawler {
+    #[must_use]
+    pub fn new(crawler: Crawler) -> Self {
+        Self {
+            state: Arc::new(Mutex::new(AsyncCrawlState {
+                crawler,
+                unclaimed: None,
+            })),
+        }
+    }
+
+    /// Snapshot of the wrapped crawler's counters.
+    #[must_use]
+    pub fn stats(&self) -> CrawlStats {
+        self.lock().crawler.stats()
+    }
+
+    /// Drains the wrapped crawler's errors, like [`Crawler::take_errors`].
+    pub fn take_errors(&self) -> ArrayVec<(PathBuf, anyhow::Error), MAX_ERRORS> {
+        self.lock().crawler.take_errors()
+    }
+
+    /// The wrapped crawler, or `None` while a cancelled call is still
+    /// crawling on the blocking pool. A batch left unclaimed is dropped.
+    #[must_use]
+    pub fn into_inner(self) -> Option<Crawler> {
+        let state = Arc::try_unwrap(self.state).ok()?;
+        Some(
+            state
+                .into_inner()
+                .unwrap_or_else(PoisonError::into_inner)
+                .crawler,
+        )
+    }
+
+    /// Crawls the next batch, like [`Crawler::process_next`].
+    ///
+    /// Cancel safe: if the returned future is dropped, the batch it was
+    /// crawling is kept and returned by the next call, so no files are lost.
+    pub async fn process_next(&mut self) -> SnapResult<Option<Vec<PathBuf>>> {
+        let state = Arc::clone(&self.state);
+        tokio::task::spawn_blocking(move || {
+            let mut state = state.lock().unwrap_or_else(PoisonError::into_inner);
+            if state.unclaimed.is_none() {
+                let result = state
+                    .crawler
+                    .process_next()
+                    .map(|batch| batch.map(<[PathBuf]>::to_vec));
+                state.unclaimed = Some(result);
+            }
+        })
+        .await?;
+
+        self.lock().unclaimed.take().unwrap_or(Ok(None))
+    }
+
+    fn lock(&self) -> MutexGuard<'_, AsyncCrawlState> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Yields every file path, one batch at a time. The stream ends after
+    /// the first error.
+    pub fn process_next_stream(
+        &mut self,
+    ) -> impl futures_util::Stream<Item = SnapResult<PathBuf>> + '_ {
+        let state = (self, Vec::new().into_iter(), false);
+        futures_util::stream::unfold(state, |(crawler, mut pending, failed)| async move {
+            loop {
+                if let Some(path) = pending.next() {
+                    return Some((Ok(path), (crawler, pending, failed)));
+                }
+                if failed {
+                    return None;
+                }
+                match crawler.process_next().await {
+                    Ok(Some(batch)) => pending = batch.into_iter(),
+                    Ok(None) => return None,
+                    Err(e) => return Some((Err(e), (crawler, pending, true))),
+                }
+            }
+        })
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use std::fs::File;
     use std::io::Write;
+    use std::time::Duration;
 
     use tempfile::TempDir;
 
@@ -180,6 +865,8 @@ mod tests {
         assert!(result.is_err());
         let err = result.unwrap_err().to_string();
         assert!(err.contains("file size"), "Error should mention file size");
+        // The crawl stopped on the file rather than skipping it
+        assert_eq!(crawler.stats().skipped_too_large, 0);
     }
 
     #[test]
@@ -240,7 +927,7 @@ mod tests {
         let long_name = "a".repeat(MAX_PATH_LENGTH + 1);
         let path = temp_dir.path().join(long_name);
 
-        let result = Crawler::validate_path(&path);
+        let result = Crawler::check_path_length(&path, MAX_PATH_LENGTH);
         assert!(result.is_err());
         let err = result.unwrap_err().to_string();
         assert!(
@@ -272,7 +959,7 @@ mod tests {
         File::create(&extra_file).unwrap();
 
         let mut crawler = Crawler::new(temp_dir.path()).unwrap();
-        crawler.file_count = MAX_FILES;
+        crawler.stats.files_processed = MAX_FILES;
 
         let result = crawler.process_next();
         assert!(result.is_err());
@@ -305,8 +992,9 @@ mod tests {
         let mut last_files = 0;
         let mut last_dirs = 1;
         while let Some(batch) = crawler.process_next().unwrap() {
+            let batch_len = batch.len();
             let (processed, _, discovered) = crawler.progress();
-            assert!(processed >= last_files + batch.len());
+            assert!(processed >= last_files + batch_len);
             assert!(discovered >= last_dirs);
 
             last_files = processed;
@@ -317,4 +1005,677 @@ mod tests {
         assert_eq!(files, 6);
         assert_eq!(dirs, 4);
     }
+
+    #[test]
+    fn test_excluded_names() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut crawler = Crawler::new(temp_dir.path()).unwrap();
+        crawler.exclude_name("skip.txt").unwrap();
+        crawler.exclude_name("target").unwrap();
+        crawler.exclude_name("target").unwrap();
+
+        File::create(temp_dir.path().join("keep.txt")).unwrap();
+        File::create(temp_dir.path().join("skip.txt")).unwrap();
+        let target = temp_dir.path().join("target");
+        fs::create_dir(&target).unwrap();
+        File::create(target.join("build.log")).unwrap();
+
+        let mut found = Vec::new();
+        while let Some(files) = crawler.process_next().unwrap() {
+            found.extend_from_slice(files);
+        }
+
+        assert_eq!(found, vec![temp_dir.path().join("keep.txt")]);
+        assert_eq!(crawler.progress(), (1, MAX_FILES, 1));
+
+        for i in 2..MAX_EXCLUDED_NAMES {
+            crawler.exclude_name(&format!("name_{i}")).unwrap();
+        }
+        assert!(crawler.exclude_name("one_too_many").is_err());
+    }
+
+    #[test]
+    fn test_with_config_limits() {
+        let temp_dir = TempDir::new().unwrap();
+        for i in 0..3 {
+            File::create(temp_dir.path().join(format!("file_{i}"))).unwrap();
+        }
+
+        let config = CrawlerConfig {
+            max_files: 2,
+            ..CrawlerConfig::default()
+        };
+        let mut crawler = Crawler::with_config(temp_dir.path(), config).unwrap();
+        assert_eq!(crawler.progress(), (0, 2, 1));
+        let err = crawler.process_next().unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<SnapError>().unwrap().code(),
+            ERROR_FILE_COUNT_EXCEEDED
+        );
+
+        let config = CrawlerConfig {
+            max_files: 5000,
+            ..CrawlerConfig::default()
+        };
+        let mut crawler = Crawler::with_config(temp_dir.path(), config).unwrap();
+        assert_eq!(crawler.process_next().unwrap().unwrap().len(), 3);
+        assert_eq!(crawler.progress(), (3, 5000, 1));
+
+        let mut f = File::create(temp_dir.path().join("file_0")).unwrap();
+        f.write_all(b"0123456789").unwrap();
+        let config = CrawlerConfig {
+            max_file_size: 9,
+            ..CrawlerConfig::default()
+        };
+        let mut crawler = Crawler::with_config(temp_dir.path(), config).unwrap();
+        let err = crawler.process_next().unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<SnapError>().unwrap().code(),
+            ERROR_FILE_SIZE_EXCEEDED
+        );
+        assert!(err.to_string().contains("9 bytes"));
+        let expected = temp_dir.path().join("file_0");
+        assert_eq!(
+            err.downcast_ref::<SnapError>().unwrap().path(),
+            Some(&*expected.to_string_lossy())
+        );
+    }
+
+    #[test]
+    fn test_with_config_depth() {
+        let temp_dir = TempDir::new().unwrap();
+        let nested = temp_dir.path().join("a").join("b");
+        fs::create_dir_all(&nested).unwrap();
+        File::create(nested.join("deep.txt")).unwrap();
+
+        let config = CrawlerConfig {
+            max_depth: 2,
+            ..CrawlerConfig::default()
+        };
+        let mut crawler = Crawler::with_config(temp_dir.path(), config).unwrap();
+        let result = loop {
+            match crawler.process_next() {
+                Ok(Some(_)) => {}
+                other => break other.map(|_| ()),
+            }
+        };
+        let err = result.unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<SnapError>().unwrap().code(),
+            ERROR_DEPTH_EXCEEDED
+        );
+
+        let config = CrawlerConfig {
+            max_depth: 3,
+            ..CrawlerConfig::default()
+        };
+        let mut crawler = Crawler::with_config(temp_dir.path(), config).unwrap();
+        let mut found = Vec::new();
+        while let Some(files) = crawler.process_next().unwrap() {
+            found.extend_from_slice(files);
+        }
+        assert_eq!(found, vec![nested.join("deep.txt")]);
+    }
+
+    #[test]
+    fn test_wide_tree_is_not_reported_as_deep() {
+        let temp_dir = TempDir::new().unwrap();
+        for i in 0..40 {
+            for j in 0..40 {
+                fs::create_dir_all(temp_dir.path().join(format!("{i}/{j}"))).unwrap();
+            }
+        }
+
+        let mut crawler = Crawler::new(temp_dir.path()).unwrap();
+        while crawler.process_next().unwrap().is_some() {}
+        assert_eq!(crawler.stats().dirs_processed, 1 + 40 + 40 * 40);
+
+        let config = CrawlerConfig {
+            max_pending_dirs: 100,
+            ..CrawlerConfig::default()
+        };
+        let mut crawler = Crawler::with_config(temp_dir.path(), config).unwrap();
+        let result = loop {
+            match crawler.process_next() {
+                Ok(Some(_)) => {}
+                other => break other.map(|_| ()),
+            }
+        };
+        let err = result.unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<SnapError>().unwrap().code(),
+            ERROR_PENDING_DIRS_EXCEEDED
+        );
+    }
+
+    #[test]
+    fn test_with_config_rejects_invalid_limits() {
+        let temp_dir = TempDir::new().unwrap();
+        let configs = [
+            CrawlerConfig {
+                max_files: 0,
+                ..CrawlerConfig::default()
+            },
+            CrawlerConfig {
+                max_depth: 0,
+                ..CrawlerConfig::default()
+            },
+            CrawlerConfig {
+                max_file_size: 0,
+                ..CrawlerConfig::default()
+            },
+            CrawlerConfig {
+                max_path_length: 0,
+                ..CrawlerConfig::default()
+            },
+            CrawlerConfig {
+                max_files: MAX_FILES_LIMIT + 1,
+                ..CrawlerConfig::default()
+            },
+            CrawlerConfig {
+                max_files: usize::MAX,
+                ..CrawlerConfig::default()
+            },
+            CrawlerConfig {
+                max_depth: MAX_DEPTH_LIMIT + 1,
+                ..CrawlerConfig::default()
+            },
+            CrawlerConfig {
+                max_pending_dirs: 0,
+                ..CrawlerConfig::default()
+            },
+            CrawlerConfig {
+                max_pending_dirs: MAX_PENDING_DIRS_LIMIT + 1,
+                ..CrawlerConfig::default()
+            },
+        ];
+        for config in configs {
+            let err = Crawler::with_config(temp_dir.path(), config).unwrap_err();
+            assert_eq!(
+                err.downcast_ref::<SnapError>().unwrap().code(),
+                ERROR_INVALID_CONFIG
+            );
+        }
+    }
+
+    #[test]
+    fn test_min_file_size() {
+        let temp_dir = TempDir::new().unwrap();
+        File::create(temp_dir.path().join("empty.txt")).unwrap();
+        fs::write(temp_dir.path().join("one.txt"), "1").unwrap();
+
+        let all = Crawler::new(temp_dir.path())
+            .unwrap()
+            .collect_all()
+            .unwrap();
+        assert_eq!(all.len(), 2);
+
+        let config = CrawlerConfig {
+            min_file_size: 1,
+            ..CrawlerConfig::default()
+        };
+        let mut crawler = Crawler::with_config(temp_dir.path(), config).unwrap();
+        let files = crawler.collect_all().unwrap();
+        assert_eq!(files, vec![temp_dir.path().join("one.txt")]);
+        assert_eq!(crawler.stats().skipped_too_small, 1);
+        assert_eq!(crawler.stats().files_processed, 1);
+        assert_eq!(crawler.stats().errors_skipped, 0);
+
+        let config = CrawlerConfig {
+            min_file_size: 10,
+            max_file_size: 5,
+            ..CrawlerConfig::default()
+        };
+        let err = Crawler::with_config(temp_dir.path(), config).unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<SnapError>().unwrap().code(),
+            ERROR_INVALID_CONFIG
+        );
+    }
+
+    #[test]
+    fn test_skip_hidden() {
+        let temp_dir = TempDir::new().unwrap();
+        let objects = temp_dir.path().join(".git/objects/ab");
+        fs::create_dir_all(&objects).unwrap();
+        File::create(objects.join("cdef")).unwrap();
+        File::create(temp_dir.path().join(".git/HEAD")).unwrap();
+        File::create(temp_dir.path().join(".env")).unwrap();
+        fs::create_dir(temp_dir.path().join("src")).unwrap();
+        File::create(temp_dir.path().join("src/main.rs")).unwrap();
+
+        let mut crawler = Crawler::new(temp_dir.path()).unwrap();
+        let files = crawler.collect_all().unwrap();
+        assert_eq!(files, [temp_dir.path().join("src/main.rs")]);
+        assert_eq!(crawler.progress().2, 2);
+
+        let config = CrawlerConfig {
+            skip_hidden: false,
+            ..CrawlerConfig::default()
+        };
+        let mut crawler = Crawler::with_config(temp_dir.path(), config).unwrap();
+        assert_eq!(crawler.collect_all().unwrap().len(), 4);
+        assert_eq!(crawler.progress().2, 5);
+
+        // A hidden root is still crawled
+        let mut crawler = Crawler::new(&temp_dir.path().join(".git")).unwrap();
+        assert_eq!(crawler.collect_all().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_modified_after() {
+        let temp_dir = TempDir::new().unwrap();
+        let now = SystemTime::now();
+        let hour = Duration::from_secs(3600);
+        for (name, modified) in [
+            ("old.txt", now - 2 * hour),
+            ("new.txt", now),
+            ("skewed.txt", now + hour),
+        ] {
+            File::create(temp_dir.path().join(name))
+                .unwrap()
+                .set_modified(modified)
+                .unwrap();
+        }
+
+        let mut files = Crawler::new(temp_dir.path())
+            .unwrap()
+            .with_modified_after(Some(now - hour))
+            .collect_all()
+            .unwrap();
+        files.sort();
+        assert_eq!(
+            files,
+            ["new.txt", "skewed.txt"].map(|name| temp_dir.path().join(name))
+        );
+
+        let all = Crawler::new(temp_dir.path())
+            .unwrap()
+            .with_modified_after(None)
+            .collect_all()
+            .unwrap();
+        assert_eq!(all.len(), 3);
+    }
+
+    #[test]
+    fn test_extension_allowlist() {
+        let temp_dir = TempDir::new().unwrap();
+        fs::create_dir(temp_dir.path().join("src")).unwrap();
+        for name in [
+            "README.MD",
+            "main.rs",
+            "src/lib.rs",
+            "notes.txt",
+            "Makefile",
+            "data.json",
+        ] {
+            File::create(temp_dir.path().join(name)).unwrap();
+        }
+
+        let mut crawler = Crawler::new(temp_dir.path())
+            .unwrap()
+            .with_extensions(&["rs", ".md"])
+            .unwrap();
+        let mut files = crawler.collect_all().unwrap();
+        files.sort();
+        assert_eq!(
+            files,
+            ["README.MD", "main.rs", "src/lib.rs"].map(|name| temp_dir.path().join(name))
+        );
+        assert_eq!(crawler.stats().files_processed, 3);
+
+        let all = Crawler::new(temp_dir.path())
+            .unwrap()
+            .with_extensions(&[])
+            .unwrap()
+            .collect_all()
+            .unwrap();
+        assert_eq!(all.len(), 6);
+    }
+
+    #[test]
+    fn test_invalid_extensions() {
+        let temp_dir = TempDir::new().unwrap();
+        let too_many: Vec<String> = (0..=MAX_EXTENSIONS).map(|i| format!("e{i}")).collect();
+        let too_many: Vec<&str> = too_many.iter().map(String::as_str).collect();
+
+        for exts in [&[""][..], &["."], &["muchtoolong"], &too_many] {
+            let err = Crawler::new(temp_dir.path())
+                .unwrap()
+                .with_extensions(exts)
+                .unwrap_err();
+            assert_eq!(
+                err.downcast_ref::<SnapError>().unwrap().code(),
+                ERROR_INVALID_CONFIG
+            );
+        }
+    }
+
+    #[test]
+    fn test_error_recovery_collects_errors() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut f = File::create(temp_dir.path().join("large.txt")).unwrap();
+        f.write_all(b"0123456789").unwrap();
+        File::create(temp_dir.path().join("small.txt")).unwrap();
+
+        let config = CrawlerConfig {
+            max_file_size: 5,
+            ..CrawlerConfig::default()
+        };
+        let mut crawler = Crawler::with_config(temp_dir.path(), config)
+            .unwrap()
+            .with_error_recovery(true);
+
+        let files = crawler.process_next().unwrap().unwrap();
+        assert_eq!(files, [temp_dir.path().join("small.txt")]);
+        assert!(crawler.process_next().unwrap().is_none());
+
+        let errors = crawler.take_errors();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].0, temp_dir.path().join("large.txt"));
+        assert_eq!(
+            errors[0].1.downcast_ref::<SnapError>().unwrap().code(),
+            ERROR_FILE_SIZE_EXCEEDED
+        );
+        assert!(crawler.take_errors().is_empty());
+        assert_eq!(crawler.stats().skipped_too_large, 1);
+    }
+
+    #[test]
+    fn test_error_recovery_keeps_limits_fatal() {
+        let temp_dir = TempDir::new().unwrap();
+        for i in 0..3 {
+            File::create(temp_dir.path().join(format!("file_{i}"))).unwrap();
+        }
+
+        let config = CrawlerConfig {
+            max_files: 2,
+            ..CrawlerConfig::default()
+        };
+        let mut crawler = Crawler::with_config(temp_dir.path(), config)
+            .unwrap()
+            .with_error_recovery(true);
+        let err = crawler.process_next().unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<SnapError>().unwrap().code(),
+            ERROR_FILE_COUNT_EXCEEDED
+        );
+        assert!(crawler.take_errors().is_empty());
+    }
+
+    #[test]
+    fn test_error_recovery_caps_buffer() {
+        let temp_dir = TempDir::new().unwrap();
+        for i in 0..=MAX_ERRORS {
+            fs::write(temp_dir.path().join(format!("file_{i}")), "too big").unwrap();
+        }
+
+        let config = CrawlerConfig {
+            max_file_size: 1,
+            ..CrawlerConfig::default()
+        };
+        let mut crawler = Crawler::with_config(temp_dir.path(), config)
+            .unwrap()
+            .with_error_recovery(true);
+        while crawler.process_next().unwrap().is_some() {}
+
+        assert_eq!(crawler.take_errors().len(), MAX_ERRORS);
+        assert_eq!(crawler.dropped_errors(), 1);
+    }
+
+    #[test]
+    fn test_unreadable_directory_is_skipped() {
+        let temp_dir = TempDir::new().unwrap();
+        File::create(temp_dir.path().join("top.txt")).unwrap();
+        let gone = temp_dir.path().join("gone");
+        fs::create_dir(&gone).unwrap();
+
+        let mut crawler = Crawler::new(temp_dir.path()).unwrap();
+        assert_eq!(crawler.process_next().unwrap().unwrap().len(), 1);
+        fs::remove_dir(&gone).unwrap();
+
+        assert!(crawler.process_next().unwrap().unwrap().is_empty());
+        assert!(crawler.process_next().unwrap().is_none());
+        let errors = crawler.take_errors();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].0, gone);
+        assert_eq!(
+            errors[0].1.downcast_ref::<io::Error>().unwrap().kind(),
+            io::ErrorKind::NotFound
+        );
+        assert_eq!(crawler.dropped_errors(), 0);
+        assert_eq!(crawler.stats().errors_skipped, 1);
+    }
+
+    #[test]
+    fn test_unreadable_directory_fails_when_strict() {
+        let temp_dir = TempDir::new().unwrap();
+        let gone = temp_dir.path().join("gone");
+        fs::create_dir(&gone).unwrap();
+
+        let config = CrawlerConfig {
+            strict: true,
+            ..CrawlerConfig::default()
+        };
+        let mut crawler = Crawler::with_config(temp_dir.path(), config).unwrap();
+        crawler.process_next().unwrap();
+        fs::remove_dir(&gone).unwrap();
+
+        let err = crawler.process_next().unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<io::Error>().unwrap().kind(),
+            io::ErrorKind::NotFound
+        );
+        assert!(crawler.take_errors().is_empty());
+    }
+
+    #[test]
+    fn test_dir_errors_share_the_error_buffer() {
+        let temp_dir = TempDir::new().unwrap();
+        let dirs: Vec<_> = (0..=MAX_ERRORS)
+            .map(|i| temp_dir.path().join(format!("dir_{i}")))
+            .collect();
+        for dir in &dirs {
+            fs::create_dir(dir).unwrap();
+        }
+
+        let mut crawler = Crawler::new(temp_dir.path()).unwrap();
+        crawler.process_next().unwrap();
+        for dir in &dirs {
+            fs::remove_dir(dir).unwrap();
+        }
+        while crawler.process_next().unwrap().is_some() {}
+
+        assert_eq!(crawler.take_errors().len(), MAX_ERRORS);
+        assert_eq!(crawler.dropped_errors(), 1);
+    }
+
+    #[test]
+    fn test_crawl_order_is_breadth_first_and_sorted() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        for dir in ["b", "a", "a/deep"] {
+            fs::create_dir(root.join(dir)).unwrap();
+        }
+        for file in [
+            "z.txt",
+            "m.txt",
+            "b/2.txt",
+            "b/1.txt",
+            "a/x.txt",
+            "a/deep/y.txt",
+        ] {
+            File::create(root.join(file)).unwrap();
+        }
+
+        let mut crawler = Crawler::new(root).unwrap();
+        let files = crawler.collect_all().unwrap();
+        let expected: Vec<_> = [
+            "m.txt",
+            "z.txt",
+            "a/x.txt",
+            "b/1.txt",
+            "b/2.txt",
+            "a/deep/y.txt",
+        ]
+        .iter()
+        .map(|file| root.join(file))
+        .collect();
+        assert_eq!(files, expected);
+        assert_eq!(Crawler::new(root).unwrap().collect_all().unwrap(), files);
+
+        let config = CrawlerConfig {
+            sort_entries: false,
+            ..CrawlerConfig::default()
+        };
+        let mut unsorted = Crawler::with_config(root, config)
+            .unwrap()
+            .collect_all()
+            .unwrap();
+        unsorted.sort();
+        let mut sorted = files;
+        sorted.sort();
+        assert_eq!(unsorted, sorted);
+    }
+
+    #[test]
+    fn test_collect_all() {
+        let temp_dir = TempDir::new().unwrap();
+        File::create(temp_dir.path().join("top.txt")).unwrap();
+        let subdir = temp_dir.path().join("sub");
+        fs::create_dir(&subdir).unwrap();
+        File::create(subdir.join("nested.txt")).unwrap();
+
+        let mut crawler = Crawler::new(temp_dir.path()).unwrap();
+        let mut files = crawler.collect_all().unwrap();
+        files.sort();
+        assert_eq!(
+            files,
+            vec![subdir.join("nested.txt"), temp_dir.path().join("top.txt")]
+        );
+        assert_eq!(crawler.progress(), (2, MAX_FILES, 2));
+        assert!(crawler.collect_all().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_extension_stats() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut crawler = Crawler::new(temp_dir.path()).unwrap();
+
+        File::create(temp_dir.path().join("main.rs")).unwrap();
+        File::create(temp_dir.path().join("lib.RS")).unwrap();
+        File::create(temp_dir.path().join("README.md")).unwrap();
+        File::create(temp_dir.path().join("Makefile")).unwrap();
+        let subdir = temp_dir.path().join("notes");
+        fs::create_dir(&subdir).unwrap();
+        File::create(subdir.join("todo.txt")).unwrap();
+        File::create(subdir.join("archive.verylongextension")).unwrap();
+
+        while crawler.process_next().unwrap().is_some() {}
+        crawler.record_non_text(1);
+
+        let stats = crawler.stats();
+        assert_eq!(stats.extension_count("rs"), 2);
+        assert_eq!(stats.extension_count("md"), 1);
+        assert_eq!(stats.extension_count("txt"), 1);
+        assert_eq!(stats.extension_count("verylong"), 1);
+        assert_eq!(stats.extension_count("json"), 0);
+        assert_eq!(stats.other_extensions, 1);
+        assert_eq!(stats.skipped_too_large, 0);
+        assert_eq!(stats.skipped_non_text, 1);
+        assert_eq!(stats.extensions().count(), 4);
+    }
+
+    #[test]
+    fn test_crawl_totals() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        fs::create_dir_all(root.join("a/b")).unwrap();
+        fs::write(root.join("top.txt"), "12345").unwrap();
+        fs::write(root.join("a/mid.txt"), "123").unwrap();
+        fs::write(root.join("a/b/deep.txt"), "1").unwrap();
+        let f = File::create(root.join("a/large.bin")).unwrap();
+        f.set_len(MAX_FILE_SIZE + 1).unwrap();
+
+        let mut crawler = Crawler::new(root).unwrap().with_error_recovery(true);
+        assert_eq!(crawler.stats().dirs_processed, 0);
+        crawler.collect_all().unwrap();
+
+        let stats = crawler.stats();
+        assert_eq!(stats.files_processed, 3);
+        assert_eq!(stats.dirs_processed, 3);
+        assert_eq!(stats.bytes_visited, 9);
+        assert_eq!(stats.errors_skipped, 1);
+        assert_eq!(stats.max_depth_reached, 2);
+        assert_eq!(crawler.progress().0, stats.files_processed);
+    }
+
+    fn crawl_names(root: &Path) -> Vec<String> {
+        let mut names: Vec<_> = Crawler::new(root)
+            .unwrap()
+            .collect_all()
+            .unwrap()
+            .iter()
+            .map(|path| {
+                path.strip_prefix(root)
+                    .unwrap()
+                    .to_string_lossy()
+                    .into_owned()
+            })
+            .collect();
+        names.sort();
+        names
+    }
+
+    #[test]
+    fn test_ignore_file_skips_matching_files() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        fs::create_dir(root.join("logs")).unwrap();
+        for name in ["app.log", "notes.txt", "logs/old.log"] {
+            File::create(root.join(name)).unwrap();
+        }
+        fs::write(root.join(IGNORE_FILE_NAME), "*.log\n").unwrap();
+
+        // The ignore file is hidden, so it is not indexed itself
+        assert_eq!(crawl_names(root), ["notes.txt"]);
+    }
+
+    #[test]
+    fn test_ignore_file_comments_negations_and_dirs() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        for dir in ["build", "src/build", "docs"] {
+            fs::create_dir_all(root.join(dir)).unwrap();
+        }
+        for name in [
+            "a.log",
+            "keep.log",
+            "build/out.txt",
+            "src/build/gen.txt",
+            "src/main.rs",
+            "docs/build",
+            "#notes",
+        ] {
+            File::create(root.join(name)).unwrap();
+        }
+        let rules = "# build output\n\n*.log\n!keep.log\n/build/\nsrc/build\n\\#notes\n";
+        fs::write(root.join(IGNORE_FILE_NAME), rules).unwrap();
+
+        assert_eq!(crawl_names(root), ["docs/build", "keep.log", "src/main.rs"]);
+    }
+
+    #[test]
+    fn test_invalid_ignore_file() {
+        let temp_dir = TempDir::new().unwrap();
+        let ignore_path = temp_dir.path().join(IGNORE_FILE_NAME);
+
+        for rules in ["!\n", "a[\n", &"*.x\n".repeat(MAX_IGNORE_PATTERNS + 1)] {
+            fs::write(&ignore_path, rules).unwrap();
+            let err = Crawler::new(temp_dir.path()).unwrap_err();
+            let err = err.downcast_ref::<SnapError>().unwrap();
+            assert_eq!(err.code(), ERROR_INVALID_CONFIG, "{rules:?}");
+            assert_eq!(err.path(), Some(ignore_path.to_str().unwrap()));
+        }
+    }
 }
@@ -1,18 +1,124 @@
+use std::path::Path;
+
+use arrayvec::ArrayString;
+
+use super::search::MAX_PATH_BYTES;
+
+const TRUNCATED_MARKER: &str = "...";
+
+/// Stable numeric codes carried by [`SnapError`]. Hundreds group the source:
+/// 1xx general, 2xx crawling, 3xx search and index files, 4xx input
+/// splitting. The `ERROR_*` constants in each module are defined from these
+/// variants, and [`exit_code`] turns them into process exit statuses.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[repr(i32)]
+pub enum ErrorCode {
+    Other = 101,
+    JsonUnsupported = 102,
+    DepthExceeded = 201,
+    FileCountExceeded = 202,
+    FileSizeExceeded = 203,
+    CrawlPathTooLong = 204,
+    TooManyExclusions = 205,
+    InvalidCrawlConfig = 206,
+    PendingDirsExceeded = 207,
+    InvalidQuery = 301,
+    InvalidIndex = 302,
+    TooManyDocuments = 303,
+    ContentTooLarge = 304,
+    IndexPathTooLong = 305,
+    InvalidSearchConfig = 306,
+    IndexLocked = 307,
+    DocumentNotFound = 308,
+    InvalidSplit = 401,
+}
+
+impl ErrorCode {
+    pub const ALL: [Self; 18] = [
+        Self::Other,
+        Self::JsonUnsupported,
+        Self::DepthExceeded,
+        Self::FileCountExceeded,
+        Self::FileSizeExceeded,
+        Self::CrawlPathTooLong,
+        Self::TooManyExclusions,
+        Self::InvalidCrawlConfig,
+        Self::PendingDirsExceeded,
+        Self::InvalidQuery,
+        Self::InvalidIndex,
+        Self::TooManyDocuments,
+        Self::ContentTooLarge,
+        Self::IndexPathTooLong,
+        Self::InvalidSearchConfig,
+        Self::IndexLocked,
+        Self::DocumentNotFound,
+        Self::InvalidSplit,
+    ];
+
+    #[must_use]
+    pub const fn code(self) -> i32 {
+        self as i32
+    }
+
+    #[must_use]
+    pub const fn exit_code(self) -> i32 {
+        exit_code(self.code())
+    }
+
+    #[must_use]
+    pub fn from_code(code: i32) -> Option<Self> {
+        Self::ALL.into_iter().find(|c| c.code() == code)
+    }
+}
+
+/// Maps an error code to a process exit status. Unix keeps only the low byte
+/// of a status, so each family moves from hundreds to tens: 1xx exits with
+/// 1x, 2xx with 2x, 3xx with 3x and 4xx with 4x (302 exits with 32). Other
+/// codes that fit in a byte are kept and the rest exit with 1.
+#[must_use]
+pub const fn exit_code(code: i32) -> i32 {
+    match code {
+        100..=999 if code % 100 < 10 => code / 100 * 10 + code % 100,
+        0..=255 => code,
+        _ => 1,
+    }
+}
+
+impl From<ErrorCode> for i32 {
+    fn from(code: ErrorCode) -> Self {
+        code.code()
+    }
+}
+
+impl TryFrom<i32> for ErrorCode {
+    type Error = i32;
+
+    fn try_from(code: i32) -> Result<Self, Self::Error> {
+        Self::from_code(code).ok_or(code)
+    }
+}
+
 #[derive(Debug)]
 pub struct SnapError {
     error: Option<anyhow::Error>,
     code: i32,
+    path: Option<ArrayString<MAX_PATH_BYTES>>,
 }
 
 impl SnapError {
     pub fn silent(code: i32) -> Self {
-        Self { error: None, code }
+        Self {
+            error: None,
+            code,
+            path: None,
+        }
     }
 
     pub fn message<T: Into<anyhow::Error>>(e: T) -> Self {
         Self {
             error: Some(e.into()),
-            code: 101,
+            code: ErrorCode::Other.code(),
+            path: None,
         }
     }
 
@@ -20,12 +126,57 @@ impl SnapError {
         Self {
             error: Some(anyhow::anyhow!("{}", msg.to_string())),
             code,
+            path: None,
         }
     }
 
+    /// Names the file or directory the error is about. Paths longer than
+    /// [`MAX_PATH_BYTES`] keep their end, where the file name is.
+    #[must_use]
+    pub fn with_path(mut self, path: &Path) -> Self {
+        let lossy = path.to_string_lossy();
+        let mut stored = ArrayString::new();
+        if lossy.len() <= MAX_PATH_BYTES {
+            stored.push_str(&lossy);
+        } else {
+            let mut start = lossy.len() - (MAX_PATH_BYTES - TRUNCATED_MARKER.len());
+            while !lossy.is_char_boundary(start) {
+                start += 1;
+            }
+            stored.push_str(TRUNCATED_MARKER);
+            stored.push_str(&lossy[start..]);
+        }
+        self.path = Some(stored);
+        self
+    }
+
+    pub fn path(&self) -> Option<&str> {
+        self.path.as_deref()
+    }
+
     pub fn code(&self) -> i32 {
         self.code
     }
+
+    /// The code as an [`ErrorCode`], or `None` for codes the library does not
+    /// define, such as ones chosen by the binary.
+    pub fn error_code(&self) -> Option<ErrorCode> {
+        ErrorCode::from_code(self.code)
+    }
+
+    pub fn exit_code(&self) -> i32 {
+        exit_code(self.code)
+    }
+
+    /// The message, the path it is about and the error code, for showing to
+    /// users.
+    pub fn user_message(&self) -> String {
+        match (&self.error, &self.path) {
+            (Some(error), Some(path)) => format!("{error}: {path} (error {})", self.code),
+            (Some(error), None) => format!("{error} (error {})", self.code),
+            (None, _) => self.to_string(),
+        }
+    }
 }
 
 macro_rules! process_error_from {
@@ -76,3 +227,101 @@ impl std::error::Error for SnapError {
 }
 
 pub type SnapResult<T> = anyhow::Result<T>;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::snapfind::{crawler, search, split};
+
+    #[test]
+    fn test_codes_round_trip() {
+        for code in ErrorCode::ALL {
+            assert_eq!(ErrorCode::from_code(code.code()), Some(code));
+            assert_eq!(ErrorCode::try_from(i32::from(code)), Ok(code));
+        }
+        assert_eq!(ErrorCode::from_code(0), None);
+        assert_eq!(ErrorCode::try_from(999), Err(999));
+    }
+
+    #[test]
+    fn test_snap_error_codes_agree() {
+        let err = SnapError::with_code("bad query", search::ERROR_INVALID_QUERY);
+        assert_eq!(err.code(), 301);
+        assert_eq!(err.error_code(), Some(ErrorCode::InvalidQuery));
+        assert_eq!(SnapError::from("oops").error_code(), Some(ErrorCode::Other));
+        assert_eq!(SnapError::silent(1).error_code(), None);
+
+        assert_eq!(crawler::ERROR_PATH_TOO_LONG, 204);
+        assert_eq!(search::ERROR_PATH_TOO_LONG, 305);
+        assert_eq!(split::ERROR_INVALID_SPLIT, 401);
+    }
+
+    #[test]
+    fn test_exit_codes() {
+        let expected = [
+            (ErrorCode::Other, 11),
+            (ErrorCode::JsonUnsupported, 12),
+            (ErrorCode::DepthExceeded, 21),
+            (ErrorCode::FileCountExceeded, 22),
+            (ErrorCode::FileSizeExceeded, 23),
+            (ErrorCode::CrawlPathTooLong, 24),
+            (ErrorCode::TooManyExclusions, 25),
+            (ErrorCode::InvalidCrawlConfig, 26),
+            (ErrorCode::PendingDirsExceeded, 27),
+            (ErrorCode::InvalidQuery, 31),
+            (ErrorCode::InvalidIndex, 32),
+            (ErrorCode::TooManyDocuments, 33),
+            (ErrorCode::ContentTooLarge, 34),
+            (ErrorCode::IndexPathTooLong, 35),
+            (ErrorCode::InvalidSearchConfig, 36),
+            (ErrorCode::IndexLocked, 37),
+            (ErrorCode::DocumentNotFound, 38),
+            (ErrorCode::InvalidSplit, 41),
+        ];
+        assert_eq!(expected.len(), ErrorCode::ALL.len());
+        for (code, exit) in expected {
+            assert_eq!(code.exit_code(), exit, "{code:?}");
+        }
+
+        assert_eq!(exit_code(102), 12);
+        assert_eq!(exit_code(1), 1);
+        assert_eq!(exit_code(150), 150);
+        assert_eq!(exit_code(-1), 1);
+        assert_eq!(exit_code(1000), 1);
+    }
+
+    #[test]
+    fn test_user_message_names_path() {
+        let err = SnapError::with_code("Maximum file size of 9 bytes exceeded", 203)
+            .with_path(Path::new("logs/large.txt"));
+        assert_eq!(err.path(), Some("logs/large.txt"));
+        assert_eq!(
+            err.user_message(),
+            "Maximum file size of 9 bytes exceeded: logs/large.txt (error 203)"
+        );
+        // Display stays the bare message for callers that print the path
+        assert_eq!(err.to_string(), "Maximum file size of 9 bytes exceeded");
+    }
+
+    #[test]
+    fn test_long_path_keeps_file_name() {
+        let long = format!(
+            "{}/é{}/name.txt",
+            "d".repeat(MAX_PATH_BYTES),
+            "x".repeat(10)
+        );
+        let err = SnapError::with_code("Path too long", 305).with_path(Path::new(&long));
+        let path = err.path().unwrap();
+        assert!(path.len() <= MAX_PATH_BYTES);
+        assert!(path.starts_with(TRUNCATED_MARKER));
+        assert!(path.ends_with("/name.txt"));
+    }
+
+    #[test]
+    fn test_user_message_includes_code() {
+        let err = SnapError::with_code("Directory not found: nope", search::ERROR_INVALID_INDEX);
+        assert_eq!(err.user_message(), "Directory not found: nope (error 302)");
+        assert_eq!(err.exit_code(), 32);
+        assert_eq!(SnapError::silent(7).user_message(), "Error code: 7");
+    }
+}
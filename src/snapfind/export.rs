@@ -0,0 +1,272 @@
+//! A portable JSON form of an index, for debugging, feeding other tools and
+//! carrying documents across changes to the binary format.
+
+use std::io::{Read, Write};
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use super::error::{SnapError, SnapResult};
+use super::search::{
+    ERROR_INVALID_INDEX, ERROR_PATH_TOO_LONG, FileStamp, IndexMetadata, MAX_PATH_BYTES,
+    SearchEngine,
+};
+
+/// Everything an index stores, with content as text.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ExportedIndex {
+    pub max_content_length: usize,
+    /// Metadata of the exported index. An imported index keeps its crawl
+    /// filters, and records when and where it was saved instead of the rest.
+    #[serde(default)]
+    pub metadata: IndexMetadata,
+    pub documents: Vec<ExportedDocument>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ExportedDocument {
+    pub path: String,
+    /// Stored content, with invalid UTF-8 replaced by U+FFFD.
+    pub content: String,
+    /// Bytes of stored content. Differs from `content.len()` only when
+    /// invalid UTF-8 was replaced, and such records are rejected on import.
+    pub content_length: usize,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stamp: Option<FileStamp>,
+}
+
+/// A record [`ExportedIndex::into_engine`] left out.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Rejection {
+    /// Position of the record in [`ExportedIndex::documents`].
+    pub record: usize,
+    pub path: String,
+    pub code: i32,
+    pub reason: String,
+}
+
+#[derive(Debug)]
+pub struct Import {
+    pub engine: SearchEngine,
+    pub rejected: Vec<Rejection>,
+}
+
+impl ExportedIndex {
+    #[must_use]
+    pub fn from_engine(engine: &SearchEngine) -> Self {
+        let documents = engine
+            .documents()
+            .map(|(path, content)| ExportedDocument {
+                path: path.to_string_lossy().into_owned(),
+                content: String::from_utf8_lossy(content).into_owned(),
+                content_length: content.len(),
+                stamp: engine.get_document(path).and_then(|doc| doc.stamp),
+            })
+            .collect();
+        Self {
+            max_content_length: engine.max_content_length(),
+            metadata: engine.metadata().clone(),
+            documents,
+        }
+    }
+
+    /// Builds an engine from the records that fit its limits. Fails only when
+    /// `max_content_length` itself is invalid; records over the path, content
+    /// or document count limits are returned in [`Import::rejected`].
+    pub fn into_engine(self) -> SnapResult<Import> {
+        let mut engine = SearchEngine::builder()
+            .max_content_length(self.max_content_length)?
+            .build();
+        if let Some(filters) = self.metadata.filters {
+            engine.set_crawl_filters(filters);
+        }
+        let mut rejected = Vec::new();
+
+        for (record, doc) in self.documents.into_iter().enumerate() {
+            let mut reject = |code, reason| {
+                rejected.push(Rejection {
+                    record,
+                    path: doc.path.clone(),
+                    code,
+                    reason,
+                });
+            };
+            if doc.path.len() > MAX_PATH_BYTES {
+                reject(
+                    ERROR_PATH_TOO_LONG,
+                    format!(
+                        "Path is {} bytes, limit is {MAX_PATH_BYTES}",
+                        doc.path.len()
+                    ),
+                );
+                continue;
+            }
+            if doc.content_length != doc.content.len() {
+                reject(
+                    ERROR_INVALID_INDEX,
+                    format!(
+                        "content_length is {} but content is {} bytes",
+                        doc.content_length,
+                        doc.content.len()
+                    ),
+                );
+                continue;
+            }
+            let path = Path::new(&doc.path);
+            match engine.add_document(path, &doc.content) {
+                Ok(()) => {
+                    if let Some(stamp) = doc.stamp {
+                        engine.set_document_stamp(path, stamp);
+                    }
+                }
+                Err(e) => {
+                    let code = e
+                        .downcast_ref::<SnapError>()
+                        .map_or(ERROR_INVALID_INDEX, SnapError::code);
+                    reject(code, e.to_string());
+                }
+            }
+        }
+
+        Ok(Import { engine, rejected })
+    }
+
+    pub fn write_json(&self, writer: impl Write) -> SnapResult<()> {
+        serde_json::to_writer_pretty(writer, self).map_err(|e| {
+            anyhow::Error::from(SnapError::with_code(
+                format!("Failed to write JSON index: {e}"),
+                ERROR_INVALID_INDEX,
+            ))
+        })
+    }
+
+    pub fn read_json(reader: impl Read) -> SnapResult<Self> {
+        serde_json::from_reader(reader).map_err(|e| {
+            anyhow::Error::from(SnapError::with_code(
+                format!("Invalid JSON index: {e}"),
+                ERROR_INVALID_INDEX,
+            ))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::time::{Duration, UNIX_EPOCH};
+
+    use tempfile::TempDir;
+
+    use super::super::crawler::CrawlFilters;
+    use super::super::search::{
+        ERROR_CONTENT_TOO_LARGE, ERROR_TOO_MANY_DOCUMENTS, MAX_DOCUMENTS, without_timestamp,
+    };
+    use super::*;
+
+    #[test]
+    fn test_binary_json_binary_round_trip() {
+        let temp_dir = TempDir::new().unwrap();
+        let original_path = temp_dir.path().join("original.idx");
+        let imported_path = temp_dir.path().join("imported.idx");
+
+        let mut engine = SearchEngine::builder()
+            .max_content_length(2_000)
+            .unwrap()
+            .build();
+        engine
+            .add_document(Path::new("notes/plan.txt"), "deploy plan")
+            .unwrap();
+        engine
+            .add_document(Path::new("src/lib.rs"), &"fn parse() {}\n".repeat(100))
+            .unwrap();
+        engine
+            .add_document(Path::new("caf\u{e9}.md"), "cr\u{e8}me br\u{fb}l\u{e9}e")
+            .unwrap();
+        engine.set_document_stamp(
+            Path::new("notes/plan.txt"),
+            FileStamp {
+                modified: UNIX_EPOCH + Duration::new(1_700_000_000, 5),
+                len: 11,
+            },
+        );
+        engine.set_crawl_filters(CrawlFilters {
+            extensions: vec!["txt".to_string()],
+            skip_hidden: true,
+            min_file_size: 0,
+        });
+        engine.save(&original_path).unwrap();
+
+        let loaded = SearchEngine::load(&original_path).unwrap();
+        let mut json = Vec::new();
+        ExportedIndex::from_engine(&loaded)
+            .write_json(&mut json)
+            .unwrap();
+
+        let exported = ExportedIndex::read_json(json.as_slice()).unwrap();
+        assert_eq!(exported.max_content_length, 2_000);
+        assert_eq!(exported.metadata, *loaded.metadata());
+        assert_eq!(exported.documents[0].content_length, 11);
+
+        let import = exported.into_engine().unwrap();
+        assert!(import.rejected.is_empty());
+        import.engine.save(&imported_path).unwrap();
+
+        let original = fs::read(&original_path).unwrap();
+        let imported = fs::read(&imported_path).unwrap();
+        assert_eq!(without_timestamp(&original), without_timestamp(&imported));
+    }
+
+    #[test]
+    fn test_import_rejects_records_over_limits() {
+        let record = |path: &str, content: &str| ExportedDocument {
+            path: path.to_string(),
+            content: content.to_string(),
+            content_length: content.len(),
+            stamp: None,
+        };
+        let mut documents = vec![
+            record(&"a".repeat(MAX_PATH_BYTES + 1), "long path"),
+            record("big.txt", &"x".repeat(11)),
+            ExportedDocument {
+                content_length: 3,
+                ..record("lossy.txt", "\u{fffd}\u{fffd}")
+            },
+        ];
+        documents.extend((0..=MAX_DOCUMENTS).map(|i| record(&format!("doc{i}.txt"), "ok")));
+        let exported = ExportedIndex {
+            max_content_length: 10,
+            metadata: IndexMetadata::default(),
+            documents,
+        };
+
+        let import = exported.into_engine().unwrap();
+        assert_eq!(import.engine.document_count(), MAX_DOCUMENTS);
+        let rejected: Vec<_> = import.rejected.iter().map(|r| (r.record, r.code)).collect();
+        assert_eq!(
+            rejected,
+            [
+                (0, ERROR_PATH_TOO_LONG),
+                (1, ERROR_CONTENT_TOO_LARGE),
+                (2, ERROR_INVALID_INDEX),
+                (MAX_DOCUMENTS + 3, ERROR_TOO_MANY_DOCUMENTS),
+            ]
+        );
+        assert_eq!(import.rejected[3].path, format!("doc{MAX_DOCUMENTS}.txt"));
+    }
+
+    #[test]
+    fn test_import_invalid_json_and_limit() {
+        let err = ExportedIndex::read_json(&b"{\"documents\": 3}"[..]).unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<SnapError>().unwrap().code(),
+            ERROR_INVALID_INDEX
+        );
+
+        let exported = ExportedIndex {
+            max_content_length: 0,
+            metadata: IndexMetadata::default(),
+            documents: Vec::new(),
+        };
+        assert!(exported.into_engine().is_err());
+    }
+}
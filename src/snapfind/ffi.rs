@@ -0,0 +1,260 @@
+//! C bindings for embedding the search engine in non-Rust programs.
+//!
+//! Functions returning `int32_t` report failure with the library's error
+//! codes (see [`ErrorCode`]). A panic never unwinds into the caller: it is
+//! caught and reported as [`ErrorCode::Other`]. The header in `include/snapfind.h` is generated
+//! from this module with `cbindgen`.
+
+use std::ffi::{CStr, c_char};
+use std::panic::{self, AssertUnwindSafe};
+use std::path::Path;
+use std::ptr;
+
+use super::error::{ErrorCode, SnapError, SnapResult};
+use super::search::{MAX_PATH_BYTES, SearchEngine};
+
+/// Bytes in [`SearchResultFFI::path`]: the longest indexable path and its
+/// terminating NUL.
+pub const FFI_PATH_BYTES: usize = 1025;
+
+const _: () = assert!(FFI_PATH_BYTES == MAX_PATH_BYTES + 1);
+
+/// One search hit. `path` is NUL-terminated and relative to the indexed root.
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct SearchResultFFI {
+    pub path: [u8; FFI_PATH_BYTES],
+    pub score: f32,
+}
+
+/// Creates an empty engine. Release it with [`snapfind_engine_free`].
+/// Returns null if the engine could not be created.
+#[unsafe(no_mangle)]
+pub extern "C" fn snapfind_engine_new() -> *mut SearchEngine {
+    guarded(|| Ok(Box::into_raw(Box::new(SearchEngine::new())))).unwrap_or(ptr::null_mut())
+}
+
+/// Frees an engine created by [`snapfind_engine_new`]. Null is ignored.
+///
+/// # Safety
+///
+/// `engine` must be null or a pointer returned by [`snapfind_engine_new`]
+/// that has not been freed yet.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn snapfind_engine_free(engine: *mut SearchEngine) {
+    if !engine.is_null() {
+        // A panic while dropping has nothing to report it to
+        let _ = guarded(|| {
+            // SAFETY: the caller passes a live pointer from `snapfind_engine_new`
+            drop(unsafe { Box::from_raw(engine) });
+            Ok(())
+        });
+    }
+}
+
+/// Adds a document. Returns 0 on success, otherwise an error code.
+///
+/// # Safety
+///
+/// `engine` must come from [`snapfind_engine_new`]; `path` and `content` must
+/// be null or NUL-terminated strings.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn snapfind_add_document(
+    engine: *mut SearchEngine,
+    path: *const c_char,
+    content: *const c_char,
+) -> i32 {
+    // SAFETY: upheld by the caller as documented above
+    let result = guarded(|| unsafe { add_document(engine, path, content) });
+    result.map_or_else(|e| code_of(&e), |()| 0)
+}
+
+/// Searches the engine and writes at most `max` hits to `out`, best first.
+/// Returns the number of hits written, or a negated error code.
+///
+/// # Safety
+///
+/// `engine` must come from [`snapfind_engine_new`], `query` must be null or a
+/// NUL-terminated string, and `out` must point to `max` writable results.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn snapfind_search(
+    engine: *mut SearchEngine,
+    query: *const c_char,
+    out: *mut SearchResultFFI,
+    max: usize,
+) -> i32 {
+    // SAFETY: upheld by the caller as documented above
+    let result = guarded(|| unsafe { search(engine, query, out, max) });
+    result.map_or_else(|e| -code_of(&e), |written| i32::try_from(written).unwrap())
+}
+
+unsafe fn add_document(
+    engine: *mut SearchEngine,
+    path: *const c_char,
+    content: *const c_char,
+) -> SnapResult<()> {
+    // SAFETY: the caller guarantees the pointers are valid or null
+    let engine = unsafe { engine.as_mut() }.ok_or_else(|| invalid("Engine is null"))?;
+    let path = unsafe { c_str(path, "Path") }?;
+    let content = unsafe { c_str(content, "Content") }?;
+    engine.add_document(Path::new(path), content)
+}
+
+unsafe fn search(
+    engine: *mut SearchEngine,
+    query: *const c_char,
+    out: *mut SearchResultFFI,
+    max: usize,
+) -> SnapResult<usize> {
+    // SAFETY: the caller guarantees the pointers are valid or null
+    let engine = unsafe { engine.as_ref() }.ok_or_else(|| invalid("Engine is null"))?;
+    let query = unsafe { c_str(query, "Query") }?;
+    if out.is_null() && max > 0 {
+        return Err(invalid("Result buffer is null"));
+    }
+
+    let results = engine.search(query)?;
+    let written = results.len().min(max);
+    for (i, result) in results.iter().take(written).enumerate() {
+        let mut entry = SearchResultFFI {
+            path: [0; FFI_PATH_BYTES],
+            score: result.score,
+        };
+        let path = result.path.to_string_lossy();
+        let len = path.len().min(MAX_PATH_BYTES);
+        entry.path[..len].copy_from_slice(&path.as_bytes()[..len]);
+        // SAFETY: `out` holds at least `max` results and `i < written <= max`
+        unsafe { ptr::write(out.add(i), entry) };
+    }
+    Ok(written)
+}
+
+unsafe fn c_str<'a>(ptr: *const c_char, what: &str) -> SnapResult<&'a str> {
+    if ptr.is_null() {
+        return Err(invalid(&format!("{what} is null")));
+    }
+    // SAFETY: the caller guarantees `ptr` is NUL-terminated
+    unsafe { CStr::from_ptr(ptr) }
+        .to_str()
+        .map_err(|_| invalid(&format!("{what} is not valid UTF-8")))
+}
+
+/// Runs `f`, turning a panic into an error so it cannot unwind across the
+/// C boundary.
+fn guarded<T>(f: impl FnOnce() -> SnapResult<T>) -> SnapResult<T> {
+    panic::catch_unwind(AssertUnwindSafe(f))
+        .unwrap_or_else(|_| Err(invalid("Internal error: snapfind panicked")))
+}
+
+fn invalid(msg: &str) -> anyhow::Error {
+    anyhow::Error::from(SnapError::with_code(msg, ErrorCode::Other.code()))
+}
+
+fn code_of(error: &anyhow::Error) -> i32 {
+    error
+        .downcast_ref::<SnapError>()
+        .map_or(ErrorCode::Other.code(), SnapError::code)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ffi::CString;
+
+    use super::super::search::{ERROR_INVALID_QUERY, ERROR_TOO_MANY_DOCUMENTS, MAX_DOCUMENTS};
+    use super::*;
+
+    fn empty_results<const N: usize>() -> [SearchResultFFI; N] {
+        [SearchResultFFI {
+            path: [0; FFI_PATH_BYTES],
+            score: 0.0,
+        }; N]
+    }
+
+    fn path_of(result: &SearchResultFFI) -> &str {
+        CStr::from_bytes_until_nul(&result.path)
+            .unwrap()
+            .to_str()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_add_and_search() {
+        let engine = snapfind_engine_new();
+        let docs = [("notes/plan.txt", "deploy plan"), ("src/lib.rs", "parser")];
+        for (path, content) in docs {
+            let path = CString::new(path).unwrap();
+            let content = CString::new(content).unwrap();
+            let code = unsafe { snapfind_add_document(engine, path.as_ptr(), content.as_ptr()) };
+            assert_eq!(code, 0);
+        }
+
+        let query = CString::new("deploy").unwrap();
+        let mut out = empty_results::<4>();
+        let written = unsafe { snapfind_search(engine, query.as_ptr(), out.as_mut_ptr(), 4) };
+        assert_eq!(written, 1);
+        assert_eq!(path_of(&out[0]), "notes/plan.txt");
+        assert!(out[0].score > 0.0);
+
+        unsafe { snapfind_engine_free(engine) };
+    }
+
+    #[test]
+    fn test_search_respects_max() {
+        let engine = snapfind_engine_new();
+        for i in 0..3 {
+            let path = CString::new(format!("log{i}.txt")).unwrap();
+            let content = CString::new("log entry").unwrap();
+            unsafe { snapfind_add_document(engine, path.as_ptr(), content.as_ptr()) };
+        }
+
+        let query = CString::new("log").unwrap();
+        let mut out = empty_results::<2>();
+        let written = unsafe { snapfind_search(engine, query.as_ptr(), out.as_mut_ptr(), 2) };
+        assert_eq!(written, 2);
+        let none = unsafe { snapfind_search(engine, query.as_ptr(), ptr::null_mut(), 0) };
+        assert_eq!(none, 0);
+
+        unsafe { snapfind_engine_free(engine) };
+    }
+
+    #[test]
+    fn test_errors_are_codes() {
+        let engine = snapfind_engine_new();
+        let content = CString::new("text").unwrap();
+
+        let code = unsafe { snapfind_add_document(engine, ptr::null(), content.as_ptr()) };
+        assert_eq!(code, ErrorCode::Other.code());
+        let code =
+            unsafe { snapfind_add_document(ptr::null_mut(), content.as_ptr(), content.as_ptr()) };
+        assert_eq!(code, ErrorCode::Other.code());
+
+        for i in 0..=MAX_DOCUMENTS {
+            let path = CString::new(format!("doc{i}.txt")).unwrap();
+            let code = unsafe { snapfind_add_document(engine, path.as_ptr(), content.as_ptr()) };
+            let expected = if i < MAX_DOCUMENTS {
+                0
+            } else {
+                ERROR_TOO_MANY_DOCUMENTS
+            };
+            assert_eq!(code, expected);
+        }
+
+        let query = CString::new("").unwrap();
+        let mut out = empty_results::<1>();
+        let code = unsafe { snapfind_search(engine, query.as_ptr(), out.as_mut_ptr(), 1) };
+        assert_eq!(code, -ERROR_INVALID_QUERY);
+
+        unsafe { snapfind_engine_free(engine) };
+        unsafe { snapfind_engine_free(ptr::null_mut()) };
+    }
+
+    #[test]
+    fn test_panics_become_error_codes() {
+        let result: SnapResult<()> = guarded(|| panic!("engine bug"));
+        let err = result.unwrap_err();
+        assert_eq!(code_of(&err), ErrorCode::Other.code());
+        assert!(err.to_string().contains("panicked"));
+
+        assert_eq!(guarded(|| Ok(7)).unwrap(), 7);
+    }
+}
@@ -0,0 +1,539 @@
+use std::io;
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use super::crawler::{Crawler, CrawlerConfig};
+use super::error::{SnapError, SnapResult};
+use super::lock;
+use super::progress::{NoProgress, ProgressSink};
+use super::search::{
+    AddOutcome, ERROR_INVALID_INDEX, FileStamp, INDEX_FILE_NAME, MAX_CONTENT_LENGTH, SearchEngine,
+};
+use super::text::TextDetector;
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct IndexSummary {
+    pub files_indexed: usize,
+    pub read_errors: usize,
+    pub truncated: usize,
+}
+
+pub fn index_into(
+    engine: &mut SearchEngine,
+    crawler: &mut Crawler,
+    detector: &mut TextDetector,
+    sink: &mut dyn ProgressSink,
+) -> SnapResult<IndexSummary> {
+    index_into_with_jobs(engine, crawler, detector, sink, 1)
+}
+
+/// Like [`index_into`], but reads and checks each batch's files on `jobs`
+/// threads. Documents are still added from this thread in batch order, so
+/// the index comes out the same for any number of jobs.
+pub fn index_into_with_jobs(
+    engine: &mut SearchEngine,
+    crawler: &mut Crawler,
+    detector: &mut TextDetector,
+    sink: &mut dyn ProgressSink,
+    jobs: usize,
+) -> SnapResult<IndexSummary> {
+    let mut summary = IndexSummary::default();
+    let mut last_processed = 0;
+    let mut last_dirs = 0;
+
+    while crawler.process_next()?.is_some() {
+        let (processed, max_files, dirs) = crawler.progress();
+
+        assert!(processed >= last_processed, "File count must not decrease");
+        assert!(dirs >= last_dirs, "Directory count must not decrease");
+        last_processed = processed;
+        last_dirs = dirs;
+
+        let mut last_indexed = None;
+        let mut non_text = 0;
+        let texts = read_batch(detector, crawler.batch(), jobs);
+        for (file, read) in crawler.batch().iter().zip(texts) {
+            let Some((stamp, text)) = read else {
+                continue;
+            };
+
+            match text {
+                Ok(Some(text)) => {
+                    let relative = file.strip_prefix(crawler.root()).unwrap_or(file);
+                    let outcome = engine.add_document_truncating(relative, &text)?;
+                    if let Some(stamp) = stamp {
+                        engine.set_document_stamp(relative, stamp);
+                    }
+                    if let AddOutcome::Truncated {
+                        original_len,
+                        stored_len,
+                    } = outcome
+                    {
+                        summary.truncated += 1;
+                        sink.on_truncated(file, original_len, stored_len);
+                    }
+                    summary.files_indexed += 1;
+                    last_indexed = Some(file);
+                }
+                Ok(None) => non_text += 1,
+                Err(e) => {
+                    summary.read_errors += 1;
+                    sink.on_read_error(file, &e);
+                }
+            }
+        }
+
+        if let Some(file) = last_indexed {
+            sink.on_batch(processed, max_files, dirs, file);
+        }
+        crawler.record_non_text(non_text);
+    }
+
+    Ok(summary)
+}
+
+/// A file's stamp, taken before reading it, and what [`read_text`] returned.
+type FileRead = (Option<FileStamp>, io::Result<Option<String>>);
+
+/// Reads every file of a batch with [`read_text`], in batch order. Index
+/// files are skipped and come back as `None`.
+fn read_batch(
+    detector: &mut TextDetector,
+    files: &[PathBuf],
+    jobs: usize,
+) -> Vec<Option<FileRead>> {
+    fn read(detector: &mut TextDetector, file: &Path) -> Option<FileRead> {
+        let is_index = file.file_name().is_some_and(|name| name == INDEX_FILE_NAME);
+        // Stamped first, so a write during the read shows up as a change
+        (!is_index).then(|| (FileStamp::of(file), read_text(detector, file)))
+    }
+
+    if jobs <= 1 || files.len() <= 1 {
+        return files.iter().map(|file| read(detector, file)).collect();
+    }
+
+    let chunk_size = files.len().div_ceil(jobs);
+    thread::scope(|scope| {
+        let workers: Vec<_> = files
+            .chunks(chunk_size)
+            .map(|chunk| {
+                scope.spawn(move || {
+                    let mut detector = TextDetector::new();
+                    chunk
+                        .iter()
+                        .map(|file| read(&mut detector, file))
+                        .collect::<Vec<_>>()
+                })
+            })
+            .collect();
+        workers
+            .into_iter()
+            .flat_map(|worker| worker.join().expect("Indexing worker panicked"))
+            .collect()
+    })
+}
+
+/// Reads `file` as text, or returns `None` if the detector finds it is not text.
+pub fn read_text(detector: &mut TextDetector, file: &Path) -> io::Result<Option<String>> {
+    let (validation, sampled) = detector.validate_file(file)?;
+    if !validation.is_valid_text() {
+        return Ok(None);
+    }
+    let content = sampled.read_content()?;
+    Ok(Some(validation.decode_lossy(&content)))
+}
+
+/// Settings for [`index_directory`].
+#[derive(Debug, Clone)]
+pub struct IndexConfig {
+    pub crawler: CrawlerConfig,
+    /// Bytes of content kept per document.
+    pub max_content_length: usize,
+    /// Threads reading files; see [`index_into_with_jobs`].
+    pub jobs: usize,
+    /// Where the index is written. `None` means [`INDEX_FILE_NAME`] in the root.
+    pub index_path: Option<PathBuf>,
+    /// Only index files with these extensions; empty indexes every file. See
+    /// [`Crawler::with_extensions`].
+    pub extensions: Vec<String>,
+}
+
+impl Default for IndexConfig {
+    fn default() -> Self {
+        Self {
+            crawler: CrawlerConfig::default(),
+            max_content_length: MAX_CONTENT_LENGTH,
+            jobs: 1,
+            index_path: None,
+            extensions: Vec::new(),
+        }
+    }
+}
+
+/// Outcome of indexing a directory.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct IndexReport {
+    pub files_indexed: usize,
+    pub files_found: usize,
+    pub directories: usize,
+    pub max_depth: usize,
+    pub bytes_visited: u64,
+    pub errors_skipped: usize,
+    pub skipped_non_text: usize,
+    pub skipped_too_large: usize,
+    pub skipped_too_small: usize,
+    pub read_errors: usize,
+    pub truncated: usize,
+    /// Where the index was written, or `None` when there was nothing to save
+    pub index_path: Option<PathBuf>,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub elapsed: Duration,
+}
+
+/// An indexing run, with the engine and crawler kept for callers that go on
+/// using them.
+#[derive(Debug)]
+pub struct IndexRun {
+    pub engine: SearchEngine,
+    pub crawler: Crawler,
+    pub report: IndexReport,
+}
+
+/// Crawls `root`, adds every text file to a new index and saves it.
+pub fn index_directory(root: &Path, config: &IndexConfig) -> SnapResult<IndexReport> {
+    index_directory_with(root, config, &mut NoProgress).map(|run| run.report)
+}
+
+/// Like [`index_directory`], reporting progress to `sink`.
+///
+/// Fails if files were found but none could be read. Nothing is saved when
+/// no file was indexed.
+pub fn index_directory_with(
+    root: &Path,
+    config: &IndexConfig,
+    sink: &mut dyn ProgressSink,
+) -> SnapResult<IndexRun> {
+    let started = Instant::now();
+    let mut run = build_index(root, config, sink)?;
+
+    if run.report.files_indexed == 0 && run.report.read_errors > 0 {
+        return Err(anyhow::Error::from(SnapError::with_code(
+            "Failed to index any files due to errors. Check file permissions and try again.",
+            ERROR_INVALID_INDEX,
+        )));
+    }
+
+    if run.report.files_indexed > 0 {
+        let index_path = config
+            .index_path
+            .clone()
+            .unwrap_or_else(|| root.join(INDEX_FILE_NAME));
+        run.engine.save(&index_path)?;
+        run.report.index_path = Some(index_path);
+    }
+    run.report.elapsed = started.elapsed();
+
+    Ok(run)
+}
+
+/// Crawls `root` into a new engine without saving it, as
+/// [`index_directory_with`] does before writing the index.
+pub fn build_index(
+    root: &Path,
+    config: &IndexConfig,
+    sink: &mut dyn ProgressSink,
+) -> SnapResult<IndexRun> {
+    let started = Instant::now();
+    let mut engine = SearchEngine::builder()
+        .max_content_length(config.max_content_length)?
+        .build();
+    let extensions: Vec<_> = config.extensions.iter().map(String::as_str).collect();
+    let mut crawler = Crawler::with_config(root, config.crawler)?
+        .with_error_recovery(true)
+        .with_extensions(&extensions)?;
+    exclude_index_files(&mut crawler)?;
+    if let Some(name) = config.index_path.as_deref().and_then(Path::file_name) {
+        crawler.exclude_name(&name.to_string_lossy())?;
+    }
+    engine.set_crawl_filters(crawler.filters());
+    let mut detector = TextDetector::new();
+    let summary =
+        index_into_with_jobs(&mut engine, &mut crawler, &mut detector, sink, config.jobs)?;
+
+    let stats = crawler.stats();
+    let report = IndexReport {
+        files_indexed: summary.files_indexed,
+        files_found: stats.files_processed,
+        directories: stats.dirs_processed,
+        max_depth: stats.max_depth_reached,
+        bytes_visited: stats.bytes_visited,
+        errors_skipped: stats.errors_skipped,
+        skipped_non_text: stats.skipped_non_text,
+        skipped_too_large: stats.skipped_too_large,
+        skipped_too_small: stats.skipped_too_small,
+        read_errors: summary.read_errors,
+        truncated: summary.truncated,
+        index_path: None,
+        elapsed: started.elapsed(),
+    };
+
+    Ok(IndexRun {
+        engine,
+        crawler,
+        report,
+    })
+}
+
+/// Keeps the index file and its lock and temporary siblings out of a crawl.
+pub fn exclude_index_files(crawler: &mut Crawler) -> SnapResult<()> {
+    crawler.exclude_name(INDEX_FILE_NAME)?;
+    crawler.exclude_name(&format!("{INDEX_FILE_NAME}{}", lock::LOCK_SUFFIX))?;
+    crawler.exclude_name(&format!("{INDEX_FILE_NAME}{}", lock::TMP_SUFFIX))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::{self, File};
+    use std::io::Write;
+    use std::path::PathBuf;
+    use std::time::UNIX_EPOCH;
+
+    use tempfile::TempDir;
+
+    use super::*;
+    use crate::progress::NoProgress;
+    use crate::search::without_timestamp;
+
+    #[derive(Default)]
+    struct RecordingSink {
+        batches: Vec<(usize, usize, PathBuf)>,
+        truncated: Vec<(PathBuf, usize, usize)>,
+    }
+
+    impl ProgressSink for RecordingSink {
+        fn on_batch(&mut self, processed: usize, _max: usize, dirs: usize, last_file: &Path) {
+            self.batches
+                .push((processed, dirs, last_file.to_path_buf()));
+        }
+
+        fn on_truncated(&mut self, path: &Path, original_len: usize, stored_len: usize) {
+            self.truncated
+                .push((path.to_path_buf(), original_len, stored_len));
+        }
+    }
+
+    fn create_test_file(dir: &Path, name: &str, content: &[u8]) -> PathBuf {
+        let path = dir.join(name);
+        let mut file = File::create(&path).unwrap();
+        file.write_all(content).unwrap();
+        path
+    }
+
+    #[test]
+    fn test_index_into_reports_batches() {
+        let temp_dir = TempDir::new().unwrap();
+        create_test_file(temp_dir.path(), "a.txt", b"alpha content\nmore\n");
+        let subdir = temp_dir.path().join("sub");
+        fs::create_dir(&subdir).unwrap();
+        let nested = create_test_file(&subdir, "b.txt", b"beta content\nmore\n");
+        create_test_file(temp_dir.path(), "c.bin", b"\x7FELF\x00\x00\x00\x00");
+
+        let mut engine = SearchEngine::new();
+        let mut crawler = Crawler::new(temp_dir.path()).unwrap();
+        let mut detector = TextDetector::new();
+        let mut sink = RecordingSink::default();
+
+        let summary = index_into(&mut engine, &mut crawler, &mut detector, &mut sink).unwrap();
+
+        assert_eq!(summary.files_indexed, 2);
+        assert_eq!(summary.read_errors, 0);
+        assert_eq!(crawler.stats().skipped_non_text, 1);
+        assert_eq!(sink.batches.len(), 2);
+        assert_eq!(sink.batches[1], (3, 2, nested));
+
+        let results = engine.search("beta").unwrap();
+        assert_eq!(results.len(), 1);
+    }
+
+    #[test]
+    fn test_reindex_skips_index_file() {
+        let temp_dir = TempDir::new().unwrap();
+        create_test_file(temp_dir.path(), "a.txt", b"alpha\nbeta\n");
+        create_test_file(temp_dir.path(), "b.md", b"# Notes\n- item\n");
+        let index_path = temp_dir.path().join(INDEX_FILE_NAME);
+
+        let mut counts = Vec::new();
+        for _ in 0..2 {
+            let mut engine = SearchEngine::new();
+            let mut crawler = Crawler::new(temp_dir.path()).unwrap();
+            let mut detector = TextDetector::new();
+            let summary =
+                index_into(&mut engine, &mut crawler, &mut detector, &mut NoProgress).unwrap();
+            engine.save(&index_path).unwrap();
+            counts.push(summary.files_indexed);
+        }
+        assert_eq!(counts, vec![2, 2]);
+
+        let engine = SearchEngine::load(&index_path).unwrap();
+        for query in ["snapfind_index", "*index", "alpha"] {
+            let results = engine.search(query).unwrap();
+            assert!(results.iter().all(|r| !r.path.ends_with(INDEX_FILE_NAME)));
+        }
+    }
+
+    #[test]
+    fn test_index_into_truncates_large_files() {
+        let temp_dir = TempDir::new().unwrap();
+        // The limit falls inside a two-byte 'é', so one byte less is stored
+        let mut content = format!("x{}", "wordé ".repeat(200));
+        content.push_str("needle\n");
+        let large = create_test_file(temp_dir.path(), "large.txt", content.as_bytes());
+        create_test_file(temp_dir.path(), "small.txt", b"short file\nneedle\n");
+
+        let mut engine = SearchEngine::new();
+        let mut crawler = Crawler::new(temp_dir.path()).unwrap();
+        let mut detector = TextDetector::new();
+        let mut sink = RecordingSink::default();
+        let summary = index_into(&mut engine, &mut crawler, &mut detector, &mut sink).unwrap();
+
+        assert_eq!(summary.files_indexed, 2);
+        assert_eq!(summary.truncated, 1);
+        let stored_len = engine.max_content_length() - 1;
+        assert_eq!(sink.truncated, vec![(large, content.len(), stored_len)]);
+        assert_eq!(
+            engine
+                .get_document(Path::new("large.txt"))
+                .unwrap()
+                .content
+                .len(),
+            stored_len
+        );
+
+        let results = engine.search("word").unwrap();
+        assert_eq!(results.len(), 1);
+        let results = engine.search("needle").unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].path, Path::new("small.txt"));
+    }
+
+    #[test]
+    fn test_index_into_keeps_stray_latin1_byte_searchable() {
+        let temp_dir = TempDir::new().unwrap();
+        // 0xE9 is `é` in Latin-1 and not valid UTF-8 on its own
+        let content = b"quarterly report for the caf\xE9 downtown\nrevenue summary\n";
+        let file = create_test_file(temp_dir.path(), "notes.txt", content);
+
+        let mut detector = TextDetector::new();
+        let text = read_text(&mut detector, &file).unwrap().unwrap();
+        assert!(text.contains("caf\u{FFFD} downtown"));
+
+        let mut engine = SearchEngine::new();
+        let mut crawler = Crawler::new(temp_dir.path()).unwrap();
+        let summary =
+            index_into(&mut engine, &mut crawler, &mut detector, &mut NoProgress).unwrap();
+        assert_eq!(summary.files_indexed, 1);
+        for query in ["quarterly", "downtown", "revenue"] {
+            let results = engine.search(query).unwrap();
+            assert_eq!(results.len(), 1, "{query}");
+        }
+    }
+
+    #[test]
+    fn test_index_is_reproducible() {
+        let files = [
+            ("readme.md", "# Project\nintro text\n"),
+            ("src/main.rs", "fn main() {}\n"),
+            ("src/lib.rs", "pub mod search;\n"),
+            ("docs/guide.md", "# Guide\nsteps\n"),
+            ("docs/api/index.md", "# API\nreference\n"),
+        ];
+        let out = TempDir::new().unwrap();
+
+        let mut indexes = Vec::new();
+        for reverse in [false, true] {
+            // Create the same tree in a different order each time
+            let temp_dir = TempDir::new().unwrap();
+            let mut order: Vec<_> = files.iter().collect();
+            if reverse {
+                order.reverse();
+            }
+            for (path, content) in order {
+                let path = temp_dir.path().join(path);
+                fs::create_dir_all(path.parent().unwrap()).unwrap();
+                fs::write(&path, content).unwrap();
+                // File stamps are stored, so the trees must agree on them too
+                File::options()
+                    .write(true)
+                    .open(&path)
+                    .unwrap()
+                    .set_modified(UNIX_EPOCH + Duration::from_secs(1_700_000_000))
+                    .unwrap();
+            }
+
+            let mut engine = SearchEngine::new();
+            let mut crawler = Crawler::new(temp_dir.path()).unwrap();
+            let mut detector = TextDetector::new();
+            index_into(&mut engine, &mut crawler, &mut detector, &mut NoProgress).unwrap();
+
+            let index_path = out.path().join(format!("index_{reverse}"));
+            engine.save(&index_path).unwrap();
+            indexes.push(without_timestamp(&fs::read(&index_path).unwrap()));
+        }
+
+        assert_eq!(indexes[0], indexes[1]);
+    }
+
+    #[test]
+    fn test_parallel_jobs_match_sequential() {
+        let temp_dir = TempDir::new().unwrap();
+        for i in 0..40 {
+            let dir = temp_dir.path().join(format!("dir_{}", i % 4));
+            fs::create_dir_all(&dir).unwrap();
+            fs::write(
+                dir.join(format!("file_{i}.txt")),
+                format!("note {i}\nshared words\n"),
+            )
+            .unwrap();
+        }
+        fs::write(temp_dir.path().join("blob.bin"), [0_u8; 64]).unwrap();
+        let out = TempDir::new().unwrap();
+
+        let mut indexes = Vec::new();
+        for jobs in [1, 4] {
+            let mut engine = SearchEngine::new();
+            let mut crawler = Crawler::new(temp_dir.path()).unwrap();
+            let mut detector = TextDetector::new();
+            let summary = index_into_with_jobs(
+                &mut engine,
+                &mut crawler,
+                &mut detector,
+                &mut NoProgress,
+                jobs,
+            )
+            .unwrap();
+            assert_eq!(summary.files_indexed, 40);
+            assert_eq!(crawler.stats().skipped_non_text, 1);
+
+            let index_path = out.path().join(format!("index_{jobs}"));
+            engine.save(&index_path).unwrap();
+            indexes.push(without_timestamp(&fs::read(&index_path).unwrap()));
+        }
+
+        assert_eq!(indexes[0], indexes[1]);
+    }
+
+    #[test]
+    fn test_index_into_empty_directory() {
+        let temp_dir = TempDir::new().unwrap();
+
+        let mut engine = SearchEngine::new();
+        let mut crawler = Crawler::new(temp_dir.path()).unwrap();
+        let mut detector = TextDetector::new();
+
+        let summary =
+            index_into(&mut engine, &mut crawler, &mut detector, &mut NoProgress).unwrap();
+        assert_eq!(summary, IndexSummary::default());
+    }
+}
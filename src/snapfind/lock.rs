@@ -0,0 +1,282 @@
+use std::ffi::OsString;
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant, SystemTime};
+use std::{process, thread};
+
+use super::error::{SnapError, SnapResult};
+use super::search::{ERROR_INDEX_LOCKED, ERROR_INVALID_INDEX};
+
+pub const LOCK_SUFFIX: &str = ".lock";
+pub const TMP_SUFFIX: &str = ".tmp";
+pub const DEFAULT_STALE_AGE: Duration = Duration::from_secs(5 * 60);
+pub const DEFAULT_WAIT: Duration = Duration::from_secs(2);
+
+const POLL_INTERVAL: Duration = Duration::from_millis(25);
+
+static TOKEN_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+/// Advisory lock held next to an index file for as long as the guard lives.
+///
+/// The lock file holds the owner's pid and a token unique to this lock, so a
+/// process taking over a stale lock can tell whether it moved the lock it
+/// judged stale or one created since.
+#[derive(Debug)]
+pub struct IndexLock {
+    path: PathBuf,
+    token: String,
+}
+
+impl IndexLock {
+    pub fn acquire(index_path: &Path) -> SnapResult<Self> {
+        Self::acquire_with(index_path, DEFAULT_STALE_AGE, DEFAULT_WAIT)
+    }
+
+    pub fn acquire_with(
+        index_path: &Path,
+        stale_age: Duration,
+        wait: Duration,
+    ) -> SnapResult<Self> {
+        let path = lock_path(index_path);
+        let deadline = Instant::now() + wait;
+
+        loop {
+            match OpenOptions::new().write(true).create_new(true).open(&path) {
+                Ok(mut file) => {
+                    // The pid is informational only; staleness is judged by age
+                    let token = new_token();
+                    let _ = write!(file, "{}\n{token}\n", process::id());
+                    return Ok(Self { path, token });
+                }
+                Err(e) if e.kind() == io::ErrorKind::AlreadyExists => {
+                    if remove_if_stale(&path, stale_age) {
+                        continue;
+                    }
+                    if Instant::now() >= deadline {
+                        return Err(locked_error(index_path, &path));
+                    }
+                    thread::sleep(POLL_INTERVAL);
+                }
+                Err(e) => {
+                    return Err(anyhow::Error::from(SnapError::with_code(
+                        format!("Failed to create lock file {}: {e}", path.display()),
+                        ERROR_INVALID_INDEX,
+                    )));
+                }
+            }
+        }
+    }
+
+    /// Blocks until no live writer holds the lock, without taking it.
+    pub fn wait_until_free(index_path: &Path) -> SnapResult<()> {
+        Self::wait_until_free_with(index_path, DEFAULT_STALE_AGE, DEFAULT_WAIT)
+    }
+
+    pub fn wait_until_free_with(
+        index_path: &Path,
+        stale_age: Duration,
+        wait: Duration,
+    ) -> SnapResult<()> {
+        let path = lock_path(index_path);
+        let deadline = Instant::now() + wait;
+
+        while path.exists() && !remove_if_stale(&path, stale_age) {
+            if Instant::now() >= deadline {
+                return Err(locked_error(index_path, &path));
+            }
+            thread::sleep(POLL_INTERVAL);
+        }
+
+        Ok(())
+    }
+
+    #[must_use]
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for IndexLock {
+    fn drop(&mut self) {
+        // Only remove the lock if it is still ours and was not taken over
+        let ours = fs::read_to_string(&self.path)
+            .is_ok_and(|contents| contents.lines().nth(1) == Some(self.token.as_str()));
+        if ours {
+            let _ = fs::remove_file(&self.path);
+        }
+    }
+}
+
+#[must_use]
+pub fn lock_path(index_path: &Path) -> PathBuf {
+    with_suffix(index_path, LOCK_SUFFIX)
+}
+
+#[must_use]
+pub fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = OsString::from(path.as_os_str());
+    name.push(suffix);
+    PathBuf::from(name)
+}
+
+fn new_token() -> String {
+    let nanos = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_nanos());
+    let count = TOKEN_COUNTER.fetch_add(1, Ordering::Relaxed);
+    format!("{}-{nanos:x}-{count}", process::id())
+}
+
+fn is_stale(lock: &Path, stale_age: Duration) -> Option<bool> {
+    let modified = fs::metadata(lock).and_then(|m| m.modified()).ok()?;
+    Some(
+        SystemTime::now()
+            .duration_since(modified)
+            .is_ok_and(|age| age >= stale_age),
+    )
+}
+
+/// Removes `lock` if it is older than `stale_age`, returning whether it is
+/// gone.
+///
+/// Two processes can both judge the same lock stale, and one of them may
+/// already have replaced it with a live lock by the time the other acts. So
+/// the lock is first renamed to a name only this process uses, which at most
+/// one process can do, and the moved file is checked again: if it is not the
+/// stale lock that was inspected, it is put back.
+fn remove_if_stale(lock: &Path, stale_age: Duration) -> bool {
+    match is_stale(lock, stale_age) {
+        Some(true) => {}
+        Some(false) => return false,
+        // Vanished between the open and the metadata call
+        None => return !lock.exists(),
+    }
+    match fs::read(lock) {
+        Ok(inspected) => take_over(lock, stale_age, &inspected),
+        Err(_) => !lock.exists(),
+    }
+}
+
+/// Moves `lock` aside and deletes it if it still is the stale lock whose
+/// contents were `inspected`; otherwise puts it back.
+fn take_over(lock: &Path, stale_age: Duration, inspected: &[u8]) -> bool {
+    let aside = with_suffix(lock, &format!(".stale-{}", new_token()));
+    if fs::rename(lock, &aside).is_err() {
+        // Another process moved it first
+        return !lock.exists();
+    }
+
+    let same_lock = is_stale(&aside, stale_age) == Some(true)
+        && fs::read(&aside).is_ok_and(|contents| contents == inspected);
+    if !same_lock {
+        // A live lock was moved by mistake; restore it unless yet another
+        // process has locked the index in the meantime
+        let _ = fs::hard_link(&aside, lock);
+    }
+    let _ = fs::remove_file(&aside);
+    same_lock
+}
+
+fn locked_error(index_path: &Path, lock: &Path) -> anyhow::Error {
+    anyhow::Error::from(SnapError::with_code(
+        format!(
+            "{} is locked by another snapfind process; wait for it to finish or remove {} if it \
+             crashed",
+            index_path.display(),
+            lock.display()
+        ),
+        ERROR_INDEX_LOCKED,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use tempfile::TempDir;
+
+    use super::*;
+
+    #[test]
+    fn test_lock_is_released_on_drop() {
+        let temp_dir = TempDir::new().unwrap();
+        let index = temp_dir.path().join("index");
+
+        let lock = IndexLock::acquire(&index).unwrap();
+        assert!(lock.path().exists());
+        assert_eq!(lock.path(), temp_dir.path().join("index.lock"));
+
+        drop(lock);
+        assert!(!lock_path(&index).exists());
+    }
+
+    #[test]
+    fn test_second_acquire_fails_fast() {
+        let temp_dir = TempDir::new().unwrap();
+        let index = temp_dir.path().join("index");
+
+        let _held = IndexLock::acquire(&index).unwrap();
+        let err = IndexLock::acquire_with(&index, DEFAULT_STALE_AGE, Duration::ZERO).unwrap_err();
+        let snap_err = err.downcast_ref::<SnapError>().unwrap();
+        assert_eq!(snap_err.code(), ERROR_INDEX_LOCKED);
+        assert!(err.to_string().contains("another snapfind process"));
+    }
+
+    #[test]
+    fn test_stale_lock_is_cleaned_up() {
+        let temp_dir = TempDir::new().unwrap();
+        let index = temp_dir.path().join("index");
+        fs::write(lock_path(&index), "12345\n").unwrap();
+
+        assert!(IndexLock::acquire_with(&index, DEFAULT_STALE_AGE, Duration::ZERO).is_err());
+        assert!(
+            IndexLock::wait_until_free_with(&index, DEFAULT_STALE_AGE, Duration::ZERO).is_err()
+        );
+
+        thread::sleep(Duration::from_millis(20));
+        let lock =
+            IndexLock::acquire_with(&index, Duration::from_millis(10), Duration::ZERO).unwrap();
+        let contents = fs::read_to_string(lock.path()).unwrap();
+        assert_eq!(
+            contents.lines().next(),
+            Some(process::id().to_string().as_str())
+        );
+    }
+
+    #[test]
+    fn test_takeover_restores_a_replaced_lock() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = lock_path(&temp_dir.path().join("index"));
+        fs::write(&path, "2\nlive-token\n").unwrap();
+
+        // Another process swapped in a live lock after this one was inspected
+        assert!(!take_over(&path, Duration::ZERO, b"1\nstale-token\n"));
+        assert_eq!(fs::read_to_string(&path).unwrap(), "2\nlive-token\n");
+
+        assert!(take_over(&path, Duration::ZERO, b"2\nlive-token\n"));
+        assert!(!path.exists());
+        assert_eq!(fs::read_dir(temp_dir.path()).unwrap().count(), 0);
+    }
+
+    #[test]
+    fn test_drop_leaves_a_lock_taken_over_by_others() {
+        let temp_dir = TempDir::new().unwrap();
+        let index = temp_dir.path().join("index");
+
+        let lock = IndexLock::acquire(&index).unwrap();
+        assert!(remove_if_stale(lock.path(), Duration::ZERO));
+        fs::write(lock_path(&index), "2\nother-token\n").unwrap();
+        drop(lock);
+        assert!(lock_path(&index).exists());
+    }
+
+    #[test]
+    fn test_wait_until_free_removes_stale_lock() {
+        let temp_dir = TempDir::new().unwrap();
+        let index = temp_dir.path().join("index");
+        fs::write(lock_path(&index), "").unwrap();
+
+        IndexLock::wait_until_free_with(&index, Duration::ZERO, Duration::ZERO).unwrap();
+        assert!(!lock_path(&index).exists());
+    }
+}
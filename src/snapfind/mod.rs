@@ -1,4 +1,17 @@
+mod checksum;
+mod compress;
 pub mod crawler;
 pub mod error;
+#[cfg(feature = "serde")]
+pub mod export;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+pub mod indexer;
+pub mod lock;
+#[cfg(feature = "unicode")]
+pub mod normalize;
+pub mod progress;
 pub mod search;
+pub mod split;
 pub mod text;
+pub mod watch;
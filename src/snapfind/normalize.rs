@@ -0,0 +1,73 @@
+use std::borrow::Cow;
+use std::path::{Path, PathBuf};
+
+use unicode_normalization::{IsNormalized, UnicodeNormalization, is_nfc_quick};
+
+/// Returns `text` in Unicode Normalization Form C, borrowing it when it is
+/// already normalized.
+#[must_use]
+pub fn nfc(text: &str) -> Cow<'_, str> {
+    if is_nfc_quick(text.chars()) == IsNormalized::Yes {
+        return Cow::Borrowed(text);
+    }
+
+    let out: String = text.nfc().collect();
+    if out == text {
+        Cow::Borrowed(text)
+    } else {
+        Cow::Owned(out)
+    }
+}
+
+/// Applies [`nfc`] to a path that is valid UTF-8, leaving other paths untouched.
+#[must_use]
+pub fn nfc_path(path: &Path) -> Cow<'_, Path> {
+    match path.to_str().map(nfc) {
+        Some(Cow::Owned(normalized)) => Cow::Owned(PathBuf::from(normalized)),
+        _ => Cow::Borrowed(path),
+    }
+}
+
+#[must_use]
+pub fn is_combining_mark(c: char) -> bool {
+    unicode_normalization::char::is_combining_mark(c)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_nfc_composes_latin() {
+        assert_eq!(nfc("cafe\u{0301}"), "caf\u{00e9}");
+        assert_eq!(nfc("A\u{030a}ngstro\u{0308}m"), "\u{00c5}ngstr\u{00f6}m");
+        assert_eq!(nfc("u\u{0308}\u{0304}"), "\u{01d6}");
+        assert!(matches!(nfc("caf\u{00e9}"), Cow::Borrowed(_)));
+        assert!(matches!(nfc("plain ascii"), Cow::Borrowed(_)));
+    }
+
+    #[test]
+    fn test_nfc_beyond_latin() {
+        // Canonical ordering of marks, then composition
+        assert_eq!(nfc("a\u{0323}\u{0302}"), nfc("a\u{0302}\u{0323}"));
+        assert_eq!(nfc("a\u{0302}\u{0323}"), "\u{1ead}");
+        // Hangul jamo and Greek with a combining accent
+        assert_eq!(nfc("\u{1100}\u{1161}"), "\u{ac00}");
+        assert_eq!(nfc("\u{03b1}\u{0301}"), "\u{03ac}");
+        // Singletons are replaced even without combining marks
+        assert_eq!(nfc("\u{212b}"), "\u{00c5}");
+    }
+
+    #[test]
+    fn test_nfc_leaves_unknown_sequences() {
+        assert_eq!(nfc("\u{0301}leading"), "\u{0301}leading");
+        assert_eq!(nfc("q\u{0301}"), "q\u{0301}");
+    }
+
+    #[test]
+    fn test_nfc_path() {
+        let decomposed = Path::new("notes/cafe\u{0301}.md");
+        assert_eq!(nfc_path(decomposed), Path::new("notes/caf\u{00e9}.md"));
+        assert!(matches!(nfc_path(Path::new("a/b.md")), Cow::Borrowed(_)));
+    }
+}
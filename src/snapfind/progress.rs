@@ -0,0 +1,17 @@
+use std::io;
+use std::path::Path;
+
+pub trait ProgressSink {
+    fn on_batch(&mut self, processed: usize, max: usize, dirs: usize, last_file: &Path);
+
+    fn on_read_error(&mut self, _path: &Path, _error: &io::Error) {}
+
+    fn on_truncated(&mut self, _path: &Path, _original_len: usize, _stored_len: usize) {}
+}
+
+#[derive(Debug, Default, Clone, Copy)]
+pub struct NoProgress;
+
+impl ProgressSink for NoProgress {
+    fn on_batch(&mut self, _processed: usize, _max: usize, _dirs: usize, _last_file: &Path) {}
+}
@@ -1,54 +1,416 @@
-use std::fs::File;
-use std::io::{Read, Write};
-use std::path::{Path, PathBuf};
+use std::borrow::Cow;
+use std::fs::{self, File};
+use std::io::{BufReader, Read, Write};
+use std::ops::{ControlFlow, Range};
+use std::path::{Component, Path, PathBuf};
+use std::time::{Duration, SystemTime};
 
-use arrayvec::ArrayVec;
+use arrayvec::{ArrayString, ArrayVec};
 
-use super::error::{SnapError, SnapResult};
+use self::inverted::{InvertedIndex, is_indexable_term, max_terms_for};
+use super::checksum::crc32;
+use super::compress;
+use super::crawler::{CrawlFilters, MAX_EXTENSION_LENGTH, MAX_EXTENSIONS};
+use super::error::{ErrorCode, SnapError, SnapResult};
+use super::lock::{self, IndexLock};
+#[cfg(feature = "unicode")]
+use super::normalize;
+
+pub mod inverted;
 
 pub const MAX_RESULTS: usize = 100;
 pub const MAX_DOCUMENTS: usize = 100;
 pub const MAX_CONTENT_LENGTH: usize = 1_000;
+pub const MAX_CONTENT_LIMIT: usize = u16::MAX as usize;
 pub const MAX_TERM_LENGTH: usize = 50;
 pub const MAX_PATH_BYTES: usize = 1024;
 pub const MAGIC: [u8; 4] = *b"SNAP";
-pub const VERSION: u8 = 1;
+pub const INDEX_FILE_NAME: &str = ".snapfind_index";
+pub const VERSION: u8 = VERSION_V8;
+pub const VERSION_V8: u8 = 8;
+pub const VERSION_V7: u8 = 7;
+pub const VERSION_V6: u8 = 6;
+pub const VERSION_V5: u8 = 5;
+pub const VERSION_V4: u8 = 4;
+pub const VERSION_V3: u8 = 3;
+pub const VERSION_V2: u8 = 2;
+pub const VERSION_V1: u8 = 1;
+/// Magic, version, document count, content limit and checksum.
+pub(crate) const INDEX_HEADER_LEN: usize = MAGIC.len() + 1 + 4 + 4 + 4;
+/// Largest metadata block: creation time, tool version, root and crawl
+/// filters.
+const MAX_METADATA_LEN: usize = 8 + 1 + u8::MAX as usize + 2 + MAX_PATH_BYTES + MAX_FILTERS_LEN;
+/// Presence flag, hidden flag, minimum size, extension count and extensions.
+const MAX_FILTERS_LEN: usize = 1 + 1 + 8 + 1 + MAX_EXTENSIONS * (1 + MAX_EXTENSION_LENGTH);
+/// Largest stored document: path and content lengths, path, content flag,
+/// content and file stamp; compressed content is only kept when it is smaller.
+const MAX_RECORD_OVERHEAD: usize = 2 + MAX_PATH_BYTES + 1 + 2 + MAX_STAMP_LEN;
+/// Presence flag, modification seconds and nanoseconds, and file size.
+const MAX_STAMP_LEN: usize = 1 + 8 + 4 + 8;
+/// Largest file any supported index can be; reads stop past it.
+#[cfg(feature = "async")]
+const MAX_INDEX_LEN: usize = INDEX_HEADER_LEN + max_payload_len(MAX_DOCUMENTS, MAX_CONTENT_LIMIT);
+pub const COMPRESSION_THRESHOLD: usize = 64;
+pub const CONTENT_RAW: u8 = 0;
+pub const CONTENT_COMPRESSED: u8 = 1;
 pub const MAX_PATTERNS: usize = 10;
+pub const MAX_STOP_WORDS: usize = 64;
+pub const MAX_QUERY_TERMS: usize = 10;
+pub const PREFIX_MATCH_WEIGHT: f32 = 0.8;
+pub const SUBSTRING_MATCH_WEIGHT: f32 = 0.4;
+pub const FUZZY_MATCH_WEIGHT: f32 = 0.6;
+pub const FUZZY_LONG_TERM_LENGTH: usize = 6;
+pub const DEFAULT_PATH_WEIGHT: f32 = 0.6;
+pub const DEFAULT_CONTENT_WEIGHT: f32 = 0.4;
+pub const WEIGHT_TOLERANCE: f32 = 0.01;
+pub const MAX_TERM_FREQUENCY: usize = 32;
+pub const TERM_FREQUENCY_SATURATION: f32 = 0.5;
+pub const PARTIAL_COMPONENT_SCORE: f32 = 50.0;
 
-pub const ERROR_INVALID_QUERY: i32 = 301;
-pub const ERROR_INVALID_INDEX: i32 = 302;
-pub const ERROR_TOO_MANY_DOCUMENTS: i32 = 303;
-pub const ERROR_CONTENT_TOO_LARGE: i32 = 304;
-pub const ERROR_PATH_TOO_LONG: i32 = 305;
+pub const ERROR_INVALID_QUERY: i32 = ErrorCode::InvalidQuery.code();
+pub const ERROR_INVALID_INDEX: i32 = ErrorCode::InvalidIndex.code();
+pub const ERROR_TOO_MANY_DOCUMENTS: i32 = ErrorCode::TooManyDocuments.code();
+pub const ERROR_CONTENT_TOO_LARGE: i32 = ErrorCode::ContentTooLarge.code();
+pub const ERROR_PATH_TOO_LONG: i32 = ErrorCode::IndexPathTooLong.code();
+pub const ERROR_INVALID_CONFIG: i32 = ErrorCode::InvalidSearchConfig.code();
+pub const ERROR_INDEX_LOCKED: i32 = ErrorCode::IndexLocked.code();
+pub const ERROR_DOCUMENT_NOT_FOUND: i32 = ErrorCode::DocumentNotFound.code();
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct SearchResult {
     pub path: PathBuf,
     pub score: f32,
 }
 
+/// Corpus-wide counts used to weight rare terms above common ones.
+#[derive(Debug, Clone, Default)]
+struct TermStats {
+    doc_count: usize,
+    doc_freq: ArrayVec<usize, MAX_QUERY_TERMS>,
+}
+
+impl TermStats {
+    #[allow(clippy::cast_precision_loss)]
+    fn idf_factor(&self, term_index: usize) -> f32 {
+        let n = self.doc_count.max(1);
+        let df = self
+            .doc_freq
+            .get(term_index)
+            .copied()
+            .unwrap_or(1)
+            .clamp(1, n);
+        let idf = (1.0 + n as f32 / df as f32).ln();
+        idf / (1.0 + n as f32).ln()
+    }
+}
+
+/// Size summary of an index, from [`SearchEngine::stats`].
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct IndexStats {
+    pub document_count: usize,
+    pub total_content_bytes: usize,
+    pub avg_content_bytes: f32,
+    /// Document with the most stored content; the first one wins ties.
+    pub largest_document: Option<PathBuf>,
+    /// Format version the index is written with.
+    pub index_version: u8,
+}
+
+impl IndexStats {
+    /// Whether the index is more than 80% full.
+    #[must_use]
+    pub const fn is_near_capacity(&self) -> bool {
+        self.document_count > MAX_DOCUMENTS * 80 / 100
+    }
+}
+
+/// When, by what and for which directory an index was written, from
+/// [`SearchEngine::metadata`]. Indexes older than format 5 carry none of it
+/// and load with the defaults.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct IndexMetadata {
+    pub created: Option<SystemTime>,
+    /// snapfind version that wrote the index; empty when unknown.
+    pub tool_version: String,
+    /// Canonical path of the indexed directory.
+    pub root: Option<PathBuf>,
+    /// Filters the directory was crawled with; `None` before format 7 or
+    /// when the index was not built by a crawl.
+    pub filters: Option<CrawlFilters>,
+    pub document_count: usize,
+}
+
+impl IndexMetadata {
+    /// Time elapsed between creation and `now`, or `None` when the creation
+    /// time is unknown or in the future.
+    #[must_use]
+    pub fn age(&self, now: SystemTime) -> Option<Duration> {
+        self.created
+            .and_then(|created| now.duration_since(created).ok())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AddOutcome {
+    Stored,
+    /// Only the first `stored_len` bytes were kept, cut on a char boundary.
+    Truncated {
+        original_len: usize,
+        stored_len: usize,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TermMatch {
+    pub term_index: usize,
+    pub matched_in_path: bool,
+    pub matched_in_content: bool,
+    pub contribution: f32,
+}
+
+#[derive(Debug, Clone)]
+pub struct ExplainedResult {
+    pub result: SearchResult,
+    pub terms: ArrayVec<TermMatch, MAX_QUERY_TERMS>,
+}
+
+/// Where a match starts in a document's content, both counted from 1.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MatchLocation {
+    pub line: usize,
+    /// Byte offset within the line.
+    pub column: usize,
+}
+
+#[derive(Debug, Clone)]
+pub struct LocatedResult {
+    pub result: SearchResult,
+    /// First content match, or `None` when only the path matched.
+    pub location: Option<MatchLocation>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TermScore {
+    pub term: ArrayString<MAX_TERM_LENGTH>,
+    pub path_hit: bool,
+    pub content_hit: bool,
+    pub contribution: f32,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScoreExplanation {
+    pub total_score: f32,
+    pub term_scores: ArrayVec<TermScore, MAX_QUERY_TERMS>,
+}
+
+impl MatchLocation {
+    fn at(content: &[u8], offset: usize) -> Self {
+        let before = &content[..offset];
+        let line_start = before
+            .iter()
+            .rposition(|&b| b == b'\n')
+            .map_or(0, |i| i + 1);
+        Self {
+            line: before.iter().filter(|&&b| b == b'\n').count() + 1,
+            column: offset - line_start + 1,
+        }
+    }
+}
+
+impl SearchResult {
+    #[must_use]
+    pub fn resolved_path(&self, root: &Path) -> PathBuf {
+        root.join(&self.path)
+    }
+}
+
+/// A search result tagged with the root directory of the index it came from.
+#[derive(Debug, Clone)]
+pub struct RootedResult {
+    pub root: PathBuf,
+    pub result: SearchResult,
+}
+
+impl RootedResult {
+    #[must_use]
+    pub fn resolved_path(&self) -> PathBuf {
+        self.result.resolved_path(&self.root)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SearchMode {
+    #[default]
+    Text,
+    Glob,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SearchOptions {
+    pub max_edit_distance: u8,
+    pub path_weight: f32,
+    pub content_weight: f32,
+    /// Skip the engine's stop words when scoring. A query made only of stop
+    /// words keeps them, so it is never emptied.
+    pub use_stop_words: bool,
+}
+
+impl Default for SearchOptions {
+    fn default() -> Self {
+        Self {
+            max_edit_distance: 0,
+            path_weight: DEFAULT_PATH_WEIGHT,
+            content_weight: DEFAULT_CONTENT_WEIGHT,
+            use_stop_words: true,
+        }
+    }
+}
+
+impl SearchOptions {
+    #[must_use]
+    pub fn fuzzy() -> Self {
+        Self {
+            max_edit_distance: 2,
+            ..Self::default()
+        }
+    }
+
+    #[must_use]
+    pub fn edit_distance_for(&self, term: &[u8]) -> u8 {
+        let allowed = if term.len() < FUZZY_LONG_TERM_LENGTH {
+            1
+        } else {
+            2
+        };
+        self.max_edit_distance.min(allowed)
+    }
+}
+
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Document {
     pub path: PathBuf,
-    pub content: ArrayVec<u8, MAX_CONTENT_LENGTH>,
+    #[cfg_attr(feature = "serde", serde(with = "hex_bytes"))]
+    pub content: Vec<u8>,
+    /// The file as it was when read; `None` before format 8 or when the
+    /// content did not come from a file.
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    pub stamp: Option<FileStamp>,
+}
+
+/// Modification time and size of a file when it was indexed, compared by
+/// [`SearchEngine::is_stale`] to tell whether it changed since.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct FileStamp {
+    pub modified: SystemTime,
+    pub len: u64,
+}
+
+impl FileStamp {
+    /// `None` on platforms without modification times.
+    #[must_use]
+    pub fn from_metadata(metadata: &fs::Metadata) -> Option<Self> {
+        let modified = metadata.modified().ok()?;
+        Some(Self {
+            modified,
+            len: metadata.len(),
+        })
+    }
+
+    /// The stamp of the file at `path`, following symlinks; `None` if it
+    /// cannot be read.
+    #[must_use]
+    pub fn of(path: &Path) -> Option<Self> {
+        fs::metadata(path)
+            .ok()
+            .as_ref()
+            .and_then(Self::from_metadata)
+    }
+}
+
+/// Stores content as a hex string, since it need not be valid UTF-8.
+#[cfg(feature = "serde")]
+mod hex_bytes {
+    use std::fmt::Write;
+
+    use serde::{Deserialize, Deserializer, Serializer, de};
+
+    pub fn serialize<S: Serializer>(bytes: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+        let mut hex = String::with_capacity(bytes.len() * 2);
+        for b in bytes {
+            let _ = write!(hex, "{b:02x}");
+        }
+        serializer.serialize_str(&hex)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<u8>, D::Error> {
+        let hex = String::deserialize(deserializer)?;
+        if hex.len() % 2 != 0 {
+            return Err(de::Error::custom("hex content has an odd length"));
+        }
+        (0..hex.len())
+            .step_by(2)
+            .map(|i| {
+                hex.get(i..i + 2)
+                    .and_then(|pair| u8::from_str_radix(pair, 16).ok())
+                    .ok_or_else(|| de::Error::custom("invalid hex content"))
+            })
+            .collect()
+    }
+}
+
+/// Per-query state shared by every document's score.
+struct Scoring {
+    glob_matcher: Option<GlobMatcher>,
+    /// Documents the inverted index says can match, when it can tell.
+    candidates: Option<[bool; MAX_DOCUMENTS]>,
+    stats: TermStats,
 }
 
 #[derive(Debug)]
 struct GlobMatcher {
     patterns: ArrayVec<globset::GlobMatcher, MAX_PATTERNS>,
+    negated: ArrayVec<globset::GlobMatcher, MAX_PATTERNS>,
 }
 
 impl GlobMatcher {
-    fn new(pattern: &str) -> SnapResult<Self> {
+    fn new(pattern: &str, literal_separator: bool) -> SnapResult<Self> {
         assert!(!pattern.is_empty(), "Pattern must not be empty");
         assert!(pattern.len() <= MAX_TERM_LENGTH, "Pattern too long");
 
         let mut matcher = Self {
             patterns: ArrayVec::new(),
+            negated: ArrayVec::new(),
         };
 
         for part in pattern.split_whitespace() {
-            let pattern_str = if !part.starts_with('*') && part.contains('*') {
+            let (part, negate) = match part.strip_prefix('!') {
+                Some(rest) => (rest, true),
+                None => (part, false),
+            };
+            if part.is_empty() {
+                return Err(anyhow::Error::from(SnapError::with_code(
+                    "Negation '!' must be followed by a pattern",
+                    ERROR_INVALID_QUERY,
+                )));
+            }
+
+            let pattern_str = if literal_separator {
+                // Like gitignore: no `/` matches the name at any depth, otherwise
+                // the pattern is anchored at the index root
+                match part.strip_prefix('/') {
+                    Some(anchored) => anchored.to_string(),
+                    None if part.contains('/') => part.to_string(),
+                    None => format!("**/{part}"),
+                }
+            } else if !part.starts_with('*') && part.contains('*') {
                 format!("*{part}")
             } else {
                 part.to_string()
@@ -56,37 +418,238 @@ impl GlobMatcher {
 
             let glob = globset::GlobBuilder::new(&pattern_str)
                 .case_insensitive(true)
-                .literal_separator(false)
+                .literal_separator(literal_separator)
                 .build()
                 .map_err(|e| {
                     SnapError::with_code(format!("Invalid pattern: {e}"), ERROR_INVALID_QUERY)
                 })?;
 
-            matcher
-                .patterns
+            let target = if negate {
+                &mut matcher.negated
+            } else {
+                &mut matcher.patterns
+            };
+            target
                 .try_push(glob.compile_matcher())
                 .map_err(|_| SnapError::with_code("Too many pattern parts", ERROR_INVALID_QUERY))?;
         }
 
         assert!(
-            !matcher.patterns.is_empty(),
+            !matcher.patterns.is_empty() || !matcher.negated.is_empty(),
             "Must have at least one pattern"
         );
 
         Ok(matcher)
     }
 
+    /// A negated pattern excludes a path even when a positive one matches it.
+    /// With only negations, every path that is not excluded matches.
     fn is_match(&self, path: &Path) -> bool {
         assert!(path.as_os_str().len() <= MAX_PATH_BYTES, "Path too long");
 
-        path.to_str()
-            .is_some_and(|path_str| self.patterns.iter().any(|glob| glob.is_match(path_str)))
+        path.to_str().is_some_and(|path_str| {
+            !self.negated.iter().any(|glob| glob.is_match(path_str))
+                && (self.patterns.is_empty()
+                    || self.patterns.iter().any(|glob| glob.is_match(path_str)))
+        })
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct StopWordList {
+    words: ArrayVec<&'static str, MAX_STOP_WORDS>,
+}
+
+static NO_STOP_WORDS: StopWordList = StopWordList::new();
+
+impl StopWordList {
+    #[must_use]
+    pub const fn new() -> Self {
+        Self {
+            words: ArrayVec::new_const(),
+        }
+    }
+
+    /// The words to skip for `query`: none when `options` turns them off or
+    /// when every term of the query is one.
+    fn for_query(&self, query: &str, options: &SearchOptions) -> &Self {
+        let all_stop_words = query
+            .split_whitespace()
+            .map(literal_term)
+            .filter(|term| !term.is_empty())
+            .take(MAX_QUERY_TERMS)
+            .all(|term| self.contains(term.as_bytes()));
+        if !options.use_stop_words || all_stop_words {
+            &NO_STOP_WORDS
+        } else {
+            self
+        }
+    }
+
+    #[must_use]
+    pub fn english() -> Self {
+        let mut list = Self::new();
+        for word in [
+            "a", "an", "and", "are", "as", "at", "be", "but", "by", "for", "from", "has", "have",
+            "he", "her", "his", "if", "in", "into", "is", "it", "its", "no", "not", "of", "on",
+            "or", "our", "she", "so", "such", "that", "the", "their", "then", "there", "these",
+            "they", "this", "to", "was", "we", "were", "will", "with", "you", "your",
+        ] {
+            list.insert(word)
+                .expect("English stop words must fit the list");
+        }
+        list
+    }
+
+    pub fn insert(&mut self, word: &'static str) -> SnapResult<()> {
+        if word.is_empty() || word.bytes().any(|b| !b.is_ascii_lowercase()) {
+            return Err(anyhow::Error::from(SnapError::with_code(
+                format!("Stop word must be lowercase ASCII letters: {word:?}"),
+                ERROR_INVALID_QUERY,
+            )));
+        }
+
+        if let Err(pos) = self.words.binary_search(&word) {
+            self.words.try_insert(pos, word).map_err(|_| {
+                anyhow::Error::from(SnapError::with_code(
+                    format!("Too many stop words (max {MAX_STOP_WORDS})"),
+                    ERROR_INVALID_QUERY,
+                ))
+            })?;
+        }
+        Ok(())
+    }
+
+    #[must_use]
+    pub fn contains(&self, term: &[u8]) -> bool {
+        self.words
+            .binary_search_by(|word| word.bytes().cmp(term.iter().map(u8::to_ascii_lowercase)))
+            .is_ok()
+    }
+
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.words.len()
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.words.is_empty()
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct SearchEngineBuilder {
+    options: SearchOptions,
+    stop_words: Option<StopWordList>,
+    mode: SearchMode,
+    linear_scan: bool,
+    glob_crosses_separators: bool,
+    max_content_length: Option<usize>,
+}
+
+impl SearchEngineBuilder {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn weights(mut self, path_weight: f32, content_weight: f32) -> SnapResult<Self> {
+        let valid = path_weight.is_finite()
+            && content_weight.is_finite()
+            && path_weight >= 0.0
+            && content_weight >= 0.0
+            && (path_weight + content_weight - 1.0).abs() <= WEIGHT_TOLERANCE;
+        if !valid {
+            return Err(anyhow::Error::from(SnapError::with_code(
+                format!(
+                    "Path weight ({path_weight}) and content weight ({content_weight}) must be \
+                     non-negative and sum to 1.0"
+                ),
+                ERROR_INVALID_CONFIG,
+            )));
+        }
+
+        self.options.path_weight = path_weight;
+        self.options.content_weight = content_weight;
+        Ok(self)
+    }
+
+    #[must_use]
+    pub fn fuzzy_distance(mut self, distance: usize) -> Self {
+        self.options.max_edit_distance = u8::try_from(distance).unwrap_or(u8::MAX);
+        self
+    }
+
+    #[must_use]
+    pub fn stop_words(mut self, list: StopWordList) -> Self {
+        self.stop_words = Some(list);
+        self
+    }
+
+    #[must_use]
+    pub const fn mode(mut self, mode: SearchMode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    pub fn max_content_length(mut self, limit: usize) -> SnapResult<Self> {
+        if limit == 0 || limit > MAX_CONTENT_LIMIT {
+            return Err(anyhow::Error::from(SnapError::with_code(
+                format!(
+                    "Content limit must be between 1 and {MAX_CONTENT_LIMIT} bytes, got {limit}"
+                ),
+                ERROR_INVALID_CONFIG,
+            )));
+        }
+
+        self.max_content_length = Some(limit);
+        Ok(self)
+    }
+
+    #[must_use]
+    pub const fn inverted_index(mut self, enabled: bool) -> Self {
+        self.linear_scan = !enabled;
+        self
+    }
+
+    /// Whether `*` in glob mode stops at `/`, as in shell and ripgrep globs.
+    ///
+    /// On by default; use `**` to cross directories. Turning it off restores the
+    /// older behavior where `*` matches across separators.
+    #[must_use]
+    pub const fn literal_separator(mut self, enabled: bool) -> Self {
+        self.glob_crosses_separators = !enabled;
+        self
+    }
+
+    #[must_use = "SearchEngine must be used to store and search documents"]
+    pub fn build(self) -> SearchEngine {
+        let max_content_length = self.max_content_length.unwrap_or(MAX_CONTENT_LENGTH);
+        SearchEngine {
+            documents: Box::new(ArrayVec::new()),
+            inverted: (!self.linear_scan)
+                .then(|| InvertedIndex::with_max_terms(max_terms_for(max_content_length))),
+            stop_words: self.stop_words.unwrap_or_else(StopWordList::english),
+            options: self.options,
+            mode: self.mode,
+            literal_separator: !self.glob_crosses_separators,
+            max_content_length,
+            metadata: IndexMetadata::default(),
+        }
     }
 }
 
 #[derive(Debug)]
 pub struct SearchEngine {
     documents: Box<ArrayVec<Document, MAX_DOCUMENTS>>,
+    inverted: Option<InvertedIndex>,
+    stop_words: StopWordList,
+    options: SearchOptions,
+    mode: SearchMode,
+    literal_separator: bool,
+    max_content_length: usize,
+    metadata: IndexMetadata,
 }
 
 impl Default for SearchEngine {
@@ -98,16 +661,152 @@ impl Default for SearchEngine {
 impl SearchEngine {
     #[must_use = "SearchEngine must be used to store and search documents"]
     pub fn new() -> Self {
-        Self {
-            documents: Box::new(ArrayVec::new()),
+        SearchEngineBuilder::new().build()
+    }
+
+    #[must_use]
+    pub fn builder() -> SearchEngineBuilder {
+        SearchEngineBuilder::new()
+    }
+
+    #[must_use]
+    pub const fn with_options(mut self, options: SearchOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    #[must_use]
+    pub const fn with_mode(mut self, mode: SearchMode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    #[must_use]
+    pub const fn mode(&self) -> SearchMode {
+        self.mode
+    }
+
+    #[must_use]
+    pub const fn max_content_length(&self) -> usize {
+        self.max_content_length
+    }
+
+    /// Metadata read by [`SearchEngine::load`]; defaulted for engines built in
+    /// memory.
+    #[must_use]
+    pub const fn metadata(&self) -> &IndexMetadata {
+        &self.metadata
+    }
+
+    /// Records the filters the documents were crawled with, saved in the
+    /// index metadata.
+    pub fn set_crawl_filters(&mut self, filters: CrawlFilters) {
+        self.metadata.filters = Some(filters);
+    }
+
+    /// Whether the file at `path`, now at `stamp`, needs to be read again: it
+    /// is not indexed, or its modification time or size differs from the ones
+    /// stored. Documents stored without a stamp fall back to comparing the
+    /// modification time with the index creation time, and are stale when
+    /// that is unknown too.
+    #[must_use]
+    pub fn is_stale(&self, path: &Path, stamp: FileStamp) -> bool {
+        let Some(doc) = self.get_document(path) else {
+            return true;
+        };
+        match doc.stamp {
+            Some(stored) => stored != stamp,
+            None => self
+                .metadata
+                .created
+                .is_none_or(|created| stamp.modified >= created),
+        }
+    }
+
+    /// Records the state of the file a document was read from, for
+    /// [`SearchEngine::is_stale`]. Returns whether `path` is indexed.
+    pub fn set_document_stamp(&mut self, path: &Path, stamp: FileStamp) -> bool {
+        let Some(position) = self.position_of(path) else {
+            return false;
+        };
+        self.documents[position].stamp = Some(stamp);
+        true
+    }
+
+    #[must_use]
+    pub fn document_count(&self) -> usize {
+        self.documents.len()
+    }
+
+    pub fn document_lengths(&self) -> impl Iterator<Item = (&Path, usize)> {
+        self.documents
+            .iter()
+            .map(|doc| (doc.path.as_path(), doc.content.len()))
+    }
+
+    /// Indexed documents in insertion order, as stored path and stored content.
+    pub fn documents(&self) -> impl Iterator<Item = (&Path, &[u8])> {
+        self.documents
+            .iter()
+            .map(|doc| (doc.path.as_path(), doc.content.as_slice()))
+    }
+
+    #[must_use]
+    pub fn stats(&self) -> IndexStats {
+        let total_content_bytes = self.documents.iter().map(|doc| doc.content.len()).sum();
+        let largest_document = self
+            .documents
+            .iter()
+            .reduce(|largest, doc| {
+                if doc.content.len() > largest.content.len() {
+                    doc
+                } else {
+                    largest
+                }
+            })
+            .map(|doc| doc.path.clone());
+
+        #[allow(clippy::cast_precision_loss)]
+        let avg_content_bytes = if self.documents.is_empty() {
+            0.0
+        } else {
+            total_content_bytes as f32 / self.documents.len() as f32
+        };
+
+        IndexStats {
+            document_count: self.documents.len(),
+            total_content_bytes,
+            avg_content_bytes,
+            largest_document,
+            index_version: VERSION,
         }
     }
 
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.documents.is_empty()
+    }
+
+    #[must_use]
+    pub fn with_stop_words(mut self, list: StopWordList) -> Self {
+        self.stop_words = list;
+        self
+    }
+
     pub fn load(path: &Path) -> SnapResult<Self> {
-        let mut file = File::open(path).map_err(|e| {
+        IndexLock::wait_until_free(path)?;
+        Self::read_from(path)
+    }
+
+    fn read_from(path: &Path) -> SnapResult<Self> {
+        let file = File::open(path).map_err(|e| {
             SnapError::with_code(format!("Failed to open index: {e}"), ERROR_INVALID_INDEX)
         })?;
+        Self::decode(BufReader::new(file))
+    }
 
+    /// Parses an index in any supported version from `file`.
+    fn decode(mut file: impl Read) -> SnapResult<Self> {
         let mut magic = [0u8; 4];
         file.read_exact(&mut magic).map_err(|e| {
             SnapError::with_code(format!("Failed to read magic: {e}"), ERROR_INVALID_INDEX)
@@ -123,9 +822,12 @@ impl SearchEngine {
         file.read_exact(&mut version).map_err(|e| {
             SnapError::with_code(format!("Failed to read version: {e}"), ERROR_INVALID_INDEX)
         })?;
-        if version[0] != VERSION {
+        let version = version[0];
+        // Versions only add to the format, so each feature below is gated on
+        // the version that introduced it
+        if !(VERSION_V1..=VERSION).contains(&version) {
             return Err(anyhow::Error::from(SnapError::with_code(
-                format!("Unsupported index version: {}", version[0]),
+                format!("Unsupported index version: {version}"),
                 ERROR_INVALID_INDEX,
             )));
         }
@@ -145,34 +847,108 @@ impl SearchEngine {
             )));
         }
 
-        let mut engine = Self::new();
-
-        for _ in 0..ndocs {
-            let mut path_len = [0u8; 2];
-            file.read_exact(&mut path_len).map_err(|e| {
+        let content_limit = if version >= VERSION_V4 {
+            let mut limit = [0u8; 4];
+            file.read_exact(&mut limit).map_err(|e| {
                 SnapError::with_code(
-                    format!("Failed to read path length: {e}"),
+                    format!("Failed to read content limit: {e}"),
                     ERROR_INVALID_INDEX,
                 )
             })?;
-            let path_len = u16::from_le_bytes(path_len) as usize;
-            if path_len > MAX_PATH_BYTES {
-                return Err(anyhow::Error::from(SnapError::with_code(
-                    "Path too long",
-                    ERROR_PATH_TOO_LONG,
-                )));
-            }
+            u32::from_le_bytes(limit) as usize
+        } else {
+            MAX_CONTENT_LENGTH
+        };
 
-            let mut path_buf = ArrayVec::<u8, MAX_PATH_BYTES>::new();
-            for _ in 0..path_len {
-                let mut byte = [0u8; 1];
-                file.read_exact(&mut byte).map_err(|e| {
-                    SnapError::with_code(format!("Failed to read path: {e}"), ERROR_INVALID_INDEX)
-                })?;
-                path_buf.try_push(byte[0]).map_err(|_| {
-                    anyhow::Error::from(SnapError::with_code("Path too long", ERROR_PATH_TOO_LONG))
-                })?;
-            }
+        let mut checksum = [0u8; 4];
+        if version >= VERSION_V3 {
+            file.read_exact(&mut checksum).map_err(|e| {
+                SnapError::with_code(format!("Failed to read checksum: {e}"), ERROR_INVALID_INDEX)
+            })?;
+        }
+
+        // The header bounds the payload, so a huge file is rejected without
+        // reading all of it
+        let payload_limit = max_payload_len(ndocs, content_limit);
+        let mut payload = Vec::new();
+        file.take(payload_limit as u64 + 1)
+            .read_to_end(&mut payload)
+            .map_err(|e| {
+                SnapError::with_code(format!("Failed to read index: {e}"), ERROR_INVALID_INDEX)
+            })?;
+        if payload.len() > payload_limit {
+            return Err(anyhow::Error::from(SnapError::with_code(
+                format!("Index is larger than {ndocs} documents can be"),
+                ERROR_INVALID_INDEX,
+            )));
+        }
+        if version >= VERSION_V3 && crc32(&payload) != u32::from_le_bytes(checksum) {
+            return Err(anyhow::Error::from(SnapError::with_code(
+                "Index file is corrupted, please re-run `snap index`",
+                ERROR_INVALID_INDEX,
+            )));
+        }
+        let mut file = payload.as_slice();
+
+        let mut engine = SearchEngineBuilder::new()
+            .max_content_length(content_limit)
+            .map_err(|_| {
+                SnapError::with_code(
+                    format!("Invalid content limit in index: {content_limit}"),
+                    ERROR_INVALID_INDEX,
+                )
+            })?
+            .build();
+        engine.metadata = if version >= VERSION_V5 {
+            read_metadata(&mut file, version)?
+        } else {
+            IndexMetadata::default()
+        };
+        engine.metadata.document_count = ndocs;
+
+        for _ in 0..ndocs {
+            let mut path_len = [0u8; 2];
+            file.read_exact(&mut path_len).map_err(|e| {
+                SnapError::with_code(
+                    format!("Failed to read path length: {e}"),
+                    ERROR_INVALID_INDEX,
+                )
+            })?;
+            let path_len = u16::from_le_bytes(path_len) as usize;
+            if path_len > MAX_PATH_BYTES {
+                return Err(anyhow::Error::from(SnapError::with_code(
+                    "Path too long",
+                    ERROR_PATH_TOO_LONG,
+                )));
+            }
+
+            let mut path_buf = [0u8; MAX_PATH_BYTES];
+            let path_buf = &mut path_buf[..path_len];
+            file.read_exact(path_buf).map_err(|e| {
+                SnapError::with_code(format!("Failed to read path: {e}"), ERROR_INVALID_INDEX)
+            })?;
+
+            let compressed = if version < VERSION_V2 {
+                false
+            } else {
+                let mut flag = [0u8; 1];
+                file.read_exact(&mut flag).map_err(|e| {
+                    SnapError::with_code(
+                        format!("Failed to read content flag: {e}"),
+                        ERROR_INVALID_INDEX,
+                    )
+                })?;
+                match flag[0] {
+                    CONTENT_RAW => false,
+                    CONTENT_COMPRESSED => true,
+                    other => {
+                        return Err(anyhow::Error::from(SnapError::with_code(
+                            format!("Invalid content flag: {other}"),
+                            ERROR_INVALID_INDEX,
+                        )));
+                    }
+                }
+            };
 
             let mut content_len = [0u8; 2];
             file.read_exact(&mut content_len).map_err(|e| {
@@ -182,201 +958,686 @@ impl SearchEngine {
                 )
             })?;
             let content_len = u16::from_le_bytes(content_len) as usize;
-            if content_len > MAX_CONTENT_LENGTH {
+            if content_len > content_limit {
                 return Err(anyhow::Error::from(SnapError::with_code(
                     "Content too large",
                     ERROR_CONTENT_TOO_LARGE,
                 )));
             }
 
-            let mut content = ArrayVec::new();
-            for _ in 0..content_len {
-                let mut byte = [0u8; 1];
-                file.read_exact(&mut byte).map_err(|e| {
-                    SnapError::with_code(
-                        format!("Failed to read content: {e}"),
-                        ERROR_INVALID_INDEX,
-                    )
-                })?;
-                content.try_push(byte[0]).map_err(|_| {
-                    anyhow::Error::from(SnapError::with_code(
-                        "Content too large",
-                        ERROR_CONTENT_TOO_LARGE,
-                    ))
-                })?;
+            let mut content = vec![0u8; content_len];
+            file.read_exact(&mut content).map_err(|e| {
+                SnapError::with_code(format!("Failed to read content: {e}"), ERROR_INVALID_INDEX)
+            })?;
+
+            let content = if compressed {
+                compress::decompress::<MAX_CONTENT_LIMIT>(&content)?.to_vec()
+            } else {
+                content
+            };
+            if content.len() > content_limit {
+                return Err(anyhow::Error::from(SnapError::with_code(
+                    "Content too large",
+                    ERROR_CONTENT_TOO_LARGE,
+                )));
             }
 
-            let path_str = String::from_utf8_lossy(&path_buf).into_owned();
-            let path = PathBuf::from(path_str);
-            engine
-                .documents
-                .try_push(Document { path, content })
-                .map_err(|_| {
-                    anyhow::Error::from(SnapError::with_code(
-                        "Too many documents",
-                        ERROR_TOO_MANY_DOCUMENTS,
-                    ))
-                })?;
+            let stamp = if version >= VERSION_V8 {
+                read_stamp(&mut file)?
+            } else {
+                None
+            };
+
+            let path = path_from_bytes(path_buf);
+            engine.push_document(Document {
+                path,
+                content,
+                stamp,
+            })?;
         }
 
         Ok(engine)
     }
 
     pub fn save(&self, path: &Path) -> SnapResult<()> {
+        self.save_with_wait(path, lock::DEFAULT_WAIT)
+    }
+
+    /// Like [`SearchEngine::save`], waiting at most `wait` for another
+    /// process's lock on `path` before failing.
+    pub fn save_with_wait(&self, path: &Path, wait: Duration) -> SnapResult<()> {
+        let _lock = IndexLock::acquire_with(path, lock::DEFAULT_STALE_AGE, wait)?;
+
+        // Write beside the target and rename so readers never see a partial index
+        let tmp_path = lock::with_suffix(path, lock::TMP_SUFFIX);
+        if let Err(e) = self.write_to(&tmp_path) {
+            let _ = std::fs::remove_file(&tmp_path);
+            return Err(e);
+        }
+
+        std::fs::rename(&tmp_path, path).map_err(|e| {
+            let _ = std::fs::remove_file(&tmp_path);
+            SnapError::with_code(format!("Failed to replace index: {e}"), ERROR_INVALID_INDEX)
+        })?;
+
+        Ok(())
+    }
+
+    fn write_to(&self, path: &Path) -> SnapResult<()> {
+        let bytes = self.encode(path)?;
         let mut file = File::create(path).map_err(|e| {
             SnapError::with_code(format!("Failed to create index: {e}"), ERROR_INVALID_INDEX)
         })?;
-
-        file.write_all(&MAGIC).map_err(|e| {
-            SnapError::with_code(format!("Failed to write magic: {e}"), ERROR_INVALID_INDEX)
+        file.write_all(&bytes).map_err(|e| {
+            SnapError::with_code(format!("Failed to write index: {e}"), ERROR_INVALID_INDEX)
         })?;
-        file.write_all(&[VERSION]).map_err(|e| {
-            SnapError::with_code(format!("Failed to write version: {e}"), ERROR_INVALID_INDEX)
+        file.sync_all().map_err(|e| {
+            SnapError::with_code(format!("Failed to flush index: {e}"), ERROR_INVALID_INDEX)
         })?;
 
+        Ok(())
+    }
+
+    /// Serializes the engine as a current-version index destined for `path`.
+    fn encode(&self, path: &Path) -> SnapResult<Vec<u8>> {
         let ndocs = u32::try_from(self.documents.len()).map_err(|_| {
             SnapError::with_code(
                 "Too many documents for index format",
                 ERROR_TOO_MANY_DOCUMENTS,
             )
         })?;
-        file.write_all(&ndocs.to_le_bytes()).map_err(|e| {
-            SnapError::with_code(
-                format!("Failed to write document count: {e}"),
-                ERROR_INVALID_INDEX,
-            )
+        let content_limit = u32::try_from(self.max_content_length).map_err(|_| {
+            SnapError::with_code("Content limit too large", ERROR_CONTENT_TOO_LARGE)
         })?;
 
+        let mut payload = Vec::new();
+        write_metadata(&mut payload, path, self.metadata.filters.as_ref());
         for doc in self.documents.iter() {
-            let path_str = doc.path.to_string_lossy();
-            let path_bytes = path_str.as_bytes();
+            let path_bytes = path_to_bytes(&doc.path);
             if path_bytes.len() > MAX_PATH_BYTES {
-                return Err(anyhow::Error::from(SnapError::with_code(
-                    "Path too long",
-                    ERROR_PATH_TOO_LONG,
-                )));
+                return Err(anyhow::Error::from(
+                    SnapError::with_code("Path too long", ERROR_PATH_TOO_LONG).with_path(&doc.path),
+                ));
             }
 
             let path_len = u16::try_from(path_bytes.len()).map_err(|_| {
-                anyhow::Error::from(SnapError::with_code(
-                    "Path too long for index format",
-                    ERROR_PATH_TOO_LONG,
-                ))
+                anyhow::Error::from(
+                    SnapError::with_code("Path too long for index format", ERROR_PATH_TOO_LONG)
+                        .with_path(&doc.path),
+                )
             })?;
-            file.write_all(&path_len.to_le_bytes()).map_err(|e| {
+            payload.write_all(&path_len.to_le_bytes()).map_err(|e| {
                 anyhow::Error::from(SnapError::with_code(
                     format!("Failed to write path length: {e}"),
                     ERROR_INVALID_INDEX,
                 ))
             })?;
-            file.write_all(path_bytes).map_err(|e| {
+            payload.write_all(&path_bytes).map_err(|e| {
                 anyhow::Error::from(SnapError::with_code(
                     format!("Failed to write path: {e}"),
                     ERROR_INVALID_INDEX,
                 ))
             })?;
 
-            let content_len = u16::try_from(doc.content.len()).map_err(|_| {
+            let packed = if doc.content.len() >= COMPRESSION_THRESHOLD {
+                compress::compress::<MAX_CONTENT_LIMIT>(&doc.content)
+                    .filter(|packed| packed.len() < doc.content.len())
+            } else {
+                None
+            };
+            let (flag, stored) = packed
+                .as_deref()
+                .map_or((CONTENT_RAW, &doc.content[..]), |p| (CONTENT_COMPRESSED, p));
+            payload.write_all(&[flag]).map_err(|e| {
+                anyhow::Error::from(SnapError::with_code(
+                    format!("Failed to write content flag: {e}"),
+                    ERROR_INVALID_INDEX,
+                ))
+            })?;
+
+            let content_len = u16::try_from(stored.len()).map_err(|_| {
                 anyhow::Error::from(SnapError::with_code(
                     "Content too large for index format",
                     ERROR_CONTENT_TOO_LARGE,
                 ))
             })?;
-            file.write_all(&content_len.to_le_bytes()).map_err(|e| {
+            payload.write_all(&content_len.to_le_bytes()).map_err(|e| {
                 anyhow::Error::from(SnapError::with_code(
                     format!("Failed to write content length: {e}"),
                     ERROR_INVALID_INDEX,
                 ))
             })?;
-            file.write_all(&doc.content).map_err(|e| {
+            payload.write_all(stored).map_err(|e| {
                 anyhow::Error::from(SnapError::with_code(
                     format!("Failed to write content: {e}"),
                     ERROR_INVALID_INDEX,
                 ))
             })?;
+            write_stamp(&mut payload, doc.stamp);
         }
 
-        Ok(())
+        let mut bytes = Vec::with_capacity(INDEX_HEADER_LEN + payload.len());
+        bytes.extend_from_slice(&MAGIC);
+        bytes.push(VERSION);
+        bytes.extend_from_slice(&ndocs.to_le_bytes());
+        bytes.extend_from_slice(&content_limit.to_le_bytes());
+        bytes.extend_from_slice(&crc32(&payload).to_le_bytes());
+        bytes.extend_from_slice(&payload);
+        Ok(bytes)
     }
 
     pub fn add_document(&mut self, path: &Path, content: &str) -> SnapResult<()> {
-        let mut doc_content = ArrayVec::new();
-        for &b in content.as_bytes() {
-            doc_content.try_push(b).map_err(|_| {
-                anyhow::Error::from(SnapError::with_code(
-                    "Content too large",
-                    ERROR_CONTENT_TOO_LARGE,
-                ))
-            })?;
+        self.add_document_bytes(path, content.as_bytes())
+    }
+
+    /// Like [`SearchEngine::add_document`], for content that need not be
+    /// valid UTF-8. It is stored as given, and terms match its bytes.
+    pub fn add_document_bytes(&mut self, path: &Path, content: &[u8]) -> SnapResult<()> {
+        if content.len() > self.max_content_length {
+            return Err(anyhow::Error::from(SnapError::with_code(
+                format!(
+                    "Content too large: {} is {} bytes, limit is {}",
+                    path.display(),
+                    content.len(),
+                    self.max_content_length
+                ),
+                ERROR_CONTENT_TOO_LARGE,
+            )));
         }
 
-        self.documents
-            .try_push(Document {
-                path: path.to_path_buf(),
-                content: doc_content,
-            })
-            .map_err(|_| {
-                anyhow::Error::from(SnapError::with_code(
-                    "Too many documents",
-                    ERROR_TOO_MANY_DOCUMENTS,
-                ))
-            })?;
+        #[cfg(feature = "unicode")]
+        let path = normalize::nfc_path(path);
+
+        self.push_document(Document {
+            path: path.to_path_buf(),
+            content: content.to_vec(),
+            stamp: None,
+        })
+    }
+
+    fn push_document(&mut self, doc: Document) -> SnapResult<()> {
+        if let Some(inverted) = self.inverted.as_mut() {
+            Self::index_document(inverted, self.documents.len(), &doc);
+        }
+
+        self.documents.try_push(doc).map_err(|_| {
+            anyhow::Error::from(SnapError::with_code(
+                "Too many documents",
+                ERROR_TOO_MANY_DOCUMENTS,
+            ))
+        })?;
+
+        Ok(())
+    }
+
+    fn index_document(inverted: &mut InvertedIndex, position: usize, doc: &Document) {
+        let doc_id = u32::try_from(position).unwrap_or(u32::MAX);
+        inverted.insert_text(doc_id, doc.path.to_string_lossy().as_bytes());
+        inverted.insert_content(doc_id, &doc.content);
+    }
+
+    /// Keeps only the documents for which `f` returns `true`, like `Vec::retain`.
+    pub fn retain(&mut self, f: impl Fn(&Document) -> bool) {
+        let before = self.documents.len();
+        self.documents.retain(|doc| f(doc));
+        if self.documents.len() != before {
+            // Postings refer to documents by position, which just shifted
+            self.rebuild_inverted();
+        }
+    }
+
+    /// Removes the first document stored under `path`, keeping the others in
+    /// order. Returns whether a document was removed.
+    pub fn remove_document(&mut self, path: &Path) -> bool {
+        let Some(position) = self.position_of(path) else {
+            return false;
+        };
+        self.documents.remove(position);
+        self.rebuild_inverted();
+        true
+    }
+
+    /// Replaces the content stored under `path` in place, or adds the document
+    /// if it is not indexed yet. Content over the engine's limit is rejected
+    /// and leaves the stored document untouched.
+    pub fn update_document(&mut self, path: &Path, content: &str) -> SnapResult<()> {
+        let Some(position) = self.position_of(path) else {
+            return self.add_document(path, content);
+        };
+        if content.len() > self.max_content_length {
+            return Err(anyhow::Error::from(SnapError::with_code(
+                format!(
+                    "Content too large: {} is {} bytes, limit is {}",
+                    path.display(),
+                    content.len(),
+                    self.max_content_length
+                ),
+                ERROR_CONTENT_TOO_LARGE,
+            )));
+        }
 
+        let doc = &mut self.documents[position];
+        doc.content = content.as_bytes().to_vec();
+        // The stamp described the old content
+        doc.stamp = None;
+        self.rebuild_inverted();
         Ok(())
     }
 
+    pub fn update_document_truncating(
+        &mut self,
+        path: &Path,
+        content: &str,
+    ) -> SnapResult<AddOutcome> {
+        let stored = self.truncated(content);
+        self.update_document(path, stored)?;
+        Ok(Self::outcome(content, stored))
+    }
+
+    fn rebuild_inverted(&mut self) {
+        if let Some(inverted) = self.inverted.as_mut() {
+            inverted.clear();
+            for (position, doc) in self.documents.iter().enumerate() {
+                Self::index_document(inverted, position, doc);
+            }
+        }
+    }
+
+    /// Whether a document with this path is indexed, comparing path components
+    /// so separator differences do not matter.
+    #[must_use]
+    pub fn contains_document(&self, path: &Path) -> bool {
+        self.position_of(path).is_some()
+    }
+
+    #[must_use]
+    pub fn get_document(&self, path: &Path) -> Option<&Document> {
+        self.position_of(path)
+            .map(|position| &self.documents[position])
+    }
+
+    /// Mutable access to a stored document. Searches fall back to a linear scan
+    /// afterwards, since the edited content is no longer reflected in the
+    /// inverted index.
+    pub fn get_document_mut(&mut self, path: &Path) -> Option<&mut Document> {
+        let position = self.position_of(path)?;
+        if let Some(inverted) = self.inverted.as_mut() {
+            inverted.mark_incomplete();
+        }
+        Some(&mut self.documents[position])
+    }
+
+    fn position_of(&self, path: &Path) -> Option<usize> {
+        #[cfg(feature = "unicode")]
+        let path = normalize::nfc_path(path);
+
+        self.documents
+            .iter()
+            .position(|doc| doc.path.components().eq(path.components()))
+    }
+
+    fn candidate_documents(&self, query: &str) -> Option<[bool; MAX_DOCUMENTS]> {
+        let inverted = self.inverted.as_ref().filter(|i| i.is_complete())?;
+        if self.options.max_edit_distance > 0 {
+            return None;
+        }
+
+        let stop_words = self.stop_words.for_query(query, &self.options);
+        let mut candidates = [false; MAX_DOCUMENTS];
+        for term in query.split_whitespace().map(literal_term) {
+            let term = term.as_bytes();
+            if term.is_empty() || stop_words.contains(term) {
+                continue;
+            }
+            if !is_indexable_term(term) {
+                return None;
+            }
+            for &doc_id in inverted.lookup(term) {
+                if let Some(slot) = candidates.get_mut(doc_id as usize) {
+                    *slot = true;
+                }
+            }
+        }
+
+        Some(candidates)
+    }
+
+    pub fn add_document_truncating(
+        &mut self,
+        path: &Path,
+        content: &str,
+    ) -> SnapResult<AddOutcome> {
+        let stored = self.truncated(content);
+        self.add_document(path, stored)?;
+        Ok(Self::outcome(content, stored))
+    }
+
+    /// The longest prefix of `content` that fits, cut on a char boundary.
+    fn truncated<'a>(&self, content: &'a str) -> &'a str {
+        if content.len() <= self.max_content_length {
+            return content;
+        }
+
+        let mut end = self.max_content_length;
+        while !content.is_char_boundary(end) {
+            end -= 1;
+        }
+        &content[..end]
+    }
+
+    const fn outcome(content: &str, stored: &str) -> AddOutcome {
+        if stored.len() == content.len() {
+            AddOutcome::Stored
+        } else {
+            AddOutcome::Truncated {
+                original_len: content.len(),
+                stored_len: stored.len(),
+            }
+        }
+    }
+
+    pub fn add_document_relative(
+        &mut self,
+        root: &Path,
+        path: &Path,
+        content: &str,
+    ) -> SnapResult<()> {
+        let relative = path.strip_prefix(root).unwrap_or(path);
+        self.add_document(relative, content)
+    }
+
     #[must_use]
     pub fn term_matches(term: &[u8], content: &[u8]) -> bool {
+        #[cfg(feature = "unicode")]
+        if let (Ok(term), Ok(content)) = (std::str::from_utf8(term), std::str::from_utf8(content)) {
+            if term.is_ascii() && content.is_ascii() {
+                return Self::exact_term_range(term.as_bytes(), content.as_bytes()).is_some();
+            }
+            return unicode_term_range(&normalize::nfc(term), &normalize::nfc(content)).is_some();
+        }
+
+        Self::exact_term_range(term, content).is_some()
+    }
+
+    /// Like `term_matches`, but returns where in `content` the match is.
+    fn term_range(term: &[u8], content: &[u8]) -> Option<Range<usize>> {
+        #[cfg(feature = "unicode")]
+        if let (Ok(term), Ok(content)) = (std::str::from_utf8(term), std::str::from_utf8(content)) {
+            if term.is_ascii() && content.is_ascii() {
+                return Self::exact_term_range(term.as_bytes(), content.as_bytes());
+            }
+            // Offsets into normalized content only apply if it was already NFC
+            return match normalize::nfc(content) {
+                Cow::Borrowed(content) => unicode_term_range(&normalize::nfc(term), content),
+                Cow::Owned(_) => None,
+            };
+        }
+
+        Self::exact_term_range(term, content)
+    }
+
+    fn exact_term_range(term: &[u8], content: &[u8]) -> Option<Range<usize>> {
         if term.is_empty() || content.is_empty() || term.len() > content.len() {
-            return false;
+            return None;
         }
 
         let mut term_lower = ArrayVec::<u8, MAX_TERM_LENGTH>::new();
         for &b in term {
             if term_lower.try_push(b.to_ascii_lowercase()).is_err() {
-                return false;
-            }
-        }
-
-        let mut content_lower = ArrayVec::<u8, MAX_CONTENT_LENGTH>::new();
-        for &b in content {
-            if content_lower.try_push(b.to_ascii_lowercase()).is_err() {
-                return false;
+                return None;
             }
         }
 
-        let content_lower = &content_lower;
         let term_lower = &term_lower;
 
-        for i in 0..=content_lower.len().saturating_sub(term_lower.len()) {
-            let is_start = i == 0 || !content_lower[i - 1].is_ascii_alphanumeric();
-            let is_end = i + term_lower.len() == content_lower.len()
-                || !content_lower[i + term_lower.len()].is_ascii_alphanumeric();
+        for i in 0..=content.len().saturating_sub(term_lower.len()) {
+            let is_start = i == 0 || !content[i - 1].is_ascii_alphanumeric();
+            let is_end = i + term_lower.len() == content.len()
+                || !content[i + term_lower.len()].is_ascii_alphanumeric();
 
             if is_start && is_end {
                 let mut matches = true;
-                for (a, b) in term_lower.iter().zip(&content_lower[i..]) {
-                    if a != b {
+                for (a, b) in term_lower.iter().zip(&content[i..]) {
+                    if *a != b.to_ascii_lowercase() {
                         matches = false;
                         break;
                     }
                 }
                 if matches {
-                    return true;
+                    return Some(i..i + term_lower.len());
                 }
             }
         }
-        false
+        None
+    }
+
+    #[must_use]
+    pub fn term_prefix_matches(prefix: &[u8], content: &[u8]) -> bool {
+        if prefix.is_empty() || content.is_empty() || prefix.len() > content.len() {
+            return false;
+        }
+
+        (0..=content.len() - prefix.len()).any(|i| {
+            let is_start = i == 0 || !content[i - 1].is_ascii_alphanumeric();
+            is_start && content[i..i + prefix.len()].eq_ignore_ascii_case(prefix)
+        })
+    }
+
+    #[must_use]
+    pub fn fuzzy_term_matches(term: &[u8], content: &[u8], max_distance: u8) -> bool {
+        fuzzy_term_range(term, content, max_distance).is_some()
+    }
+
+    fn term_weight(term: &[u8], content: &[u8], options: &SearchOptions) -> f32 {
+        let Some(prefix) = prefix_term(term) else {
+            return if Self::term_matches(term, content) {
+                1.0
+            } else if Self::fuzzy_term_matches(term, content, options.edit_distance_for(term)) {
+                FUZZY_MATCH_WEIGHT
+            } else {
+                0.0
+            };
+        };
+
+        if Self::term_matches(prefix, content) {
+            1.0
+        } else if Self::term_prefix_matches(prefix, content) {
+            PREFIX_MATCH_WEIGHT
+        } else if content
+            .windows(prefix.len())
+            .any(|w| w.eq_ignore_ascii_case(prefix))
+        {
+            SUBSTRING_MATCH_WEIGHT
+        } else {
+            0.0
+        }
+    }
+
+    /// Where `term` first scores in `content`, trying matches in the same
+    /// order as `term_weight`.
+    fn match_range(term: &[u8], content: &[u8], options: &SearchOptions) -> Option<Range<usize>> {
+        let Some(prefix) = prefix_term(term) else {
+            return Self::term_range(term, content)
+                .or_else(|| fuzzy_term_range(term, content, options.edit_distance_for(term)));
+        };
+
+        if let Some(range) = Self::term_range(prefix, content) {
+            return Some(range);
+        }
+        let start = (0..=content.len().checked_sub(prefix.len())?)
+            .find(|&i| {
+                let is_start = i == 0 || !content[i - 1].is_ascii_alphanumeric();
+                is_start && content[i..i + prefix.len()].eq_ignore_ascii_case(prefix)
+            })
+            .or_else(|| {
+                content
+                    .windows(prefix.len())
+                    .position(|w| w.eq_ignore_ascii_case(prefix))
+            })?;
+        Some(start..start + prefix.len())
+    }
+
+    fn first_match(&self, query: &str, content: &[u8]) -> Option<MatchLocation> {
+        let stop_words = self.stop_words.for_query(query, &self.options);
+        query
+            .split_whitespace()
+            .map(literal_term)
+            .take(MAX_QUERY_TERMS)
+            .filter(|term| !term.is_empty() && !stop_words.contains(term.as_bytes()))
+            .filter_map(|term| Self::match_range(term.as_bytes(), content, &self.options))
+            .map(|range| range.start)
+            .min()
+            .map(|offset| MatchLocation::at(content, offset))
+    }
+
+    /// Byte ranges of `path`, as shown by `Path::to_string_lossy`, where the
+    /// query's terms matched, sorted and without overlaps. Glob searches match
+    /// the whole path and report no ranges.
+    #[must_use]
+    pub fn path_match_ranges(
+        &self,
+        query: &str,
+        path: &Path,
+    ) -> ArrayVec<Range<usize>, MAX_QUERY_TERMS> {
+        let mut ranges = ArrayVec::<Range<usize>, MAX_QUERY_TERMS>::new();
+        if self.mode == SearchMode::Glob {
+            return ranges;
+        }
+
+        let text = path.to_string_lossy();
+        let stop_words = self.stop_words.for_query(query, &self.options);
+        for term in query
+            .split_whitespace()
+            .map(literal_term)
+            .take(MAX_QUERY_TERMS)
+        {
+            if term.is_empty() || stop_words.contains(term.as_bytes()) {
+                continue;
+            }
+            if let Some(range) = Self::match_range(term.as_bytes(), text.as_bytes(), &self.options)
+            {
+                ranges.push(range);
+            }
+        }
+
+        ranges.sort_unstable_by_key(|range| range.start);
+        let mut merged = ArrayVec::<Range<usize>, MAX_QUERY_TERMS>::new();
+        for range in ranges {
+            match merged.last_mut() {
+                Some(last) if range.start <= last.end => last.end = last.end.max(range.end),
+                _ => merged.push(range),
+            }
+        }
+        merged
     }
 
+    /// Scores `doc` on its own. Search also weighs each term by how rare it
+    /// is across the index, so this is not the score search reports.
+    #[deprecated(
+        since = "1.0.0",
+        note = "does not weigh terms by rarity; use `SearchEngine::explain_score`"
+    )]
     #[must_use]
     pub fn calculate_score(query: &str, doc: &Document) -> f32 {
+        #[allow(deprecated)]
+        Self::calculate_score_with(query, doc, &StopWordList::new(), &SearchOptions::default())
+    }
+
+    /// Like [`Self::calculate_score`], with explicit stop words and options.
+    #[deprecated(
+        since = "1.0.0",
+        note = "does not weigh terms by rarity; use `SearchEngine::explain_score`"
+    )]
+    #[must_use]
+    pub fn calculate_score_with(
+        query: &str,
+        doc: &Document,
+        stop_words: &StopWordList,
+        options: &SearchOptions,
+    ) -> f32 {
+        Self::score_document(query, doc, stop_words, options, &TermStats::default(), None)
+    }
+
+    fn term_stats(&self, query: &str) -> TermStats {
+        let mut stats = TermStats {
+            doc_count: self.documents.len(),
+            doc_freq: ArrayVec::new(),
+        };
+        for term in query
+            .split_whitespace()
+            .map(literal_term)
+            .take(MAX_QUERY_TERMS)
+        {
+            let df = if term.is_empty() {
+                0
+            } else if let Some(df) = self.indexed_doc_freq(term.as_bytes()) {
+                df
+            } else {
+                self.documents
+                    .iter()
+                    .filter(|doc| {
+                        Self::term_weight(term.as_bytes(), &doc.content, &self.options) > 0.0
+                    })
+                    .count()
+            };
+            stats.doc_freq.push(df);
+        }
+        stats
+    }
+
+    /// Documents whose content matches `term`, read from the inverted index
+    /// when it gives the same count as scanning with `term_weight`: it is
+    /// complete, matching is exact, and words split the same way.
+    fn indexed_doc_freq(&self, term: &[u8]) -> Option<usize> {
+        let inverted = self.inverted.as_ref().filter(|i| i.is_complete())?;
+        let exact = self.options.max_edit_distance == 0 && is_indexable_term(term);
+        let same_words = !(cfg!(feature = "unicode") && inverted.has_non_ascii_content());
+        (exact && same_words).then(|| inverted.content_frequency(term))
+    }
+
+    fn term_frequency(term: &[u8], content: &[u8]) -> usize {
+        let prefix = prefix_term(term);
+        let needle = prefix.unwrap_or(term);
+        if !needle.iter().all(u8::is_ascii_alphanumeric) {
+            return 1;
+        }
+
+        let count_words = |matches: &dyn Fn(&[u8]) -> bool| {
+            content
+                .split(|b| !b.is_ascii_alphanumeric())
+                .filter(|word| matches(word))
+                .take(MAX_TERM_FREQUENCY)
+                .count()
+        };
+
+        // Mirror term_weight: exact hits take precedence over prefix hits
+        let mut count = count_words(&|word| word.eq_ignore_ascii_case(needle));
+        if count == 0 && prefix.is_some() {
+            count = count_words(&|word| {
+                word.len() >= needle.len() && word[..needle.len()].eq_ignore_ascii_case(needle)
+            });
+        }
+        count.max(1)
+    }
+
+    fn score_document(
+        query: &str,
+        doc: &Document,
+        stop_words: &StopWordList,
+        options: &SearchOptions,
+        stats: &TermStats,
+        mut breakdown: Option<&mut ArrayVec<TermMatch, MAX_QUERY_TERMS>>,
+    ) -> f32 {
         let mut score = 0.0_f32;
-        let mut query_terms = ArrayVec::<&[u8], 10>::new();
+        let mut query_terms = ArrayVec::<(usize, &[u8]), MAX_QUERY_TERMS>::new();
         let mut matches_found = 0_u32;
+        let stop_words = stop_words.for_query(query, options);
 
-        for term in query.split_whitespace() {
-            if query_terms.try_push(term.as_bytes()).is_err() {
+        for (term_index, term) in query.split_whitespace().map(literal_term).enumerate() {
+            if term.is_empty() || stop_words.contains(term.as_bytes()) {
+                continue;
+            }
+            if query_terms.try_push((term_index, term.as_bytes())).is_err() {
                 break;
             }
         }
@@ -386,64 +1647,290 @@ impl SearchEngine {
             return 0.0;
         }
 
-        for term in query_terms {
+        #[allow(clippy::cast_precision_loss)]
+        let term_count = u32::try_from(term_count).unwrap_or(1) as f32;
+
+        for (term_index, term) in query_terms {
             let mut term_score = 0.0;
 
-            if Self::term_matches(term, doc.path.to_string_lossy().as_bytes()) {
-                term_score += 0.6;
+            let path_weight =
+                Self::term_weight(term, doc.path.to_string_lossy().as_bytes(), options);
+            if path_weight > 0.0 {
+                term_score += options.path_weight * path_weight;
                 matches_found += 1;
             }
 
-            if Self::term_matches(term, &doc.content) {
-                term_score += 0.4;
+            let content_weight = Self::term_weight(term, &doc.content, options);
+            if content_weight > 0.0 {
+                #[allow(clippy::cast_precision_loss)]
+                let tf = Self::term_frequency(term, &doc.content) as f32;
+                let tf_factor = tf / (tf + TERM_FREQUENCY_SATURATION);
+                term_score += options.content_weight
+                    * content_weight
+                    * tf_factor
+                    * stats.idf_factor(term_index);
                 matches_found += 1;
             }
 
+            if let Some(breakdown) = breakdown.as_deref_mut() {
+                breakdown.push(TermMatch {
+                    term_index,
+                    matched_in_path: path_weight > 0.0,
+                    matched_in_content: content_weight > 0.0,
+                    contribution: term_score / term_count * 100.0,
+                });
+            }
+
             score += term_score;
         }
 
         if matches_found == 0 {
             0.0
         } else {
-            #[allow(clippy::cast_precision_loss)]
-            let term_count = u32::try_from(term_count).unwrap_or(1) as f32;
             (score / term_count * 100.0).min(100.0)
         }
     }
 
     pub fn search(&self, query: &str) -> SnapResult<ArrayVec<SearchResult, MAX_RESULTS>> {
-        validate_query(query)?;
+        let results: ArrayVec<_, MAX_RESULTS> = self.search_iter(query)?.collect();
 
+        assert!(results.len() <= MAX_RESULTS, "Result buffer overflow");
+
+        Ok(results)
+    }
+
+    pub fn search_iter<'a>(
+        &'a self,
+        query: &str,
+    ) -> SnapResult<impl Iterator<Item = SearchResult> + use<'a>> {
+        let ranked = self.ranked(query)?;
+        Ok(ranked
+            .into_iter()
+            .take(MAX_RESULTS)
+            .map(|(score, idx)| SearchResult {
+                path: self.documents[idx].path.clone(),
+                score,
+            }))
+    }
+
+    pub fn search_page(
+        &self,
+        query: &str,
+        page: usize,
+        page_size: usize,
+    ) -> SnapResult<(ArrayVec<SearchResult, MAX_RESULTS>, usize)> {
+        validate_page(page, page_size)?;
+
+        let ranked = self.ranked(query)?;
+        let total = ranked.len();
+        let results = ranked
+            .into_iter()
+            .skip(page * page_size)
+            .take(page_size)
+            .map(|(score, idx)| SearchResult {
+                path: self.documents[idx].path.clone(),
+                score,
+            })
+            .collect();
+
+        Ok((results, total))
+    }
+
+    /// Like `search`, but also reports where each document's content first
+    /// matched. Glob searches only look at paths, so they never have a location.
+    pub fn search_located(&self, query: &str) -> SnapResult<ArrayVec<LocatedResult, MAX_RESULTS>> {
         let mut results = ArrayVec::new();
-        let mut scores = ArrayVec::<(f32, usize), MAX_DOCUMENTS>::new();
+        for (score, idx) in self.ranked(query)?.into_iter().take(MAX_RESULTS) {
+            let doc = &self.documents[idx];
+            let location = match self.mode {
+                SearchMode::Glob => None,
+                SearchMode::Text => self.first_match(query, &doc.content),
+            };
+            results.push(LocatedResult {
+                result: SearchResult {
+                    path: doc.path.clone(),
+                    score,
+                },
+                location,
+            });
+        }
+
+        assert!(results.len() <= MAX_RESULTS, "Result buffer overflow");
 
-        let glob_matcher = GlobMatcher::new(query)?;
-        let is_glob_query = query.contains('*');
+        Ok(results)
+    }
 
-        for (idx, doc) in self.documents.iter().enumerate() {
-            let score = if is_glob_query {
-                if glob_matcher.is_match(&doc.path) {
+    /// Where `query` first matches the content of the indexed document at
+    /// `path`, as [`SearchEngine::search_located`] reports it.
+    #[must_use]
+    pub fn locate(&self, query: &str, path: &Path) -> Option<MatchLocation> {
+        match self.mode {
+            SearchMode::Glob => None,
+            SearchMode::Text => self
+                .get_document(path)
+                .and_then(|doc| self.first_match(query, &doc.content)),
+        }
+    }
+
+    pub fn search_explain(
+        &self,
+        query: &str,
+    ) -> SnapResult<ArrayVec<ExplainedResult, MAX_RESULTS>> {
+        let mut results = ArrayVec::new();
+        let scoring = self.scoring(query)?;
+        for (score, idx) in self
+            .ranked_with(query, &scoring)?
+            .into_iter()
+            .take(MAX_RESULTS)
+        {
+            let doc = &self.documents[idx];
+            let mut terms = ArrayVec::new();
+            if self.mode == SearchMode::Text {
+                Self::score_document(
+                    query,
+                    doc,
+                    &self.stop_words,
+                    &self.options,
+                    &scoring.stats,
+                    Some(&mut terms),
+                );
+            }
+            results.push(ExplainedResult {
+                result: SearchResult {
+                    path: doc.path.clone(),
+                    score,
+                },
+                terms,
+            });
+        }
+
+        assert!(results.len() <= MAX_RESULTS, "Result buffer overflow");
+
+        Ok(results)
+    }
+
+    pub fn explain_score(&self, query: &str, path: &Path) -> SnapResult<ScoreExplanation> {
+        validate_query(query)?;
+
+        let doc = self.get_document(path).ok_or_else(|| {
+            anyhow::Error::from(SnapError::with_code(
+                format!("Document not in index: {}", path.display()),
+                ERROR_DOCUMENT_NOT_FOUND,
+            ))
+        })?;
+
+        let mut term_scores = ArrayVec::new();
+        let total_score = match self.mode {
+            SearchMode::Glob => {
+                if GlobMatcher::new(query, self.literal_separator)?.is_match(&doc.path) {
                     100.0
                 } else {
                     0.0
                 }
-            } else {
-                let base_score = Self::calculate_score(query, doc);
-                if glob_matcher.is_match(&doc.path) {
-                    (base_score * 1.5).min(100.0)
-                } else {
-                    base_score
+            }
+            SearchMode::Text => {
+                let mut matches = ArrayVec::<TermMatch, MAX_QUERY_TERMS>::new();
+                let total = Self::score_document(
+                    query,
+                    doc,
+                    &self.stop_words,
+                    &self.options,
+                    &self.term_stats(query),
+                    Some(&mut matches),
+                );
+                let terms: ArrayVec<&str, MAX_QUERY_TERMS> =
+                    query.split_whitespace().take(MAX_QUERY_TERMS).collect();
+                for m in matches {
+                    let text = terms.get(m.term_index).copied().unwrap_or_default();
+                    term_scores.push(TermScore {
+                        term: ArrayString::from(text).unwrap_or_default(),
+                        path_hit: m.matched_in_path,
+                        content_hit: m.matched_in_content,
+                        contribution: m.contribution,
+                    });
                 }
-            };
+                total
+            }
+        };
 
-            if score > 0.0 {
-                scores.try_push((score, idx)).map_err(|_| {
-                    anyhow::Error::from(SnapError::with_code(
-                        "Too many matching documents",
-                        ERROR_TOO_MANY_DOCUMENTS,
-                    ))
-                })?;
+        Ok(ScoreExplanation {
+            total_score,
+            term_scores,
+        })
+    }
+
+    /// Calls `f` with each matching document as it is scored, unsorted and in
+    /// index order, until `f` returns `ControlFlow::Break`.
+    ///
+    /// Scores are the same as [`SearchEngine::search`] reports; only the
+    /// ranking is skipped, so stopping early saves scoring the rest. Use it for
+    /// existence checks or when any few matches will do.
+    pub fn search_each(
+        &self,
+        query: &str,
+        mut f: impl FnMut(SearchResult) -> ControlFlow<()>,
+    ) -> SnapResult<()> {
+        let scoring = self.scoring(query)?;
+        self.score_each(query, &scoring, |score, idx| {
+            f(SearchResult {
+                path: self.documents[idx].path.clone(),
+                score,
+            })
+        })
+    }
+
+    fn ranked(&self, query: &str) -> SnapResult<ArrayVec<(f32, usize), MAX_DOCUMENTS>> {
+        self.ranked_with(query, &self.scoring(query)?)
+    }
+
+    #[cfg(not(feature = "rayon"))]
+    fn ranked_with(
+        &self,
+        query: &str,
+        scoring: &Scoring,
+    ) -> SnapResult<ArrayVec<(f32, usize), MAX_DOCUMENTS>> {
+        let mut scores = ArrayVec::<(f32, usize), MAX_DOCUMENTS>::new();
+        let mut overflowed = false;
+        self.score_each(query, scoring, |score, idx| {
+            if scores.try_push((score, idx)).is_err() {
+                overflowed = true;
+                return ControlFlow::Break(());
             }
+            ControlFlow::Continue(())
+        })?;
+        Self::sorted_scores(scores, overflowed)
+    }
+
+    /// Scores documents on the rayon thread pool. Matches are collected in
+    /// document order, so ties rank exactly as in the sequential build.
+    #[cfg(feature = "rayon")]
+    fn ranked_with(
+        &self,
+        query: &str,
+        scoring: &Scoring,
+    ) -> SnapResult<ArrayVec<(f32, usize), MAX_DOCUMENTS>> {
+        use rayon::prelude::*;
+
+        let matches: Vec<(f32, usize)> = (0..self.documents.len())
+            .into_par_iter()
+            .map(|idx| (self.score_at(query, scoring, idx), idx))
+            .filter(|(score, _)| *score > 0.0)
+            .collect();
+
+        let mut scores = ArrayVec::<(f32, usize), MAX_DOCUMENTS>::new();
+        let overflowed = scores.try_extend_from_slice(&matches).is_err();
+        Self::sorted_scores(scores, overflowed)
+    }
+
+    fn sorted_scores(
+        mut scores: ArrayVec<(f32, usize), MAX_DOCUMENTS>,
+        overflowed: bool,
+    ) -> SnapResult<ArrayVec<(f32, usize), MAX_DOCUMENTS>> {
+        if overflowed {
+            return Err(anyhow::Error::from(SnapError::with_code(
+                "Too many matching documents",
+                ERROR_TOO_MANY_DOCUMENTS,
+            )));
         }
 
         assert!(scores.len() <= MAX_DOCUMENTS, "Score buffer overflow");
@@ -452,18 +1939,104 @@ impl SearchEngine {
             .as_mut_slice()
             .sort_by(|a, b| b.0.partial_cmp(&a.0).unwrap_or(std::cmp::Ordering::Equal));
 
-        for (score, idx) in scores.iter().take(MAX_RESULTS) {
-            results
-                .try_push(SearchResult {
-                    path: self.documents[*idx].path.clone(),
-                    score: *score,
-                })
-                .map_err(|_| {
-                    anyhow::Error::from(SnapError::with_code(
-                        "Too many results",
-                        ERROR_TOO_MANY_DOCUMENTS,
-                    ))
-                })?;
+        Ok(scores)
+    }
+
+    fn score_each(
+        &self,
+        query: &str,
+        scoring: &Scoring,
+        mut f: impl FnMut(f32, usize) -> ControlFlow<()>,
+    ) -> SnapResult<()> {
+        for idx in 0..self.documents.len() {
+            let score = self.score_at(query, scoring, idx);
+            if score > 0.0 && f(score, idx).is_break() {
+                break;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Validates `query` and prepares what scoring every document needs.
+    fn scoring(&self, query: &str) -> SnapResult<Scoring> {
+        validate_query(query)?;
+
+        let glob_matcher = match self.mode {
+            SearchMode::Glob => Some(GlobMatcher::new(query, self.literal_separator)?),
+            SearchMode::Text => None,
+        };
+        let candidates = glob_matcher
+            .is_none()
+            .then(|| self.candidate_documents(query))
+            .flatten();
+        let stats = if glob_matcher.is_none() {
+            self.term_stats(query)
+        } else {
+            TermStats::default()
+        };
+
+        Ok(Scoring {
+            glob_matcher,
+            candidates,
+            stats,
+        })
+    }
+
+    /// Score of the document at `idx`; 0 when it does not match.
+    fn score_at(&self, query: &str, scoring: &Scoring, idx: usize) -> f32 {
+        if scoring.candidates.is_some_and(|c| !c[idx]) {
+            return 0.0;
+        }
+
+        let doc = &self.documents[idx];
+        match &scoring.glob_matcher {
+            Some(matcher) if matcher.is_match(&doc.path) => 100.0,
+            Some(_) => 0.0,
+            None => Self::score_document(
+                query,
+                doc,
+                &self.stop_words,
+                &self.options,
+                &scoring.stats,
+                None,
+            ),
+        }
+    }
+
+    pub fn search_prefix(&self, prefix: &str) -> SnapResult<ArrayVec<SearchResult, MAX_RESULTS>> {
+        if prefix.len() > MAX_PATH_BYTES {
+            return Err(anyhow::Error::from(SnapError::with_code(
+                "Prefix too long",
+                ERROR_PATH_TOO_LONG,
+            )));
+        }
+
+        let prefix = prefix.as_bytes();
+        let mut matches = ArrayVec::<(f32, usize), MAX_DOCUMENTS>::new();
+
+        for (idx, doc) in self.documents.iter().enumerate() {
+            if let Some(score) = prefix_score(prefix, doc.path.as_os_str().as_encoded_bytes()) {
+                matches.push((score, idx));
+            }
+        }
+
+        matches.as_mut_slice().sort_by(|a, b| {
+            let path_a = &self.documents[a.1].path;
+            let path_b = &self.documents[b.1].path;
+            path_a
+                .as_os_str()
+                .len()
+                .cmp(&path_b.as_os_str().len())
+                .then_with(|| path_a.cmp(path_b))
+        });
+
+        let mut results = ArrayVec::new();
+        for (score, idx) in matches.iter().take(MAX_RESULTS) {
+            results.push(SearchResult {
+                path: self.documents[*idx].path.clone(),
+                score: *score,
+            });
         }
 
         assert!(results.len() <= MAX_RESULTS, "Result buffer overflow");
@@ -472,258 +2045,2662 @@ impl SearchEngine {
     }
 }
 
-pub fn validate_query(query: &str) -> SnapResult<()> {
-    if query.is_empty() {
-        return Err(anyhow::Error::from(SnapError::with_code(
-            "Query must not be empty",
-            ERROR_INVALID_QUERY,
-        )));
+/// A [`SearchEngine`] whose index is loaded and saved with tokio's file I/O.
+/// Searching is pure CPU work and stays synchronous; waiting on the index
+/// lock runs on the blocking thread pool.
+#[cfg(feature = "async")]
+#[derive(Debug, Default)]
+pub struct AsyncSearchEngine {
+    engine: SearchEngine,
+}
+
+#[cfg(feature = "async")]
+impl AsyncSearchEngine {
+    #[must_use]
+    pub const fn new(engine: SearchEngine) -> Self {
+        Self { engine }
     }
 
-    if query.len() > MAX_TERM_LENGTH {
-        return Err(anyhow::Error::from(SnapError::with_code(
-            "Query too long",
-            ERROR_INVALID_QUERY,
-        )));
+    #[must_use]
+    pub const fn engine(&self) -> &SearchEngine {
+        &self.engine
+    }
+
+    pub const fn engine_mut(&mut self) -> &mut SearchEngine {
+        &mut self.engine
+    }
+
+    #[must_use]
+    pub fn into_inner(self) -> SearchEngine {
+        self.engine
+    }
+
+    /// Loads an index like [`SearchEngine::load`].
+    pub async fn load(path: &Path) -> SnapResult<Self> {
+        use tokio::io::AsyncReadExt;
+
+        let lock_target = path.to_path_buf();
+        tokio::task::spawn_blocking(move || IndexLock::wait_until_free(&lock_target)).await??;
+
+        let file = tokio::fs::File::open(path).await.map_err(|e| {
+            SnapError::with_code(format!("Failed to open index: {e}"), ERROR_INVALID_INDEX)
+        })?;
+        let mut bytes = Vec::new();
+        let mut file = file.take(MAX_INDEX_LEN as u64 + 1);
+        file.read_to_end(&mut bytes).await.map_err(|e| {
+            SnapError::with_code(format!("Failed to read index: {e}"), ERROR_INVALID_INDEX)
+        })?;
+
+        SearchEngine::decode(bytes.as_slice()).map(Self::new)
     }
 
-    if query.contains('\0') || !query.chars().all(|c| c.is_ascii() || c.is_whitespace()) {
+    /// Saves the index like [`SearchEngine::save`], replacing `path` atomically.
+    pub async fn save(&self, path: &Path) -> SnapResult<()> {
+        let lock_target = path.to_path_buf();
+        let _lock = tokio::task::spawn_blocking(move || IndexLock::acquire(&lock_target)).await??;
+
+        let tmp_path = lock::with_suffix(path, lock::TMP_SUFFIX);
+        if let Err(e) = self.write_to(&tmp_path).await {
+            let _ = tokio::fs::remove_file(&tmp_path).await;
+            return Err(e);
+        }
+
+        if let Err(e) = tokio::fs::rename(&tmp_path, path).await {
+            let _ = tokio::fs::remove_file(&tmp_path).await;
+            return Err(anyhow::Error::from(SnapError::with_code(
+                format!("Failed to replace index: {e}"),
+                ERROR_INVALID_INDEX,
+            )));
+        }
+
+        Ok(())
+    }
+
+    async fn write_to(&self, path: &Path) -> SnapResult<()> {
+        use tokio::io::AsyncWriteExt;
+
+        let bytes = self.engine.encode(path)?;
+        let mut file = tokio::fs::File::create(path).await.map_err(|e| {
+            SnapError::with_code(format!("Failed to create index: {e}"), ERROR_INVALID_INDEX)
+        })?;
+        file.write_all(&bytes).await.map_err(|e| {
+            SnapError::with_code(format!("Failed to write index: {e}"), ERROR_INVALID_INDEX)
+        })?;
+        file.sync_all().await.map_err(|e| {
+            SnapError::with_code(format!("Failed to flush index: {e}"), ERROR_INVALID_INDEX)
+        })?;
+
+        Ok(())
+    }
+
+    /// Searches the wrapped engine; see [`SearchEngine::search`].
+    pub fn search(&self, query: &str) -> SnapResult<ArrayVec<SearchResult, MAX_RESULTS>> {
+        self.engine.search(query)
+    }
+}
+
+#[cfg(feature = "async")]
+impl From<SearchEngine> for AsyncSearchEngine {
+    fn from(engine: SearchEngine) -> Self {
+        Self::new(engine)
+    }
+}
+
+fn prefix_score(prefix: &[u8], path: &[u8]) -> Option<f32> {
+    let rest = path.strip_prefix(prefix)?;
+    let at_boundary =
+        prefix.is_empty() || prefix.ends_with(b"/") || rest.is_empty() || rest.starts_with(b"/");
+
+    Some(if at_boundary {
+        100.0
+    } else {
+        PARTIAL_COMPONENT_SCORE
+    })
+}
+
+/// Like `SearchEngine::exact_term_range`, but word boundaries fall between
+/// Unicode scalars and letters are compared after simple case folding.
+#[cfg(feature = "unicode")]
+fn unicode_term_range(term: &str, content: &str) -> Option<Range<usize>> {
+    if term.is_empty() {
+        return None;
+    }
+
+    let mut prev = None;
+    for (i, c) in content.char_indices() {
+        let is_start = prev.is_none_or(|p: char| !p.is_alphanumeric());
+        if is_start && let Some(len) = folded_prefix_len(term, &content[i..]) {
+            let is_end = content[i + len..]
+                .chars()
+                .next()
+                .is_none_or(|next| !next.is_alphanumeric());
+            if is_end {
+                return Some(i..i + len);
+            }
+        }
+        prev = Some(c);
+    }
+    None
+}
+
+/// Byte length of the start of `text` that equals `term` once both are case
+/// folded, if `text` starts with `term` at all.
+#[cfg(feature = "unicode")]
+fn folded_prefix_len(term: &str, text: &str) -> Option<usize> {
+    let mut expected = term.chars().flat_map(char::to_lowercase).peekable();
+    for (offset, c) in text.char_indices() {
+        if expected.peek().is_none() {
+            return Some(offset);
+        }
+        for folded in c.to_lowercase() {
+            if expected.next() != Some(folded) {
+                return None;
+            }
+        }
+    }
+    expected.peek().is_none().then_some(text.len())
+}
+
+fn fuzzy_term_range(term: &[u8], content: &[u8], max_distance: u8) -> Option<Range<usize>> {
+    if term.is_empty() || term.len() > MAX_TERM_LENGTH || max_distance == 0 {
+        return None;
+    }
+
+    let max_distance = usize::from(max_distance);
+    let mut offset = 0;
+    for word in content.split(|b| !b.is_ascii_alphanumeric()) {
+        // Adjacent separators leave empty words, which any short term is
+        // within reach of
+        if !word.is_empty()
+            && word.len().abs_diff(term.len()) <= max_distance
+            && edit_distance_within(term, word, max_distance)
+        {
+            return Some(offset..offset + word.len());
+        }
+        offset += word.len() + 1;
+    }
+    None
+}
+
+fn edit_distance_within(a: &[u8], b: &[u8], max_distance: usize) -> bool {
+    assert!(a.len() <= MAX_TERM_LENGTH, "Term too long");
+
+    let mut prev = [0_usize; MAX_TERM_LENGTH + 1];
+    let mut curr = [0_usize; MAX_TERM_LENGTH + 1];
+    for (i, cell) in prev.iter_mut().enumerate().take(a.len() + 1) {
+        *cell = i;
+    }
+
+    for (j, &bc) in b.iter().enumerate() {
+        curr[0] = j + 1;
+        let mut row_min = curr[0];
+        for (i, &ac) in a.iter().enumerate() {
+            let cost = usize::from(!ac.eq_ignore_ascii_case(&bc));
+            curr[i + 1] = (prev[i] + cost).min(prev[i + 1] + 1).min(curr[i] + 1);
+            row_min = row_min.min(curr[i + 1]);
+        }
+        if row_min > max_distance {
+            return false;
+        }
+        std::mem::swap(&mut prev, &mut curr);
+    }
+
+    prev[a.len()] <= max_distance
+}
+
+fn prefix_term(term: &[u8]) -> Option<&[u8]> {
+    let prefix = term.strip_suffix(b"*")?;
+    let is_literal = !prefix.is_empty()
+        && !prefix
+            .iter()
+            .any(|b| matches!(b, b'*' | b'?' | b'[' | b']' | b'{' | b'}'));
+    is_literal.then_some(prefix)
+}
+
+fn literal_term(term: &str) -> &str {
+    if prefix_term(term.as_bytes()).is_some() {
+        term
+    } else {
+        term.trim_matches('*')
+    }
+}
+
+/// Merges results from several indexes into one ranking, best first.
+///
+/// A file reachable from more than one root (say, when one indexed directory
+/// contains another, or through a symlink) is kept once, with its highest
+/// score. Scores are compared as they are, but each index weighs terms by how
+/// rare they are among its own documents, so the ranking across indexes is
+/// approximate.
+#[must_use]
+pub fn merge_results(
+    sets: &[(PathBuf, ArrayVec<SearchResult, MAX_RESULTS>)],
+) -> ArrayVec<RootedResult, MAX_RESULTS> {
+    let mut merged: Vec<(PathBuf, RootedResult)> = Vec::new();
+    for (root, results) in sets {
+        for result in results {
+            let key = canonical_path(&result.resolved_path(root));
+            let rooted = RootedResult {
+                root: root.clone(),
+                result: result.clone(),
+            };
+            match merged.iter_mut().find(|(existing, _)| *existing == key) {
+                Some((_, existing)) if existing.result.score < result.score => *existing = rooted,
+                Some(_) => {}
+                None => merged.push((key, rooted)),
+            }
+        }
+    }
+
+    merged.sort_by(|a, b| {
+        b.1.result
+            .score
+            .partial_cmp(&a.1.result.score)
+            .unwrap_or(std::cmp::Ordering::Equal)
+            .then_with(|| a.0.cmp(&b.0))
+    });
+
+    merged
+        .into_iter()
+        .take(MAX_RESULTS)
+        .map(|(_, rooted)| rooted)
+        .collect()
+}
+
+/// Resolves `path` on disk so every spelling of a file compares equal. A file
+/// that cannot be resolved, such as one removed since it was indexed, only
+/// has its `.` components dropped.
+fn canonical_path(path: &Path) -> PathBuf {
+    fs::canonicalize(path).unwrap_or_else(|_| {
+        path.components()
+            .filter(|c| !matches!(c, Component::CurDir))
+            .collect()
+    })
+}
+
+/// Reads the metadata block that starts a payload from format 5 on; crawl
+/// filters follow the root from format 7.
+fn read_metadata(file: &mut &[u8], version: u8) -> SnapResult<IndexMetadata> {
+    let invalid = |e: std::io::Error| {
+        anyhow::Error::from(SnapError::with_code(
+            format!("Failed to read index metadata: {e}"),
+            ERROR_INVALID_INDEX,
+        ))
+    };
+
+    let mut created = [0u8; 8];
+    file.read_exact(&mut created).map_err(invalid)?;
+    let created =
+        SystemTime::UNIX_EPOCH.checked_add(Duration::from_secs(u64::from_le_bytes(created)));
+
+    let mut version_len = [0u8; 1];
+    file.read_exact(&mut version_len).map_err(invalid)?;
+    let mut tool_version = vec![0u8; usize::from(version_len[0])];
+    file.read_exact(&mut tool_version).map_err(invalid)?;
+
+    let mut root_len = [0u8; 2];
+    file.read_exact(&mut root_len).map_err(invalid)?;
+    let root_len = usize::from(u16::from_le_bytes(root_len));
+    if root_len > MAX_PATH_BYTES {
         return Err(anyhow::Error::from(SnapError::with_code(
-            "Query contains invalid characters",
-            ERROR_INVALID_QUERY,
+            "Path too long",
+            ERROR_PATH_TOO_LONG,
         )));
     }
+    let mut root = vec![0u8; root_len];
+    file.read_exact(&mut root).map_err(invalid)?;
 
-    Ok(())
+    let filters = if version >= VERSION_V7 {
+        read_filters(file)?
+    } else {
+        None
+    };
+
+    Ok(IndexMetadata {
+        created,
+        tool_version: String::from_utf8_lossy(&tool_version).into_owned(),
+        root: (!root.is_empty()).then(|| path_from_bytes(&root)),
+        filters,
+        document_count: 0,
+    })
+}
+
+fn read_filters(file: &mut &[u8]) -> SnapResult<Option<CrawlFilters>> {
+    let invalid = |msg: String| {
+        anyhow::Error::from(SnapError::with_code(
+            format!("Failed to read index crawl filters: {msg}"),
+            ERROR_INVALID_INDEX,
+        ))
+    };
+    let mut byte = [0u8; 1];
+
+    file.read_exact(&mut byte)
+        .map_err(|e| invalid(e.to_string()))?;
+    if byte[0] == 0 {
+        return Ok(None);
+    }
+
+    file.read_exact(&mut byte)
+        .map_err(|e| invalid(e.to_string()))?;
+    let skip_hidden = byte[0] != 0;
+
+    let mut min_file_size = [0u8; 8];
+    file.read_exact(&mut min_file_size)
+        .map_err(|e| invalid(e.to_string()))?;
+
+    file.read_exact(&mut byte)
+        .map_err(|e| invalid(e.to_string()))?;
+    let count = usize::from(byte[0]);
+    if count > MAX_EXTENSIONS {
+        return Err(invalid(format!("more than {MAX_EXTENSIONS} extensions")));
+    }
+    let mut extensions = Vec::with_capacity(count);
+    for _ in 0..count {
+        file.read_exact(&mut byte)
+            .map_err(|e| invalid(e.to_string()))?;
+        let len = usize::from(byte[0]);
+        if len > MAX_EXTENSION_LENGTH {
+            return Err(invalid(format!(
+                "extension longer than {MAX_EXTENSION_LENGTH} bytes"
+            )));
+        }
+        let mut ext = vec![0u8; len];
+        file.read_exact(&mut ext)
+            .map_err(|e| invalid(e.to_string()))?;
+        extensions.push(String::from_utf8(ext).map_err(|e| invalid(e.to_string()))?);
+    }
+
+    Ok(Some(CrawlFilters {
+        extensions,
+        skip_hidden,
+        min_file_size: u64::from_le_bytes(min_file_size),
+    }))
+}
+
+/// Writes the metadata block for an index saved at `index_path`, whose
+/// directory is taken as the indexed root.
+fn write_metadata(payload: &mut Vec<u8>, index_path: &Path, filters: Option<&CrawlFilters>) {
+    let created = creation_time(std::env::var("SOURCE_DATE_EPOCH").ok().as_deref());
+    payload.extend_from_slice(&created.to_le_bytes());
+
+    let tool_version = env!("CARGO_PKG_VERSION");
+    let version_len = u8::try_from(tool_version.len()).unwrap_or(u8::MAX);
+    payload.push(version_len);
+    payload.extend_from_slice(&tool_version.as_bytes()[..usize::from(version_len)]);
+
+    let root = match index_path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    let root = root.canonicalize().unwrap_or_else(|_| root.to_path_buf());
+    let root = path_to_bytes(&root);
+    // An unrepresentable root is left out rather than failing the save
+    let root = if root.len() > MAX_PATH_BYTES {
+        &[][..]
+    } else {
+        &root
+    };
+    let root_len = u16::try_from(root.len()).unwrap_or_default();
+    payload.extend_from_slice(&root_len.to_le_bytes());
+    payload.extend_from_slice(root);
+
+    write_filters(payload, filters);
+}
+
+/// Writes `filters`, keeping only the extensions a crawler would accept.
+fn write_filters(payload: &mut Vec<u8>, filters: Option<&CrawlFilters>) {
+    let Some(filters) = filters else {
+        payload.push(0);
+        return;
+    };
+    payload.push(1);
+    payload.push(u8::from(filters.skip_hidden));
+    payload.extend_from_slice(&filters.min_file_size.to_le_bytes());
+
+    let extensions: Vec<&str> = filters
+        .extensions
+        .iter()
+        .map(String::as_str)
+        .filter(|ext| ext.len() <= MAX_EXTENSION_LENGTH)
+        .take(MAX_EXTENSIONS)
+        .collect();
+    // MAX_EXTENSIONS fits in a byte
+    payload.push(extensions.len() as u8);
+    for ext in extensions {
+        payload.push(ext.len() as u8);
+        payload.extend_from_slice(ext.as_bytes());
+    }
+}
+
+/// Reads the file stamp that ends each document record from format 8 on.
+fn read_stamp(file: &mut &[u8]) -> SnapResult<Option<FileStamp>> {
+    let invalid = |e: std::io::Error| {
+        anyhow::Error::from(SnapError::with_code(
+            format!("Failed to read file stamp: {e}"),
+            ERROR_INVALID_INDEX,
+        ))
+    };
+
+    let mut flag = [0u8; 1];
+    file.read_exact(&mut flag).map_err(invalid)?;
+    if flag[0] == 0 {
+        return Ok(None);
+    }
+    let mut secs = [0u8; 8];
+    file.read_exact(&mut secs).map_err(invalid)?;
+    let mut nanos = [0u8; 4];
+    file.read_exact(&mut nanos).map_err(invalid)?;
+    let mut len = [0u8; 8];
+    file.read_exact(&mut len).map_err(invalid)?;
+
+    let modified = SystemTime::UNIX_EPOCH.checked_add(Duration::new(
+        u64::from_le_bytes(secs),
+        u32::from_le_bytes(nanos),
+    ));
+    Ok(modified.map(|modified| FileStamp {
+        modified,
+        len: u64::from_le_bytes(len),
+    }))
+}
+
+/// Writes `stamp`, or only an absent flag when there is none or the time is
+/// before the epoch.
+fn write_stamp(payload: &mut Vec<u8>, stamp: Option<FileStamp>) {
+    let since_epoch = stamp.and_then(|stamp| {
+        let elapsed = stamp.modified.duration_since(SystemTime::UNIX_EPOCH).ok()?;
+        Some((elapsed, stamp.len))
+    });
+    let Some((elapsed, len)) = since_epoch else {
+        payload.push(0);
+        return;
+    };
+    payload.push(1);
+    payload.extend_from_slice(&elapsed.as_secs().to_le_bytes());
+    payload.extend_from_slice(&elapsed.subsec_nanos().to_le_bytes());
+    payload.extend_from_slice(&len.to_le_bytes());
+}
+
+/// Seconds since the epoch recorded as an index's creation time. A valid
+/// `SOURCE_DATE_EPOCH` is used instead of the clock so identical trees give
+/// byte-identical indexes.
+fn creation_time(source_date_epoch: Option<&str>) -> u64 {
+    source_date_epoch
+        .and_then(|epoch| epoch.trim().parse().ok())
+        .unwrap_or_else(|| {
+            SystemTime::now()
+                .duration_since(SystemTime::UNIX_EPOCH)
+                .map_or(0, |elapsed| elapsed.as_secs())
+        })
+}
+
+/// Index bytes without the creation time, and the checksum that covers it.
+#[cfg(test)]
+pub(crate) fn without_timestamp(bytes: &[u8]) -> Vec<u8> {
+    let checksum = INDEX_HEADER_LEN - 4;
+    let created = INDEX_HEADER_LEN..INDEX_HEADER_LEN + 8;
+    [&bytes[..checksum], &bytes[created.end..]].concat()
+}
+
+/// Bytes stored for `path` from format 6: the raw OS bytes on Unix, where
+/// any byte string is a valid name, and UTF-8 elsewhere. Earlier formats
+/// wrote UTF-8 with invalid sequences replaced, which reads back the same.
+#[cfg(unix)]
+fn path_to_bytes(path: &Path) -> Cow<'_, [u8]> {
+    use std::os::unix::ffi::OsStrExt;
+    Cow::Borrowed(path.as_os_str().as_bytes())
+}
+
+#[cfg(not(unix))]
+fn path_to_bytes(path: &Path) -> Cow<'_, [u8]> {
+    match path.to_string_lossy() {
+        Cow::Borrowed(path) => Cow::Borrowed(path.as_bytes()),
+        Cow::Owned(path) => Cow::Owned(path.into_bytes()),
+    }
+}
+
+#[cfg(unix)]
+fn path_from_bytes(bytes: &[u8]) -> PathBuf {
+    use std::os::unix::ffi::OsStrExt;
+    PathBuf::from(std::ffi::OsStr::from_bytes(bytes))
+}
+
+#[cfg(not(unix))]
+fn path_from_bytes(bytes: &[u8]) -> PathBuf {
+    PathBuf::from(String::from_utf8_lossy(bytes).into_owned())
+}
+
+/// Most payload bytes an index holding `ndocs` documents of at most
+/// `content_limit` bytes can have.
+const fn max_payload_len(ndocs: usize, content_limit: usize) -> usize {
+    MAX_METADATA_LEN + ndocs * (MAX_RECORD_OVERHEAD + content_limit)
+}
+
+#[must_use]
+pub fn has_index_magic(path: &Path) -> bool {
+    let mut magic = [0u8; 4];
+    File::open(path)
+        .and_then(|mut file| file.read_exact(&mut magic))
+        .is_ok_and(|()| magic == MAGIC)
 }
 
-#[cfg(test)]
-mod tests {
-    use std::fs::File;
-    use std::io::Write;
+#[cfg(feature = "unicode")]
+fn is_query_char(c: char) -> bool {
+    c.is_ascii() || c.is_whitespace() || c.is_alphanumeric() || normalize::is_combining_mark(c)
+}
+
+#[cfg(not(feature = "unicode"))]
+fn is_query_char(c: char) -> bool {
+    c.is_ascii() || c.is_whitespace()
+}
+
+pub fn validate_page(page: usize, page_size: usize) -> SnapResult<()> {
+    let valid = page_size > 0
+        && page_size <= MAX_RESULTS
+        && page
+            .checked_mul(page_size)
+            .is_some_and(|offset| offset < MAX_DOCUMENTS * 2);
+    if !valid {
+        return Err(anyhow::Error::from(SnapError::with_code(
+            format!("Invalid page parameters: page {page}, page size {page_size}"),
+            ERROR_INVALID_QUERY,
+        )));
+    }
+
+    Ok(())
+}
+
+pub fn validate_query(query: &str) -> SnapResult<()> {
+    if query.is_empty() {
+        return Err(anyhow::Error::from(SnapError::with_code(
+            "Query must not be empty",
+            ERROR_INVALID_QUERY,
+        )));
+    }
+
+    if query.len() > MAX_TERM_LENGTH {
+        return Err(anyhow::Error::from(SnapError::with_code(
+            "Query too long",
+            ERROR_INVALID_QUERY,
+        )));
+    }
+
+    if query.contains('\0') || !query.chars().all(is_query_char) {
+        return Err(anyhow::Error::from(SnapError::with_code(
+            "Query contains invalid characters",
+            ERROR_INVALID_QUERY,
+        )));
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::{self, File};
+    use std::io::Write;
+    use std::time::Instant;
+
+    use tempfile::TempDir;
+
+    use super::*;
+
+    fn create_test_file(dir: &TempDir, name: &str, content: &str) -> PathBuf {
+        let path = dir.path().join(name);
+        let mut file = File::create(&path).unwrap();
+        file.write_all(content.as_bytes()).unwrap();
+        path
+    }
+
+    #[test]
+    fn test_engine_creation() {
+        let _engine = SearchEngine::new();
+    }
+
+    #[test]
+    fn test_add_and_search_document() {
+        let mut engine = SearchEngine::new();
+        let temp_dir = TempDir::new().unwrap();
+        let path = create_test_file(
+            &temp_dir,
+            "test.txt",
+            "This is a test document with some unique content",
+        );
+
+        let result = engine.add_document(&path, "This is a test document with some unique content");
+        assert!(result.is_ok());
+
+        let results = engine.search("unique content").unwrap();
+        assert!(!results.is_empty());
+        assert_eq!(results[0].path, path);
+    }
+
+    #[test]
+    fn test_search_prefix() {
+        let mut engine = SearchEngine::new();
+        for path in [
+            "src/main.rs",
+            "src/map/mod.rs",
+            "src/snapfind/search.rs",
+            "README.md",
+        ] {
+            engine.add_document(Path::new(path), "content").unwrap();
+        }
+
+        let results = engine.search_prefix("src/ma").unwrap();
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0].path, Path::new("src/main.rs"));
+        assert_eq!(results[1].path, Path::new("src/map/mod.rs"));
+        assert!(results.iter().all(|r| r.score == PARTIAL_COMPONENT_SCORE));
+
+        let results = engine.search_prefix("src/map").unwrap();
+        assert_eq!(results.len(), 1);
+        assert!((results[0].score - 100.0).abs() < f32::EPSILON);
+
+        let results = engine.search_prefix("src/").unwrap();
+        assert_eq!(results.len(), 3);
+        assert!(
+            results
+                .windows(2)
+                .all(|w| { w[0].path.as_os_str().len() <= w[1].path.as_os_str().len() })
+        );
+        assert!(
+            results
+                .iter()
+                .all(|r| (r.score - 100.0).abs() < f32::EPSILON)
+        );
+
+        // Raw prefixes bypass query validation
+        assert!(engine.search_prefix("").unwrap().len() == 4);
+        assert!(engine.search_prefix("lib/").unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_search_no_results() {
+        let engine = SearchEngine::new();
+        let results = engine.search("nonexistent").unwrap();
+        assert!(results.is_empty());
+    }
+
+    #[test]
+    fn test_multiple_documents() {
+        let mut engine = SearchEngine::new();
+        let temp_dir = TempDir::new().unwrap();
+
+        for i in 0..5 {
+            let path = create_test_file(
+                &temp_dir,
+                &format!("test_{i}.txt"),
+                &format!("Document {i} with content"),
+            );
+            engine
+                .add_document(&path, &format!("Document {i} with content"))
+                .unwrap();
+        }
+
+        let results = engine.search("Document").unwrap();
+        assert_eq!(results.len(), 5);
+    }
+
+    #[test]
+    fn test_result_limit() {
+        let mut engine = SearchEngine::new();
+        let temp_dir = TempDir::new().unwrap();
+
+        for i in 0..MAX_DOCUMENTS {
+            let path = create_test_file(&temp_dir, &format!("doc_{i}.txt"), "common content");
+            engine.add_document(&path, "common content").unwrap();
+        }
+
+        let results = engine.search("common").unwrap();
+        assert_eq!(results.len(), MAX_DOCUMENTS);
+
+        let path = create_test_file(&temp_dir, "one_too_many.txt", "common content");
+        assert!(engine.add_document(&path, "common content").is_err());
+    }
+
+    #[test]
+    fn test_result_ranking() {
+        let mut engine = SearchEngine::new();
+        let temp_dir = TempDir::new().unwrap();
+
+        let path1 = create_test_file(&temp_dir, "rust_guide.txt", "rust programming guide");
+        let path2 = create_test_file(&temp_dir, "other.txt", "rust content");
+
+        engine
+            .add_document(&path1, "rust programming guide")
+            .unwrap();
+        engine.add_document(&path2, "rust content").unwrap();
+
+        let results = engine.search("rust").unwrap();
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0].path, path1);
+        assert!(results[0].score > results[1].score);
+    }
+
+    #[test]
+    fn test_filename_match() {
+        let mut engine = SearchEngine::new();
+        let temp_dir = TempDir::new().unwrap();
+        let path = create_test_file(&temp_dir, "important_doc.txt", "Some content");
+
+        engine.add_document(&path, "Some content").unwrap();
+        let results = engine.search("important").unwrap();
+        assert!(!results.is_empty());
+        assert_eq!(results[0].path, path);
+    }
+
+    #[test]
+    fn test_case_insensitive() {
+        let mut engine = SearchEngine::new();
+        let temp_dir = TempDir::new().unwrap();
+        let path = create_test_file(&temp_dir, "TEST.txt", "UPPERCASE CONTENT");
+
+        engine.add_document(&path, "UPPERCASE CONTENT").unwrap();
+
+        let results = engine.search("uppercase").unwrap();
+        assert!(!results.is_empty());
+        let results2 = engine.search("UPPERCASE").unwrap();
+        assert!(!results2.is_empty());
+    }
+
+    #[test]
+    fn test_content_too_large() {
+        let mut engine = SearchEngine::new();
+        let temp_dir = TempDir::new().unwrap();
+        let large_content = "x".repeat(MAX_CONTENT_LENGTH + 1);
+        let path = create_test_file(&temp_dir, "large.txt", &large_content);
+        assert!(engine.add_document(&path, &large_content).is_err());
+    }
+
+    #[test]
+    fn test_configurable_content_limit() {
+        let temp_dir = TempDir::new().unwrap();
+        let index_path = temp_dir.path().join("large.idx");
+        let content = format!("{} needle", "filler text ".repeat(250));
+        assert!(content.len() > MAX_CONTENT_LENGTH);
+
+        let err = SearchEngine::new()
+            .add_document(Path::new("big.txt"), &content)
+            .unwrap_err();
+        assert!(err.to_string().contains("limit is 1000"));
+
+        let mut engine = SearchEngine::builder()
+            .max_content_length(4096)
+            .unwrap()
+            .build();
+        engine.add_document(Path::new("big.txt"), &content).unwrap();
+        engine.save(&index_path).unwrap();
+
+        let loaded = SearchEngine::load(&index_path).unwrap();
+        assert_eq!(loaded.max_content_length(), 4096);
+        assert_eq!(loaded.documents[0].content, content.as_bytes());
+        assert_eq!(loaded.search("needle").unwrap().len(), 1);
+
+        assert!(SearchEngine::builder().max_content_length(0).is_err());
+        assert!(
+            SearchEngine::builder()
+                .max_content_length(MAX_CONTENT_LIMIT + 1)
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_add_document_truncating() {
+        let mut engine = SearchEngine::builder()
+            .max_content_length(8)
+            .unwrap()
+            .build();
+
+        let outcome = engine
+            .add_document_truncating(Path::new("short.txt"), "fits")
+            .unwrap();
+        assert_eq!(outcome, AddOutcome::Stored);
+
+        // The cut lands inside the two-byte 'é', so it backs off to a boundary
+        let outcome = engine
+            .add_document_truncating(Path::new("long.txt"), "abcdefgé and more")
+            .unwrap();
+        assert_eq!(
+            outcome,
+            AddOutcome::Truncated {
+                original_len: 18,
+                stored_len: 7
+            }
+        );
+        assert_eq!(engine.documents[1].content, b"abcdefg");
+
+        assert!(
+            engine
+                .add_document(Path::new("strict.txt"), "abcdefgé and more")
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_load_v3_index() {
+        let temp_dir = TempDir::new().unwrap();
+        let index_path = temp_dir.path().join("v3.idx");
+
+        let mut payload = Vec::new();
+        payload.extend_from_slice(&5_u16.to_le_bytes());
+        payload.extend_from_slice(b"a.txt");
+        payload.push(CONTENT_RAW);
+        payload.extend_from_slice(&11_u16.to_le_bytes());
+        payload.extend_from_slice(b"hello world");
+
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(&MAGIC);
+        bytes.push(VERSION_V3);
+        bytes.extend_from_slice(&1_u32.to_le_bytes());
+        bytes.extend_from_slice(&crc32(&payload).to_le_bytes());
+        bytes.extend_from_slice(&payload);
+        std::fs::write(&index_path, bytes).unwrap();
+
+        let loaded = SearchEngine::load(&index_path).unwrap();
+        assert_eq!(loaded.max_content_length(), MAX_CONTENT_LENGTH);
+        assert_eq!(loaded.search("hello").unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_multiple_term_scoring() {
+        let mut engine = SearchEngine::new();
+        let temp_dir = TempDir::new().unwrap();
+
+        let path1 = create_test_file(&temp_dir, "test1.txt", "rust programming guide");
+        let path2 = create_test_file(&temp_dir, "rust_book.txt", "some other content");
+
+        engine
+            .add_document(&path1, "rust programming guide")
+            .unwrap();
+        engine.add_document(&path2, "some other content").unwrap();
+
+        let results = engine.search("rust").unwrap();
+        assert_eq!(results.len(), 2);
+        assert!(results[0].score > results[1].score);
+    }
+
+    #[test]
+    fn test_stop_word_list() {
+        let list = StopWordList::english();
+        assert!(list.contains(b"the"));
+        assert!(list.contains(b"The"));
+        assert!(list.contains(b"OF"));
+        assert!(!list.contains(b"theory"));
+        assert!(!list.contains(b""));
+        assert!(list.len() <= MAX_STOP_WORDS);
+
+        let mut custom = StopWordList::new();
+        assert!(custom.is_empty());
+        custom.insert("foo").unwrap();
+        custom.insert("foo").unwrap();
+        assert_eq!(custom.len(), 1);
+        assert!(custom.insert("Bar").is_err());
+    }
+
+    #[test]
+    fn test_stop_words_skipped_in_scoring() {
+        let temp_dir = TempDir::new().unwrap();
+        let path1 = create_test_file(&temp_dir, "the_notes.txt", "rust notes");
+        let path2 = create_test_file(&temp_dir, "guide.txt", "a guide to rust");
+
+        let mut engine = SearchEngine::new().with_stop_words(StopWordList::new());
+        engine.add_document(&path1, "rust notes").unwrap();
+        engine.add_document(&path2, "a guide to rust").unwrap();
+        let results = engine.search("the rust").unwrap();
+        assert!(results[0].score > results[1].score);
+
+        let engine = engine.with_stop_words(StopWordList::english());
+        let results = engine.search("the rust").unwrap();
+        assert_eq!(results.len(), 2);
+        assert!((results[0].score - results[1].score).abs() < f32::EPSILON);
+    }
+
+    #[test]
+    fn test_english_stop_words_by_default() {
+        let mut engine = SearchEngine::new();
+        engine
+            .add_document(
+                Path::new("notes/to_the_point.txt"),
+                "how to get to the top of the list",
+            )
+            .unwrap();
+        engine
+            .add_document(Path::new("docs/server.md"), "configure the server port")
+            .unwrap();
+
+        let query = "how to configure the server";
+        let results = engine.search(query).unwrap();
+        assert_eq!(results[0].path, Path::new("docs/server.md"));
+        let best = results[0].score;
+        let other = results
+            .iter()
+            .find(|r| r.path != Path::new("docs/server.md"));
+        assert!(other.is_none_or(|r| r.score < best / 2.0));
+
+        // Without stop words the function words lift the unrelated note
+        let engine = engine.with_options(SearchOptions {
+            use_stop_words: false,
+            ..SearchOptions::default()
+        });
+        let results = engine.search(query).unwrap();
+        assert_eq!(results.len(), 2);
+        let note = results
+            .iter()
+            .find(|r| r.path == Path::new("notes/to_the_point.txt"))
+            .unwrap();
+        assert!(note.score >= best / 2.0);
+    }
+
+    #[test]
+    fn test_all_stop_word_query_keeps_its_terms() {
+        let mut engine = SearchEngine::new();
+        engine
+            .add_document(Path::new("song.txt"), "to be or not to be")
+            .unwrap();
+        engine
+            .add_document(Path::new("other.txt"), "unrelated words")
+            .unwrap();
+
+        let results = engine.search("to be").unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].path, Path::new("song.txt"));
+        let location = engine.locate("to be", Path::new("song.txt"));
+        assert_eq!(location, Some(MatchLocation { line: 1, column: 1 }));
+    }
+
+    #[cfg(feature = "unicode")]
+    #[test]
+    fn test_term_matches_normalizes_unicode() {
+        let composed = "caf\u{00e9}";
+        let decomposed = "cafe\u{0301}";
+        assert!(SearchEngine::term_matches(
+            composed.as_bytes(),
+            decomposed.as_bytes()
+        ));
+        assert!(SearchEngine::term_matches(
+            decomposed.as_bytes(),
+            composed.as_bytes()
+        ));
+        assert!(SearchEngine::term_matches(
+            "Cafe\u{0301}".as_bytes(),
+            "menu: caf\u{00e9} au lait".as_bytes()
+        ));
+
+        let mut engine = SearchEngine::new();
+        engine
+            .add_document(Path::new("notes/cafe\u{0301}.md"), "opening hours")
+            .unwrap();
+        let (path, _) = engine.documents().next().unwrap();
+        assert_eq!(path, Path::new("notes/caf\u{00e9}.md"));
+
+        for query in [composed, decomposed] {
+            let results = engine.search(query).unwrap();
+            assert_eq!(results.len(), 1, "query {query:?} should match");
+        }
+    }
+
+    #[cfg(feature = "unicode")]
+    #[test]
+    fn test_term_matches_unicode_boundaries() {
+        let matches = |term: &str, content: &str| {
+            SearchEngine::term_matches(term.as_bytes(), content.as_bytes())
+        };
+
+        assert!(matches("CAF\u{00c9}", "menu: caf\u{00e9} au lait"));
+        assert!(matches("\u{00fc}ber", "\u{00dc}ber alles"));
+        assert!(!matches("caf", "caf\u{00e9}"));
+        assert!(!matches("\u{00e9}t\u{00e9}", "pr\u{00e9}t\u{00e9}s"));
+
+        assert!(matches(
+            "\u{65e5}\u{672c}\u{8a9e}",
+            "\u{65e5}\u{672c}\u{8a9e}\u{3002}"
+        ));
+        assert!(matches("\u{65e5}\u{672c}", "\u{65e5}\u{672c} \u{8a9e}"));
+        assert!(!matches("\u{65e5}\u{672c}", "\u{65e5}\u{672c}\u{8a9e}"));
+        assert!(!matches("\u{672c}", "\u{65e5}\u{672c}\u{8a9e}"));
+
+        let mut engine = SearchEngine::new();
+        engine
+            .add_document(
+                Path::new("jp.txt"),
+                "\u{65e5}\u{672c}\u{8a9e} \u{306e}\u{30c6}\u{30ad}\u{30b9}\u{30c8}",
+            )
+            .unwrap();
+        engine
+            .add_document(Path::new("fr.txt"), "Le caf\u{00e9} est ferm\u{00e9}")
+            .unwrap();
+        let results = engine.search("\u{65e5}\u{672c}\u{8a9e}").unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].path, Path::new("jp.txt"));
+        let results = engine.search("CAF\u{00c9}").unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].path, Path::new("fr.txt"));
+        assert!(engine.search("ferm").unwrap().is_empty());
+    }
+
+    #[cfg(not(feature = "unicode"))]
+    #[test]
+    fn test_term_matches_is_byte_exact_without_unicode() {
+        assert!(!SearchEngine::term_matches(
+            "caf\u{00e9}".as_bytes(),
+            "cafe\u{0301}".as_bytes()
+        ));
+    }
+
+    #[test]
+    fn test_term_prefix_matches() {
+        assert!(SearchEngine::term_prefix_matches(b"rust", b"rustc"));
+        assert!(SearchEngine::term_prefix_matches(b"RUST", b"use rustfmt;"));
+        assert!(SearchEngine::term_prefix_matches(
+            b"src/main",
+            b"/repo/src/main.rs"
+        ));
+        assert!(!SearchEngine::term_prefix_matches(b"rust", b"trusted"));
+        assert!(!SearchEngine::term_prefix_matches(b"", b"rust"));
+        assert!(!SearchEngine::term_prefix_matches(b"rustup", b"rust"));
+    }
+
+    #[test]
+    fn test_text_mode_treats_asterisk_literally() {
+        let mut engine = SearchEngine::new();
+        engine
+            .add_document(Path::new("src/ops.rs"), "impl operator overloading")
+            .unwrap();
+        engine
+            .add_document(Path::new("docs/guide.md"), "see the guide")
+            .unwrap();
+
+        assert_eq!(engine.mode(), SearchMode::Text);
+        let results = engine.search("operator*").unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].path, Path::new("src/ops.rs"));
+
+        let results = engine.search("*guide*").unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].path, Path::new("docs/guide.md"));
+
+        assert!(engine.search("*").unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_glob_mode_matches_paths_only() {
+        let mut engine = SearchEngine::builder().mode(SearchMode::Glob).build();
+        engine
+            .add_document(Path::new("docs/guide.md"), "markdown notes")
+            .unwrap();
+        engine
+            .add_document(Path::new("src/main.rs"), "mentions guide.md")
+            .unwrap();
+
+        let results = engine.search("*.md").unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].path, Path::new("docs/guide.md"));
+        assert!((results[0].score - 100.0).abs() < f32::EPSILON);
+
+        let engine = engine.with_mode(SearchMode::Text);
+        let results = engine.search("guide.md").unwrap();
+        assert_eq!(results.len(), 2);
+    }
+
+    #[test]
+    fn test_glob_literal_separator() {
+        let paths = [
+            "a.txt",
+            "src/b.txt",
+            "src/nested/c.txt",
+            "docs/src/d.txt",
+            "docs/e.md",
+        ];
+        let matching = |engine: &SearchEngine, query: &str| -> Vec<String> {
+            let mut found: Vec<_> = engine
+                .search(query)
+                .unwrap()
+                .into_iter()
+                .map(|r| r.path.to_string_lossy().into_owned())
+                .collect();
+            found.sort();
+            found
+        };
+
+        let mut engine = SearchEngine::builder().mode(SearchMode::Glob).build();
+        let mut legacy = SearchEngine::builder()
+            .mode(SearchMode::Glob)
+            .literal_separator(false)
+            .build();
+        for path in paths {
+            engine.add_document(Path::new(path), "").unwrap();
+            legacy.add_document(Path::new(path), "").unwrap();
+        }
+
+        let all_txt = vec!["a.txt", "docs/src/d.txt", "src/b.txt", "src/nested/c.txt"];
+        assert_eq!(matching(&engine, "*.txt"), all_txt);
+        assert_eq!(matching(&engine, "**/*.txt"), all_txt);
+        assert_eq!(matching(&engine, "src/*.txt"), vec!["src/b.txt"]);
+        assert_eq!(
+            matching(&engine, "src/**/*.txt"),
+            vec!["src/b.txt", "src/nested/c.txt"]
+        );
+        assert_eq!(matching(&engine, "/a.txt"), vec!["a.txt"]);
+        assert_eq!(matching(&engine, "c.txt"), vec!["src/nested/c.txt"]);
+
+        assert_eq!(
+            matching(&legacy, "src/*.txt"),
+            vec!["docs/src/d.txt", "src/b.txt", "src/nested/c.txt"]
+        );
+    }
+
+    #[test]
+    fn test_glob_negation() {
+        let mut engine = SearchEngine::builder().mode(SearchMode::Glob).build();
+        for path in [
+            "app.js",
+            "app.min.js",
+            "lib/util.js",
+            "lib/util.min.js",
+            "notes.txt",
+        ] {
+            engine.add_document(Path::new(path), "").unwrap();
+        }
+        let matching = |query: &str| -> Vec<String> {
+            let mut found: Vec<_> = engine
+                .search(query)
+                .unwrap()
+                .into_iter()
+                .map(|r| {
+                    assert!((r.score - 100.0).abs() < f32::EPSILON);
+                    r.path.to_string_lossy().into_owned()
+                })
+                .collect();
+            found.sort();
+            found
+        };
+
+        assert_eq!(matching("*.js !*.min.js"), vec!["app.js", "lib/util.js"]);
+        assert_eq!(matching("!*.min.js *.js"), vec!["app.js", "lib/util.js"]);
+        assert_eq!(matching("*.js !lib/*"), vec!["app.js", "app.min.js"]);
+        assert_eq!(
+            matching("!*.min.js"),
+            vec!["app.js", "lib/util.js", "notes.txt"]
+        );
+        assert!(matching("!*.min.js !*.js !*.txt").is_empty());
+
+        let err = engine.search("*.js !").unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<SnapError>().unwrap().code(),
+            ERROR_INVALID_QUERY
+        );
+    }
+
+    #[test]
+    fn test_glob_matches_legacy_absolute_paths() {
+        let mut engine = SearchEngine::builder().mode(SearchMode::Glob).build();
+        engine
+            .add_document(Path::new("/home/user/notes/todo.txt"), "")
+            .unwrap();
+
+        assert_eq!(engine.search("*.txt").unwrap().len(), 1);
+        assert_eq!(engine.search("notes/*.txt").unwrap().len(), 0);
+        assert_eq!(engine.search("**/notes/*.txt").unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_inverted_index_matches_linear_scan() {
+        let mut indexed = SearchEngine::new();
+        let mut linear = SearchEngine::builder().inverted_index(false).build();
+        let docs = [
+            ("src/main.rs", "fn main() { println!(\"Hello\"); }"),
+            ("src/lib.rs", "pub mod search;\npub mod main_loop;"),
+            ("docs/README.md", "# Introduction\nRun the main binary"),
+            ("notes/todo.txt", "remember the Rustacean meetup"),
+        ];
+        for (path, content) in docs {
+            indexed.add_document(Path::new(path), content).unwrap();
+            linear.add_document(Path::new(path), content).unwrap();
+        }
+
+        for query in [
+            "main",
+            "MAIN hello",
+            "lib",
+            "# Introduction",
+            "rust*",
+            "absent",
+        ] {
+            let expected = linear.search(query).unwrap();
+            let actual = indexed.search(query).unwrap();
+            assert_eq!(actual.len(), expected.len(), "query: {query}");
+            for (a, e) in actual.iter().zip(expected.iter()) {
+                assert_eq!(a.path, e.path, "query: {query}");
+                assert!((a.score - e.score).abs() < f32::EPSILON, "query: {query}");
+            }
+        }
+    }
+
+    #[test]
+    fn test_doc_freq_from_inverted_index_matches_scan() {
+        let mut indexed = SearchEngine::new();
+        let mut linear = SearchEngine::builder().inverted_index(false).build();
+        let docs = [
+            ("src/main.rs", "fn main() { deploy(); }"),
+            ("src/deploy.rs", "pub fn deploy() {}"),
+            ("notes/todo.txt", "Deploy on Friday, then deploy again"),
+            ("notes/ideas.txt", "nothing here"),
+        ];
+        for (path, content) in docs {
+            indexed.add_document(Path::new(path), content).unwrap();
+            linear.add_document(Path::new(path), content).unwrap();
+        }
+
+        // "src" and "notes" only appear in paths, which df does not count
+        for query in ["deploy", "DEPLOY main", "src notes", "fri*", "absent"] {
+            assert_eq!(
+                indexed.term_stats(query).doc_freq,
+                linear.term_stats(query).doc_freq,
+                "query: {query}"
+            );
+        }
+        assert_eq!(indexed.indexed_doc_freq(b"deploy"), Some(3));
+        assert_eq!(indexed.indexed_doc_freq(b"src"), Some(0));
+        assert_eq!(indexed.indexed_doc_freq(b"fri*"), None);
+        assert_eq!(linear.indexed_doc_freq(b"deploy"), None);
+
+        let fuzzy = SearchEngine::new().with_options(SearchOptions::fuzzy());
+        assert_eq!(fuzzy.indexed_doc_freq(b"deploy"), None);
+    }
+
+    #[test]
+    fn test_inverted_index_serves_realistic_corpus() {
+        let mut engine = SearchEngine::new();
+        for i in 0..MAX_DOCUMENTS {
+            // Mostly distinct words, as real files have
+            let content: String = (0..90).map(|w| format!("term{i}n{w} ")).collect();
+            engine
+                .add_document(Path::new(&format!("doc{i}.txt")), &content)
+                .unwrap();
+        }
+
+        let inverted = engine.inverted.as_ref().unwrap();
+        assert!(inverted.len() > 1024);
+        assert!(inverted.is_complete());
+        let candidates = engine.candidate_documents("term42n3").unwrap();
+        assert_eq!(candidates.iter().filter(|&&c| c).count(), 1);
+        assert!(candidates[42]);
+
+        let results = engine.search("term42n3").unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].path, Path::new("doc42.txt"));
+    }
+
+    #[test]
+    fn test_search_iter() {
+        let mut engine = SearchEngine::new();
+        engine
+            .add_document(Path::new("a.txt"), "rust rust guide")
+            .unwrap();
+        engine
+            .add_document(Path::new("rust.txt"), "rust guide")
+            .unwrap();
+        engine
+            .add_document(Path::new("c.txt"), "unrelated")
+            .unwrap();
+
+        let all = engine.search("rust").unwrap();
+        let top: Vec<_> = engine.search_iter("rust").unwrap().take(1).collect();
+        assert_eq!(top.len(), 1);
+        assert_eq!(top[0].path, all[0].path);
+        assert_eq!(top[0].path, Path::new("rust.txt"));
+
+        assert_eq!(engine.search_iter("rust").unwrap().count(), all.len());
+        assert!(engine.search_iter("").is_err());
+    }
+
+    #[test]
+    fn test_explain_score() {
+        let mut engine = SearchEngine::new().with_stop_words(StopWordList::english());
+        engine
+            .add_document(Path::new("src/main.rs"), "fn main() { parse(args) }")
+            .unwrap();
+        engine
+            .add_document(Path::new("docs/guide.md"), "nothing relevant")
+            .unwrap();
+
+        let explanation = engine
+            .explain_score("the main parse", Path::new("src/main.rs"))
+            .unwrap();
+        let score = engine.search("the main parse").unwrap()[0].score;
+        assert!((explanation.total_score - score).abs() < f32::EPSILON);
+
+        let terms = &explanation.term_scores;
+        assert_eq!(terms.len(), 2);
+        assert_eq!(terms[0].term.as_str(), "main");
+        assert!(terms[0].path_hit && terms[0].content_hit);
+        assert_eq!(terms[1].term.as_str(), "parse");
+        assert!(!terms[1].path_hit && terms[1].content_hit);
+        let total: f32 = terms.iter().map(|t| t.contribution).sum();
+        assert!((total - explanation.total_score).abs() < 0.01);
+
+        let miss = engine
+            .explain_score("main", Path::new("docs/guide.md"))
+            .unwrap();
+        assert!(miss.total_score.abs() < f32::EPSILON);
+        assert!(!miss.term_scores[0].path_hit && !miss.term_scores[0].content_hit);
+
+        let err = engine
+            .explain_score("main", Path::new("missing.rs"))
+            .unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<SnapError>().unwrap().code(),
+            ERROR_DOCUMENT_NOT_FOUND
+        );
+    }
+
+    #[test]
+    fn test_document_accessors() {
+        let mut engine = SearchEngine::new();
+        assert_eq!(engine.document_count(), 0);
+        assert!(engine.is_empty());
+        assert_eq!(engine.documents().count(), 0);
+
+        engine.add_document(Path::new("a.txt"), "abc").unwrap();
+        engine
+            .add_document(Path::new("dir/b.txt"), "hello world")
+            .unwrap();
+
+        assert_eq!(engine.document_count(), 2);
+        let lengths: Vec<_> = engine.document_lengths().collect();
+        assert_eq!(
+            lengths,
+            vec![(Path::new("a.txt"), 3), (Path::new("dir/b.txt"), 11)]
+        );
+
+        assert_eq!(engine.document_count(), 2);
+        assert!(!engine.is_empty());
+        let documents: Vec<_> = engine.documents().collect();
+        assert_eq!(
+            documents,
+            vec![
+                (Path::new("a.txt"), b"abc".as_slice()),
+                (Path::new("dir/b.txt"), b"hello world".as_slice()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_retain() {
+        let mut engine = SearchEngine::new();
+        let docs = [
+            ("old/report.txt", "quarterly budget"),
+            ("keep/plan.txt", "budget plan"),
+            ("old/notes.txt", "meeting notes"),
+            ("keep/todo.txt", "meeting agenda"),
+        ];
+        for (path, content) in docs {
+            engine.add_document(Path::new(path), content).unwrap();
+        }
+
+        engine.retain(|doc| doc.path.starts_with("keep"));
+        assert_eq!(engine.document_count(), 2);
+
+        let results = engine.search("budget").unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].path, Path::new("keep/plan.txt"));
+        let results = engine.search("meeting").unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].path, Path::new("keep/todo.txt"));
+        assert!(engine.search("quarterly").unwrap().is_empty());
+
+        engine.retain(|_| true);
+        assert_eq!(engine.document_count(), 2);
+        engine
+            .add_document(Path::new("new/budget.txt"), "fresh")
+            .unwrap();
+        assert_eq!(engine.search("budget").unwrap().len(), 2);
+
+        engine.retain(|_| false);
+        assert!(engine.is_empty());
+        assert!(engine.search("budget").unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_document_lookup() {
+        let mut engine = SearchEngine::new();
+        engine
+            .add_document(Path::new("src/main.rs"), "fn main")
+            .unwrap();
+        engine
+            .add_document(Path::new("notes.txt"), "groceries")
+            .unwrap();
+
+        assert!(engine.contains_document(Path::new("src/main.rs")));
+        assert!(engine.contains_document(Path::new("src//main.rs")));
+        assert!(!engine.contains_document(Path::new("main.rs")));
+        let doc = engine.get_document(Path::new("notes.txt")).unwrap();
+        assert_eq!(doc.content, b"groceries");
+        assert!(engine.get_document(Path::new("missing.txt")).is_none());
+
+        let doc = engine.get_document_mut(Path::new("notes.txt")).unwrap();
+        doc.content = b"hardware store".to_vec();
+        let results = engine.search("hardware").unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].path, Path::new("notes.txt"));
+        assert!(engine.search("groceries").unwrap().is_empty());
+        assert!(engine.get_document_mut(Path::new("missing.txt")).is_none());
+    }
+
+    #[test]
+    fn test_remove_and_update_document() {
+        let mut engine = SearchEngine::new();
+        for (path, content) in [("a.txt", "alpha"), ("b.txt", "beta"), ("c.txt", "gamma")] {
+            engine.add_document(Path::new(path), content).unwrap();
+        }
+
+        assert!(engine.remove_document(Path::new("b.txt")));
+        let paths: Vec<_> = engine.documents().map(|(path, _)| path).collect();
+        assert_eq!(paths, [Path::new("a.txt"), Path::new("c.txt")]);
+        assert!(engine.search("beta").unwrap().is_empty());
+
+        engine.update_document(Path::new("a.txt"), "delta").unwrap();
+        assert!(engine.search("alpha").unwrap().is_empty());
+        assert_eq!(engine.search("delta").unwrap()[0].path, Path::new("a.txt"));
+        let paths: Vec<_> = engine.documents().map(|(path, _)| path).collect();
+        assert_eq!(paths, [Path::new("a.txt"), Path::new("c.txt")]);
+
+        let outcome = engine
+            .update_document_truncating(Path::new("d.txt"), &"x".repeat(MAX_CONTENT_LENGTH + 1))
+            .unwrap();
+        assert_eq!(
+            outcome,
+            AddOutcome::Truncated {
+                original_len: MAX_CONTENT_LENGTH + 1,
+                stored_len: MAX_CONTENT_LENGTH
+            }
+        );
+        assert_eq!(engine.document_count(), 3);
+    }
+
+    #[test]
+    fn test_index_stats() {
+        let mut engine = SearchEngine::new();
+        let stats = engine.stats();
+        assert_eq!(stats.document_count, 0);
+        assert_eq!(stats.total_content_bytes, 0);
+        assert!(stats.avg_content_bytes.abs() < f32::EPSILON);
+        assert_eq!(stats.largest_document, None);
+        assert_eq!(stats.index_version, VERSION);
+
+        for (path, content) in [("a.txt", "12"), ("b.txt", "1234"), ("c.txt", "1234")] {
+            engine.add_document(Path::new(path), content).unwrap();
+        }
+        let stats = engine.stats();
+        assert_eq!(stats.document_count, 3);
+        assert_eq!(stats.total_content_bytes, 10);
+        assert!((stats.avg_content_bytes - 10.0 / 3.0).abs() < 1e-6);
+        assert_eq!(stats.largest_document.as_deref(), Some(Path::new("b.txt")));
+        assert!(!stats.is_near_capacity());
+
+        for i in 3..=MAX_DOCUMENTS * 80 / 100 {
+            engine
+                .add_document(Path::new(&format!("{i}.txt")), "x")
+                .unwrap();
+        }
+        assert!(engine.stats().is_near_capacity());
+    }
+
+    #[test]
+    fn test_remove_missing_document() {
+        let mut engine = SearchEngine::new();
+        assert!(!engine.remove_document(Path::new("missing.txt")));
+
+        engine.add_document(Path::new("a.txt"), "alpha").unwrap();
+        assert!(!engine.remove_document(Path::new("missing.txt")));
+        assert!(!engine.remove_document(Path::new("a")));
+        assert_eq!(engine.document_count(), 1);
+        assert!(engine.remove_document(Path::new("a.txt")));
+        assert!(!engine.remove_document(Path::new("a.txt")));
+        assert!(engine.is_empty());
+    }
+
+    #[test]
+    fn test_update_document_at_capacity() {
+        let mut engine = SearchEngine::new();
+        for i in 0..MAX_DOCUMENTS {
+            engine
+                .add_document(Path::new(&format!("doc{i}.txt")), "old")
+                .unwrap();
+        }
+
+        engine
+            .update_document(Path::new("doc7.txt"), "fresh")
+            .unwrap();
+        assert_eq!(engine.document_count(), MAX_DOCUMENTS);
+        let results = engine.search("fresh").unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].path, Path::new("doc7.txt"));
+
+        let err = engine
+            .update_document(Path::new("new.txt"), "fresh")
+            .unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<SnapError>().unwrap().code(),
+            ERROR_TOO_MANY_DOCUMENTS
+        );
+        assert!(!engine.contains_document(Path::new("new.txt")));
+
+        let err = engine
+            .update_document(Path::new("doc7.txt"), &"x".repeat(MAX_CONTENT_LENGTH + 1))
+            .unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<SnapError>().unwrap().code(),
+            ERROR_CONTENT_TOO_LARGE
+        );
+        let doc = engine.get_document(Path::new("doc7.txt")).unwrap();
+        assert_eq!(doc.content, b"fresh");
+
+        assert!(engine.remove_document(Path::new("doc0.txt")));
+        engine
+            .update_document(Path::new("new.txt"), "fresh")
+            .unwrap();
+        let paths: Vec<_> = engine.documents().map(|(path, _)| path).collect();
+        assert_eq!(paths[0], Path::new("doc1.txt"));
+        assert_eq!(paths[MAX_DOCUMENTS - 1], Path::new("new.txt"));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serde_round_trip() {
+        let result = SearchResult {
+            path: PathBuf::from("docs/guide.md"),
+            score: 42.5,
+        };
+        let json = serde_json::to_string(&result).unwrap();
+        assert_eq!(json, r#"{"path":"docs/guide.md","score":42.5}"#);
+        let parsed: SearchResult = serde_json::from_str(&json).unwrap();
+        assert_eq!(parsed, result);
+
+        let doc = Document {
+            path: PathBuf::from("bin.dat"),
+            content: vec![0x00, 0xff, b'a'],
+            stamp: None,
+        };
+        let json = serde_json::to_string(&doc).unwrap();
+        assert_eq!(json, r#"{"path":"bin.dat","content":"00ff61"}"#);
+        let parsed: Document = serde_json::from_str(&json).unwrap();
+        assert_eq!(parsed.path, doc.path);
+        assert_eq!(parsed.content, doc.content);
+        assert!(serde_json::from_str::<Document>(r#"{"path":"a","content":"0"}"#).is_err());
+        assert!(serde_json::from_str::<Document>(r#"{"path":"a","content":"zz"}"#).is_err());
+
+        let mut engine = SearchEngine::new();
+        engine.add_document(Path::new("a.txt"), "alpha").unwrap();
+        let stats = engine.stats();
+        let json = serde_json::to_string(&stats).unwrap();
+        assert_eq!(serde_json::from_str::<IndexStats>(&json).unwrap(), stats);
+    }
+
+    #[test]
+    fn test_search_each() {
+        let mut engine = SearchEngine::new();
+        for i in 0..10 {
+            let content = if i % 3 == 0 { "todo later" } else { "done" };
+            engine
+                .add_document(Path::new(&format!("note{i}.txt")), content)
+                .unwrap();
+        }
+
+        let mut seen = Vec::new();
+        engine
+            .search_each("todo", |result| {
+                seen.push(result);
+                ControlFlow::Continue(())
+            })
+            .unwrap();
+        let paths: Vec<_> = seen.iter().map(|r| r.path.clone()).collect();
+        assert_eq!(
+            paths,
+            ["note0.txt", "note3.txt", "note6.txt", "note9.txt"].map(PathBuf::from)
+        );
+        let sorted = engine.search("todo").unwrap();
+        assert!(seen.iter().all(|r| sorted.contains(r)));
+
+        let mut calls = 0;
+        engine
+            .search_each("todo", |_| {
+                calls += 1;
+                if calls == 2 {
+                    ControlFlow::Break(())
+                } else {
+                    ControlFlow::Continue(())
+                }
+            })
+            .unwrap();
+        assert_eq!(calls, 2);
+
+        assert!(
+            engine
+                .search_each("", |_| ControlFlow::Continue(()))
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_search_page() {
+        let mut engine = SearchEngine::new();
+        for i in 0..7 {
+            let content = format!("todo item {i}");
+            engine
+                .add_document(Path::new(&format!("note{i}.txt")), &content)
+                .unwrap();
+        }
+        engine
+            .add_document(Path::new("other.txt"), "nothing here")
+            .unwrap();
+
+        let all = engine.search("todo").unwrap();
+        let (first, total) = engine.search_page("todo", 0, 3).unwrap();
+        assert_eq!(total, 7);
+        assert_eq!(first.len(), 3);
+        assert_eq!(first[0].path, all[0].path);
+
+        let (last, total) = engine.search_page("todo", 2, 3).unwrap();
+        assert_eq!(total, 7);
+        assert_eq!(last.len(), 1);
+        assert_eq!(last[0].path, all[6].path);
+
+        let (beyond, total) = engine.search_page("todo", 5, 3).unwrap();
+        assert!(beyond.is_empty());
+        assert_eq!(total, 7);
+
+        for (page, size) in [(0, 0), (0, MAX_RESULTS + 1), (MAX_DOCUMENTS * 2, 1)] {
+            let err = engine.search_page("todo", page, size).unwrap_err();
+            assert!(err.to_string().contains("Invalid page parameters"));
+        }
+    }
+
+    #[test]
+    fn test_merge_results() {
+        let result = |path: &str, score| SearchResult {
+            path: PathBuf::from(path),
+            score,
+        };
+        let notes: ArrayVec<_, MAX_RESULTS> =
+            [result("todo.md", 40.0), result("sub/plan.md", 90.0)]
+                .into_iter()
+                .collect();
+        let sub: ArrayVec<_, MAX_RESULTS> = [result("plan.md", 70.0), result("other.md", 60.0)]
+            .into_iter()
+            .collect();
+        let projects: ArrayVec<_, MAX_RESULTS> = [result("readme.md", 80.0)].into_iter().collect();
+
+        let merged = merge_results(&[
+            (PathBuf::from("notes"), notes),
+            (PathBuf::from("./notes/sub"), sub),
+            (PathBuf::from("projects"), projects),
+        ]);
+
+        let paths: Vec<_> = merged.iter().map(RootedResult::resolved_path).collect();
+        assert_eq!(
+            paths,
+            vec![
+                PathBuf::from("notes/sub/plan.md"),
+                PathBuf::from("projects/readme.md"),
+                PathBuf::from("./notes/sub/other.md"),
+                PathBuf::from("notes/todo.md"),
+            ]
+        );
+        assert_eq!(merged[0].root, Path::new("notes"));
+        assert!((merged[0].result.score - 90.0).abs() < f32::EPSILON);
+        assert!(merge_results(&[]).is_empty());
+    }
+
+    #[test]
+    fn test_merge_results_resolves_paths_on_disk() {
+        let temp_dir = TempDir::new().unwrap();
+        let notes = temp_dir.path().join("notes");
+        fs::create_dir_all(notes.join("sub")).unwrap();
+        fs::create_dir(temp_dir.path().join("other")).unwrap();
+        fs::write(notes.join("sub/plan.md"), "plan").unwrap();
+
+        let result = |path: &str, score| -> ArrayVec<_, MAX_RESULTS> {
+            [SearchResult {
+                path: PathBuf::from(path),
+                score,
+            }]
+            .into_iter()
+            .collect()
+        };
+        let merged = merge_results(&[
+            (notes.clone(), result("sub/plan.md", 50.0)),
+            (
+                temp_dir.path().join("other/../notes/sub"),
+                result("plan.md", 70.0),
+            ),
+        ]);
+        assert_eq!(merged.len(), 1);
+        assert!((merged[0].result.score - 70.0).abs() < f32::EPSILON);
+    }
+
+    #[test]
+    fn test_search_located() {
+        let mut engine = SearchEngine::new();
+        engine
+            .add_document(Path::new("first.txt"), "deploy the service")
+            .unwrap();
+        engine
+            .add_document(Path::new("later.txt"), "intro\nsteps:\n  then deploy it")
+            .unwrap();
+        engine
+            .add_document(Path::new("deploy.md"), "nothing relevant here")
+            .unwrap();
+
+        let results = engine.search_located("deploy").unwrap();
+        assert_eq!(results.len(), 3);
+        let location = |path: &str| {
+            results
+                .iter()
+                .find(|r| r.result.path == Path::new(path))
+                .unwrap()
+                .location
+        };
+        assert_eq!(
+            location("first.txt"),
+            Some(MatchLocation { line: 1, column: 1 })
+        );
+        assert_eq!(
+            location("later.txt"),
+            Some(MatchLocation { line: 3, column: 8 })
+        );
+        assert_eq!(location("deploy.md"), None);
+
+        // The earliest of several matching terms wins
+        assert_eq!(
+            engine.locate("service deploy", Path::new("first.txt")),
+            Some(MatchLocation { line: 1, column: 1 })
+        );
+
+        assert_eq!(
+            engine.locate("deploy", Path::new("later.txt")),
+            Some(MatchLocation { line: 3, column: 8 })
+        );
+        assert_eq!(engine.locate("deploy", Path::new("deploy.md")), None);
+        assert_eq!(engine.locate("deploy", Path::new("missing.txt")), None);
+
+        let engine = engine.with_mode(SearchMode::Glob);
+        assert_eq!(engine.locate("deploy", Path::new("later.txt")), None);
+        let results = engine.search_located("*.txt").unwrap();
+        assert_eq!(results.len(), 2);
+        assert!(results.iter().all(|r| r.location.is_none()));
+    }
+
+    #[test]
+    fn test_path_match_ranges() {
+        let engine = SearchEngine::new();
+        let path = Path::new("src/main.rs");
+        assert_eq!(
+            engine
+                .path_match_ranges("MAIN src missing", path)
+                .as_slice(),
+            &[0..3, 4..8]
+        );
+        assert_eq!(engine.path_match_ranges("ma*", path).first(), Some(&(4..6)));
+        let ranges = engine.path_match_ranges("main ma*", path);
+        assert_eq!(ranges.len(), 1);
+        assert_eq!(ranges[0], 4..8);
+        assert!(engine.path_match_ranges("missing", path).is_empty());
+
+        let engine = SearchEngine::new().with_mode(SearchMode::Glob);
+        assert!(engine.path_match_ranges("src/*.rs", path).is_empty());
+    }
+
+    #[test]
+    fn test_search_explain() {
+        let mut engine = SearchEngine::new().with_stop_words(StopWordList::english());
+        engine
+            .add_document(Path::new("src/main.rs"), "fn main() { parse(args) }")
+            .unwrap();
+        engine
+            .add_document(Path::new("docs/parse.md"), "how the parser works")
+            .unwrap();
+
+        let plain = engine.search("the main parse").unwrap();
+        let explained = engine.search_explain("the main parse").unwrap();
+        assert_eq!(plain.len(), explained.len());
+
+        for (p, e) in plain.iter().zip(explained.iter()) {
+            assert_eq!(p.path, e.result.path);
+            assert!((p.score - e.result.score).abs() < f32::EPSILON);
+            let total: f32 = e.terms.iter().map(|t| t.contribution).sum();
+            assert!((total.min(100.0) - e.result.score).abs() < 0.01);
+        }
+
+        let main_rs = &explained[0];
+        assert_eq!(main_rs.result.path, Path::new("src/main.rs"));
+        assert_eq!(main_rs.terms.len(), 2);
+        assert_eq!(main_rs.terms[0].term_index, 1);
+        assert!(main_rs.terms[0].matched_in_path);
+        assert!(main_rs.terms[0].matched_in_content);
+        assert_eq!(main_rs.terms[1].term_index, 2);
+        assert!(!main_rs.terms[1].matched_in_path);
+        assert!(main_rs.terms[1].matched_in_content);
+    }
+
+    #[test]
+    fn test_term_frequency_ranking() {
+        let mut engine = SearchEngine::new();
+        engine
+            .add_document(
+                Path::new("compose.md"),
+                "docker compose: docker networks, docker volumes, docker images, docker",
+            )
+            .unwrap();
+        engine
+            .add_document(Path::new("README.md"), "we also ship a docker image")
+            .unwrap();
+
+        let results = engine.search("docker").unwrap();
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0].path, Path::new("compose.md"));
+        assert!(results[0].score > results[1].score);
+        assert!(results.iter().all(|r| r.score <= 100.0));
+    }
+
+    #[test]
+    fn test_content_match_outranks_path_substring_match() {
+        let mut engine = SearchEngine::new();
+        engine
+            .add_document(Path::new("docs/readmefirst.txt"), "setup steps")
+            .unwrap();
+        engine
+            .add_document(Path::new("guide.txt"), "keep the readme short")
+            .unwrap();
+
+        // A plain word is not a glob: a path that merely contains it gets no
+        // boost over a document that mentions it
+        let results = engine.search("readme").unwrap();
+        assert_eq!(results[0].path, Path::new("guide.txt"));
+        assert!(results.iter().skip(1).all(|r| r.score < results[0].score));
+    }
+
+    #[test]
+    fn test_common_terms_weigh_less_than_rare_terms() {
+        let mut engine = SearchEngine::new();
+        engine
+            .add_document(Path::new("a.txt"), "project overview")
+            .unwrap();
+        engine
+            .add_document(Path::new("b.txt"), "project roadmap")
+            .unwrap();
+        engine
+            .add_document(Path::new("c.txt"), "project kubernetes notes")
+            .unwrap();
+
+        let explanation = engine
+            .explain_score("project kubernetes", Path::new("c.txt"))
+            .unwrap();
+        let common = explanation.term_scores[0];
+        let rare = explanation.term_scores[1];
+        assert_eq!(common.term.as_str(), "project");
+        assert_eq!(rare.term.as_str(), "kubernetes");
+        assert!(common.content_hit && rare.content_hit);
+        assert!(rare.contribution > common.contribution);
+
+        let results = engine.search("project kubernetes").unwrap();
+        assert_eq!(results[0].path, Path::new("c.txt"));
+    }
+
+    #[test]
+    fn test_prefix_search_ranking() {
+        let mut engine = SearchEngine::new();
+        let exact = PathBuf::from("notes/exact.txt");
+        let prefix = PathBuf::from("notes/prefix.txt");
+        let substring = PathBuf::from("notes/substring.txt");
+        let unrelated = PathBuf::from("notes/unrelated.txt");
+
+        engine.add_document(&exact, "we use rust daily").unwrap();
+        engine
+            .add_document(&prefix, "install rustup and rustfmt")
+            .unwrap();
+        engine
+            .add_document(&substring, "a trusted toolchain")
+            .unwrap();
+        engine.add_document(&unrelated, "nothing to see").unwrap();
+
+        let results = engine.search("rust*").unwrap();
+        let paths: Vec<_> = results.iter().map(|r| r.path.clone()).collect();
+        assert_eq!(paths, vec![exact, prefix, substring]);
+        assert!(results[0].score > results[1].score);
+        assert!(results[1].score > results[2].score);
+    }
+
+    #[test]
+    fn test_path_prefix_search() {
+        let mut engine = SearchEngine::new();
+        let main_rs = PathBuf::from("repo/src/main.rs");
+        let lib_rs = PathBuf::from("repo/src/lib.rs");
+        engine.add_document(&main_rs, "fn main() {}").unwrap();
+        engine.add_document(&lib_rs, "pub mod search;").unwrap();
+
+        let results = engine.search("src/main*").unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].path, main_rs);
+    }
+
+    #[test]
+    fn test_fuzzy_term_matches() {
+        assert!(SearchEngine::fuzzy_term_matches(
+            b"recieve",
+            b"we receive mail",
+            2
+        ));
+        assert!(SearchEngine::fuzzy_term_matches(
+            b"serch",
+            b"full search",
+            1
+        ));
+        assert!(SearchEngine::fuzzy_term_matches(
+            b"searchh",
+            b"full search",
+            1
+        ));
+        assert!(!SearchEngine::fuzzy_term_matches(
+            b"recieve",
+            b"we receive mail",
+            0
+        ));
+        assert!(!SearchEngine::fuzzy_term_matches(b"abcdef", b"uvwdef", 2));
+        assert!(!SearchEngine::fuzzy_term_matches(b"rust", b"trusted", 1));
+    }
+
+    #[test]
+    fn test_fuzzy_skips_empty_words() {
+        assert!(!SearchEngine::fuzzy_term_matches(b"z", b"foo, bar", 1));
+        assert!(!SearchEngine::fuzzy_term_matches(
+            b"k",
+            b"end..\n\n(see: notes)",
+            1
+        ));
+        assert_eq!(fuzzy_term_range(b"bat", b"foo, .. bar", 1), Some(8..11));
+
+        let mut engine = SearchEngine::new().with_options(SearchOptions::fuzzy());
+        engine
+            .add_document(Path::new("notes.txt"), "foo, bar -- baz...\n\nqux!")
+            .unwrap();
+        assert!(engine.search("z").unwrap().is_empty());
+        assert!(engine.search("k").unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_fuzzy_search_ranks_exact_first() {
+        let mut engine = SearchEngine::new();
+        let exact = PathBuf::from("mail/exact.txt");
+        let typo = PathBuf::from("mail/typo.txt");
+        engine.add_document(&exact, "please recieve this").unwrap();
+        engine.add_document(&typo, "please receive this").unwrap();
+
+        let results = engine.search("recieve").unwrap();
+        assert_eq!(results.len(), 1);
+
+        let engine = engine.with_options(SearchOptions::fuzzy());
+        let results = engine.search("recieve").unwrap();
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0].path, exact);
+        assert_eq!(results[1].path, typo);
+        assert!(results[0].score > results[1].score);
+    }
+
+    #[test]
+    fn test_fuzzy_distance_scales_with_term_length() {
+        let options = SearchOptions::fuzzy();
+        assert_eq!(options.edit_distance_for(b"rust"), 1);
+        assert_eq!(options.edit_distance_for(b"receive"), 2);
+        assert_eq!(SearchOptions::default().edit_distance_for(b"receive"), 0);
+
+        let mut engine = SearchEngine::new().with_options(options);
+        engine
+            .add_document(Path::new("a.txt"), "trust the rust")
+            .unwrap();
+        assert!(engine.search("rsut").unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_builder_weights() {
+        let mut code = SearchEngine::builder().weights(0.9, 0.1).unwrap().build();
+        let mut docs = SearchEngine::builder().weights(0.1, 0.9).unwrap().build();
+
+        for engine in [&mut code, &mut docs] {
+            engine
+                .add_document(Path::new("parser.rs"), "tokens")
+                .unwrap();
+            engine
+                .add_document(Path::new("notes.md"), "the parser is slow")
+                .unwrap();
+        }
+
+        assert_eq!(
+            code.search("parser").unwrap()[0].path,
+            PathBuf::from("parser.rs")
+        );
+        assert_eq!(
+            docs.search("parser").unwrap()[0].path,
+            PathBuf::from("notes.md")
+        );
+    }
+
+    #[test]
+    fn test_builder_rejects_invalid_weights() {
+        for (path, content) in [(0.5, 0.4), (1.2, -0.2), (f32::NAN, 0.5)] {
+            let err = SearchEngine::builder().weights(path, content).unwrap_err();
+            assert_eq!(
+                err.downcast_ref::<SnapError>().unwrap().code(),
+                ERROR_INVALID_CONFIG
+            );
+        }
+        assert!(SearchEngine::builder().weights(0.705, 0.3).is_ok());
+    }
+
+    #[test]
+    fn test_builder_fuzzy_and_stop_words() {
+        let mut engine = SearchEngine::builder()
+            .fuzzy_distance(2)
+            .stop_words(StopWordList::english())
+            .build();
+        engine
+            .add_document(Path::new("mail.txt"), "the receive queue")
+            .unwrap();
+
+        assert_eq!(engine.search("recieve").unwrap().len(), 1);
+        // Stop words alone are kept rather than leaving nothing to score
+        assert_eq!(engine.search("the").unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_add_document_relative() {
+        let mut engine = SearchEngine::new();
+        let root = Path::new("/home/me/project");
+        engine
+            .add_document_relative(root, &root.join("src/main.rs"), "fn main")
+            .unwrap();
+        engine
+            .add_document_relative(root, Path::new("/elsewhere/notes.txt"), "main notes")
+            .unwrap();
+
+        let results = engine.search("main").unwrap();
+        let paths: Vec<_> = results.iter().map(|r| r.path.clone()).collect();
+        assert!(paths.contains(&PathBuf::from("src/main.rs")));
+        assert!(paths.contains(&PathBuf::from("/elsewhere/notes.txt")));
+
+        let moved = Path::new("/srv/project");
+        for result in &results {
+            let resolved = result.resolved_path(moved);
+            assert!(
+                resolved == moved.join("src/main.rs")
+                    || resolved == Path::new("/elsewhere/notes.txt")
+            );
+        }
+    }
+
+    #[test]
+    fn test_save_and_load() {
+        let temp_dir = TempDir::new().unwrap();
+        let index_path = temp_dir.path().join("test.idx");
+
+        let mut engine = SearchEngine::new();
+        let doc_path = create_test_file(&temp_dir, "test.txt", "test content");
+        engine.add_document(&doc_path, "test content").unwrap();
+
+        engine.save(&index_path).unwrap();
+
+        let loaded = SearchEngine::load(&index_path).unwrap();
+
+        assert_eq!(loaded.documents.len(), 1);
+        assert_eq!(loaded.documents[0].path, doc_path);
+        assert_eq!(
+            String::from_utf8_lossy(&loaded.documents[0].content),
+            "test content"
+        );
+    }
+
+    #[test]
+    fn test_load_invalid_file() {
+        let temp_dir = TempDir::new().unwrap();
+        let invalid_path = temp_dir.path().join("invalid.idx");
+        File::create(&invalid_path).unwrap();
+
+        let result = SearchEngine::load(&invalid_path);
+        assert!(result.is_err());
+        let err = result.unwrap_err().to_string();
+        assert!(
+            err.contains("magic") || err.contains("index") || err.contains("Invalid"),
+            "Error should mention file format issue. Got: {err}"
+        );
+    }
+
+    #[test]
+    fn test_load_rejects_oversized_payload() {
+        let temp_dir = TempDir::new().unwrap();
+        let index_path = temp_dir.path().join("padded.idx");
+        let mut engine = SearchEngine::new();
+        engine.add_document(Path::new("a.txt"), "alpha").unwrap();
+        engine.save(&index_path).unwrap();
+
+        let mut bytes = fs::read(&index_path).unwrap();
+        bytes.resize(
+            INDEX_HEADER_LEN + max_payload_len(1, MAX_CONTENT_LENGTH) + 1,
+            0,
+        );
+        fs::write(&index_path, bytes).unwrap();
+
+        let err = SearchEngine::load(&index_path).unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<SnapError>().unwrap().code(),
+            ERROR_INVALID_INDEX
+        );
+        assert!(err.to_string().contains("larger than"), "{err}");
+    }
+
+    #[test]
+    fn test_load_missing_file() {
+        let temp_dir = TempDir::new().unwrap();
+        let missing_path = temp_dir.path().join("missing.idx");
+
+        assert!(SearchEngine::load(&missing_path).is_err());
+    }
+
+    #[test]
+    fn test_save_load_multiple_documents() {
+        let temp_dir = TempDir::new().unwrap();
+        let index_path = temp_dir.path().join("multi.idx");
+
+        let mut engine = SearchEngine::new();
+        let mut paths = Vec::new();
 
-    use tempfile::TempDir;
+        for i in 0..5 {
+            let path =
+                create_test_file(&temp_dir, &format!("doc_{i}.txt"), &format!("content {i}"));
+            engine.add_document(&path, &format!("content {i}")).unwrap();
+            paths.push(path);
+        }
 
-    use super::*;
+        engine.save(&index_path).unwrap();
+        let loaded = SearchEngine::load(&index_path).unwrap();
 
-    fn create_test_file(dir: &TempDir, name: &str, content: &str) -> PathBuf {
-        let path = dir.path().join(name);
-        let mut file = File::create(&path).unwrap();
-        file.write_all(content.as_bytes()).unwrap();
-        path
+        assert_eq!(loaded.documents.len(), 5);
+        for (i, doc) in loaded.documents.iter().enumerate() {
+            assert_eq!(doc.path, paths[i]);
+            assert_eq!(
+                String::from_utf8_lossy(&doc.content),
+                format!("content {i}")
+            );
+        }
     }
 
     #[test]
-    fn test_engine_creation() {
-        let _engine = SearchEngine::new();
+    fn test_save_load_compressible_content() {
+        let temp_dir = TempDir::new().unwrap();
+        let index_path = temp_dir.path().join("v2.idx");
+        let content = "repeat after me ".repeat(60);
+
+        let mut engine = SearchEngine::new();
+        let doc_path = create_test_file(&temp_dir, "repeat.txt", &content);
+        engine.add_document(&doc_path, &content).unwrap();
+        engine.save(&index_path).unwrap();
+
+        let size = std::fs::metadata(&index_path).unwrap().len();
+        assert!(size < u64::try_from(content.len()).unwrap() / 2);
+
+        let loaded = SearchEngine::load(&index_path).unwrap();
+        assert_eq!(
+            String::from_utf8_lossy(&loaded.documents[0].content),
+            content
+        );
     }
 
     #[test]
-    fn test_add_and_search_document() {
-        let mut engine = SearchEngine::new();
+    fn test_save_load_incompressible_content() {
         let temp_dir = TempDir::new().unwrap();
-        let path = create_test_file(
-            &temp_dir,
-            "test.txt",
-            "This is a test document with some unique content",
-        );
+        let index_path = temp_dir.path().join("raw.idx");
+        let content: String = (0..MAX_CONTENT_LENGTH)
+            .map(|i| char::from(b'!' + u8::try_from((i * 7 + i / 13) % 94).unwrap()))
+            .collect();
 
-        let result = engine.add_document(&path, "This is a test document with some unique content");
-        assert!(result.is_ok());
+        let mut engine = SearchEngine::new();
+        let doc_path = create_test_file(&temp_dir, "noise.txt", &content);
+        engine.add_document(&doc_path, &content).unwrap();
+        engine.add_document(&doc_path, "tiny").unwrap();
+        engine.save(&index_path).unwrap();
 
-        let results = engine.search("unique content").unwrap();
-        assert!(!results.is_empty());
-        assert_eq!(results[0].path, path);
+        let loaded = SearchEngine::load(&index_path).unwrap();
+        assert_eq!(
+            String::from_utf8_lossy(&loaded.documents[0].content),
+            content
+        );
+        assert_eq!(
+            String::from_utf8_lossy(&loaded.documents[1].content),
+            "tiny"
+        );
     }
 
     #[test]
-    fn test_search_no_results() {
-        let engine = SearchEngine::new();
-        let results = engine.search("nonexistent").unwrap();
-        assert!(results.is_empty());
+    fn test_load_full_index_is_fast() {
+        let temp_dir = TempDir::new().unwrap();
+        let index_path = temp_dir.path().join("full.idx");
+        let mut engine = SearchEngine::new();
+        for i in 0..MAX_DOCUMENTS {
+            let content: String = (0..MAX_CONTENT_LENGTH)
+                .map(|j| char::from(b'!' + u8::try_from((i * 31 + j * 7 + j / 13) % 94).unwrap()))
+                .collect();
+            let path = format!("{}/doc{i}.txt", "nested/".repeat(100));
+            engine.add_document(Path::new(&path), &content).unwrap();
+        }
+        engine.save(&index_path).unwrap();
+
+        let start = Instant::now();
+        let loaded = SearchEngine::load(&index_path).unwrap();
+        let elapsed = start.elapsed();
+        assert_eq!(loaded.documents.len(), MAX_DOCUMENTS);
+        assert!(elapsed < Duration::from_millis(50), "load took {elapsed:?}");
     }
 
     #[test]
-    fn test_multiple_documents() {
-        let mut engine = SearchEngine::new();
+    fn test_load_v1_index() {
         let temp_dir = TempDir::new().unwrap();
+        let index_path = temp_dir.path().join("v1.idx");
 
-        for i in 0..5 {
-            let path = create_test_file(
-                &temp_dir,
-                &format!("test_{i}.txt"),
-                &format!("Document {i} with content"),
-            );
-            engine
-                .add_document(&path, &format!("Document {i} with content"))
-                .unwrap();
-        }
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(&MAGIC);
+        bytes.push(VERSION_V1);
+        bytes.extend_from_slice(&1_u32.to_le_bytes());
+        bytes.extend_from_slice(&8_u16.to_le_bytes());
+        bytes.extend_from_slice(b"notes.md");
+        bytes.extend_from_slice(&12_u16.to_le_bytes());
+        bytes.extend_from_slice(b"legacy index");
+        std::fs::write(&index_path, bytes).unwrap();
 
-        let results = engine.search("Document").unwrap();
-        assert_eq!(results.len(), 5);
+        let loaded = SearchEngine::load(&index_path).unwrap();
+        assert_eq!(loaded.documents.len(), 1);
+        assert_eq!(loaded.documents[0].path, PathBuf::from("notes.md"));
+        assert_eq!(
+            String::from_utf8_lossy(&loaded.documents[0].content),
+            "legacy index"
+        );
     }
 
     #[test]
-    fn test_result_limit() {
-        let mut engine = SearchEngine::new();
+    fn test_load_corrupt_compressed_block() {
         let temp_dir = TempDir::new().unwrap();
+        let index_path = temp_dir.path().join("corrupt.idx");
 
-        for i in 0..MAX_DOCUMENTS {
-            let path = create_test_file(&temp_dir, &format!("doc_{i}.txt"), "common content");
-            engine.add_document(&path, "common content").unwrap();
-        }
+        let mut payload = Vec::new();
+        payload.extend_from_slice(&5_u16.to_le_bytes());
+        payload.extend_from_slice(b"a.txt");
+        payload.push(CONTENT_COMPRESSED);
+        payload.extend_from_slice(&3_u16.to_le_bytes());
+        payload.extend_from_slice(&[0x80, 0x09, 0x00]);
 
-        let results = engine.search("common").unwrap();
-        assert_eq!(results.len(), MAX_DOCUMENTS);
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(&MAGIC);
+        bytes.push(VERSION_V3);
+        bytes.extend_from_slice(&1_u32.to_le_bytes());
+        bytes.extend_from_slice(&crc32(&payload).to_le_bytes());
+        bytes.extend_from_slice(&payload);
+        std::fs::write(&index_path, bytes).unwrap();
 
-        let path = create_test_file(&temp_dir, "one_too_many.txt", "common content");
-        assert!(engine.add_document(&path, "common content").is_err());
+        let err = SearchEngine::load(&index_path).unwrap_err();
+        let snap_err = err.downcast_ref::<SnapError>().unwrap();
+        assert_eq!(snap_err.code(), ERROR_INVALID_INDEX);
     }
 
     #[test]
-    fn test_result_ranking() {
-        let mut engine = SearchEngine::new();
+    fn test_save_respects_index_lock() {
         let temp_dir = TempDir::new().unwrap();
+        let index_path = temp_dir.path().join(INDEX_FILE_NAME);
 
-        let path1 = create_test_file(&temp_dir, "rust_guide.txt", "rust programming guide");
-        let path2 = create_test_file(&temp_dir, "other.txt", "rust content");
-
+        let mut engine = SearchEngine::new();
         engine
-            .add_document(&path1, "rust programming guide")
+            .add_document(Path::new("a.txt"), "locked content")
             .unwrap();
-        engine.add_document(&path2, "rust content").unwrap();
 
-        let results = engine.search("rust").unwrap();
-        assert_eq!(results.len(), 2);
-        assert_eq!(results[0].path, path1);
-        assert!(results[0].score > results[1].score);
+        let held = IndexLock::acquire(&index_path).unwrap();
+        let err = engine
+            .save_with_wait(&index_path, Duration::ZERO)
+            .unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<SnapError>().unwrap().code(),
+            ERROR_INDEX_LOCKED
+        );
+        assert!(!index_path.exists());
+        drop(held);
+
+        engine.save(&index_path).unwrap();
+        assert!(!lock::lock_path(&index_path).exists());
+        assert!(!lock::with_suffix(&index_path, lock::TMP_SUFFIX).exists());
+        assert_eq!(SearchEngine::load(&index_path).unwrap().documents.len(), 1);
     }
 
     #[test]
-    fn test_filename_match() {
-        let mut engine = SearchEngine::new();
+    fn test_load_detects_flipped_byte() {
         let temp_dir = TempDir::new().unwrap();
-        let path = create_test_file(&temp_dir, "important_doc.txt", "Some content");
+        let index_path = temp_dir.path().join("flipped.idx");
 
-        engine.add_document(&path, "Some content").unwrap();
-        let results = engine.search("important").unwrap();
-        assert!(!results.is_empty());
-        assert_eq!(results[0].path, path);
+        let mut engine = SearchEngine::new();
+        let doc_path = create_test_file(&temp_dir, "test.txt", "checksummed content");
+        engine
+            .add_document(&doc_path, "checksummed content")
+            .unwrap();
+        engine.save(&index_path).unwrap();
+
+        let mut bytes = std::fs::read(&index_path).unwrap();
+        let last = bytes.len() - 1;
+        bytes[last] ^= 0x01;
+        std::fs::write(&index_path, &bytes).unwrap();
+
+        let err = SearchEngine::load(&index_path).unwrap_err();
+        assert!(err.to_string().contains("Index file is corrupted"));
+        assert_eq!(
+            err.downcast_ref::<SnapError>().unwrap().code(),
+            ERROR_INVALID_INDEX
+        );
+
+        bytes.truncate(last);
+        std::fs::write(&index_path, &bytes).unwrap();
+        let err = SearchEngine::load(&index_path).unwrap_err();
+        assert!(err.to_string().contains("Index file is corrupted"));
     }
 
     #[test]
-    fn test_case_insensitive() {
+    fn test_add_document_bytes() {
         let mut engine = SearchEngine::new();
+        engine
+            .add_document_bytes(Path::new("latin1.txt"), b"caf\xE9 menu prices")
+            .unwrap();
+        assert_eq!(engine.documents[0].content, b"caf\xE9 menu prices");
+        assert_eq!(engine.search("prices").unwrap().len(), 1);
+
+        let too_large = vec![b'a'; engine.max_content_length() + 1];
+        let err = engine
+            .add_document_bytes(Path::new("big.txt"), &too_large)
+            .unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<SnapError>().unwrap().code(),
+            ERROR_CONTENT_TOO_LARGE
+        );
+    }
+
+    #[test]
+    fn test_metadata_round_trip() {
         let temp_dir = TempDir::new().unwrap();
-        let path = create_test_file(&temp_dir, "TEST.txt", "UPPERCASE CONTENT");
+        let index_path = temp_dir.path().join(INDEX_FILE_NAME);
 
-        engine.add_document(&path, "UPPERCASE CONTENT").unwrap();
+        let mut engine = SearchEngine::new();
+        assert_eq!(engine.metadata(), &IndexMetadata::default());
+        engine.add_document(Path::new("a.txt"), "alpha").unwrap();
+        engine.add_document(Path::new("b.txt"), "beta").unwrap();
+        let before = SystemTime::now() - Duration::from_secs(1);
+        engine.save(&index_path).unwrap();
 
-        let results = engine.search("uppercase").unwrap();
-        assert!(!results.is_empty());
-        let results2 = engine.search("UPPERCASE").unwrap();
-        assert!(!results2.is_empty());
+        let loaded = SearchEngine::load(&index_path).unwrap();
+        let metadata = loaded.metadata();
+        assert_eq!(metadata.document_count, 2);
+        assert_eq!(metadata.tool_version, env!("CARGO_PKG_VERSION"));
+        assert_eq!(
+            metadata.root.as_deref(),
+            Some(temp_dir.path().canonicalize().unwrap().as_path())
+        );
+        assert!(metadata.created.unwrap() >= before);
+        assert!(metadata.age(SystemTime::now()).is_some());
     }
 
     #[test]
-    fn test_content_too_large() {
-        let mut engine = SearchEngine::new();
+    fn test_crawl_filters_round_trip() {
         let temp_dir = TempDir::new().unwrap();
-        let large_content = "x".repeat(MAX_CONTENT_LENGTH + 1);
-        let path = create_test_file(&temp_dir, "large.txt", &large_content);
-        assert!(engine.add_document(&path, &large_content).is_err());
+        let index_path = temp_dir.path().join(INDEX_FILE_NAME);
+        let filters = CrawlFilters {
+            extensions: vec!["txt".to_string(), "md".to_string()],
+            skip_hidden: false,
+            min_file_size: 4,
+        };
+
+        let mut engine = SearchEngine::new();
+        engine.add_document(Path::new("a.txt"), "alpha").unwrap();
+        engine.save(&index_path).unwrap();
+        assert_eq!(
+            SearchEngine::load(&index_path).unwrap().metadata().filters,
+            None
+        );
+
+        engine.set_crawl_filters(filters.clone());
+        engine.save(&index_path).unwrap();
+        let loaded = SearchEngine::load(&index_path).unwrap();
+        assert_eq!(loaded.metadata().filters, Some(filters));
     }
 
     #[test]
-    fn test_multiple_term_scoring() {
-        let mut engine = SearchEngine::new();
+    fn test_is_stale() {
         let temp_dir = TempDir::new().unwrap();
+        let index_path = temp_dir.path().join(INDEX_FILE_NAME);
+        let stamp = |modified, len| FileStamp { modified, len };
+        let epoch = SystemTime::UNIX_EPOCH;
 
-        let path1 = create_test_file(&temp_dir, "test1.txt", "rust programming guide");
-        let path2 = create_test_file(&temp_dir, "rust_book.txt", "some other content");
+        let mut engine = SearchEngine::new();
+        engine.add_document(Path::new("a.txt"), "alpha").unwrap();
+        // No stamp and no creation time: everything must be re-read
+        assert!(engine.is_stale(Path::new("a.txt"), stamp(epoch, 5)));
+
+        // Without a stamp, the creation time decides
+        engine.save(&index_path).unwrap();
+        let loaded = SearchEngine::load(&index_path).unwrap();
+        let created = loaded.metadata().created.unwrap();
+        let before = created - Duration::from_secs(1);
+        assert!(!loaded.is_stale(Path::new("a.txt"), stamp(before, 5)));
+        assert!(loaded.is_stale(Path::new("a.txt"), stamp(created, 5)));
+        assert!(loaded.is_stale(Path::new("b.txt"), stamp(epoch, 5)));
+
+        // A stored stamp must match exactly, so older copies are caught
+        let indexed = stamp(epoch + Duration::new(1_700_000_000, 42), 5);
+        assert!(engine.set_document_stamp(Path::new("a.txt"), indexed));
+        assert!(!engine.set_document_stamp(Path::new("b.txt"), indexed));
+        engine.save(&index_path).unwrap();
+        let loaded = SearchEngine::load(&index_path).unwrap();
+        assert_eq!(
+            loaded.get_document(Path::new("a.txt")).unwrap().stamp,
+            Some(indexed)
+        );
+        assert!(!loaded.is_stale(Path::new("a.txt"), indexed));
+        assert!(loaded.is_stale(Path::new("a.txt"), stamp(indexed.modified, 6)));
+        assert!(loaded.is_stale(Path::new("a.txt"), stamp(before, 5)));
+        assert!(loaded.is_stale(
+            Path::new("a.txt"),
+            stamp(indexed.modified + Duration::from_nanos(1), 5)
+        ));
 
+        // New content drops the stamp of the old
         engine
-            .add_document(&path1, "rust programming guide")
+            .update_document(Path::new("a.txt"), "alpha two")
             .unwrap();
-        engine.add_document(&path2, "some other content").unwrap();
-
-        let results = engine.search("rust").unwrap();
-        assert_eq!(results.len(), 2);
-        assert!(results[0].score > results[1].score);
+        assert_eq!(engine.get_document(Path::new("a.txt")).unwrap().stamp, None);
     }
 
     #[test]
-    fn test_save_and_load() {
+    fn test_load_v4_index_defaults_metadata() {
         let temp_dir = TempDir::new().unwrap();
-        let index_path = temp_dir.path().join("test.idx");
+        let index_path = temp_dir.path().join("v4.idx");
 
-        let mut engine = SearchEngine::new();
-        let doc_path = create_test_file(&temp_dir, "test.txt", "test content");
-        engine.add_document(&doc_path, "test content").unwrap();
+        let mut payload = Vec::new();
+        payload.extend_from_slice(&5_u16.to_le_bytes());
+        payload.extend_from_slice(b"c.txt");
+        payload.push(CONTENT_RAW);
+        payload.extend_from_slice(&2_u16.to_le_bytes());
+        payload.extend_from_slice(b"v4");
 
-        engine.save(&index_path).unwrap();
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(&MAGIC);
+        bytes.push(VERSION_V4);
+        bytes.extend_from_slice(&1_u32.to_le_bytes());
+        bytes.extend_from_slice(&(MAX_CONTENT_LENGTH as u32).to_le_bytes());
+        bytes.extend_from_slice(&crc32(&payload).to_le_bytes());
+        bytes.extend_from_slice(&payload);
+        std::fs::write(&index_path, bytes).unwrap();
 
         let loaded = SearchEngine::load(&index_path).unwrap();
+        assert_eq!(String::from_utf8_lossy(&loaded.documents[0].content), "v4");
+        let metadata = loaded.metadata();
+        assert_eq!(metadata.document_count, 1);
+        assert!(metadata.created.is_none());
+        assert!(metadata.tool_version.is_empty());
+        assert!(metadata.root.is_none());
+    }
 
-        assert_eq!(loaded.documents.len(), 1);
-        assert_eq!(loaded.documents[0].path, doc_path);
-        assert_eq!(
-            String::from_utf8_lossy(&loaded.documents[0].content),
-            "test content"
-        );
+    #[test]
+    fn test_creation_time_honors_source_date_epoch() {
+        assert_eq!(creation_time(Some("1700000000")), 1_700_000_000);
+        assert_eq!(creation_time(Some(" 0\n")), 0);
+
+        let now = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .unwrap()
+            .as_secs();
+        for invalid in [None, Some(""), Some("yesterday"), Some("-1")] {
+            assert!(creation_time(invalid) >= now, "{invalid:?}");
+        }
     }
 
     #[test]
-    fn test_load_invalid_file() {
+    fn test_load_v5_index() {
         let temp_dir = TempDir::new().unwrap();
-        let invalid_path = temp_dir.path().join("invalid.idx");
-        File::create(&invalid_path).unwrap();
+        let index_path = temp_dir.path().join("v5.idx");
 
-        let result = SearchEngine::load(&invalid_path);
-        assert!(result.is_err());
-        let err = result.unwrap_err().to_string();
-        assert!(
-            err.contains("magic") || err.contains("index") || err.contains("Invalid"),
-            "Error should mention file format issue. Got: {err}"
-        );
+        let mut payload = Vec::new();
+        payload.extend_from_slice(&7_u64.to_le_bytes());
+        payload.push(3);
+        payload.extend_from_slice(b"0.9");
+        payload.extend_from_slice(&5_u16.to_le_bytes());
+        payload.extend_from_slice(b"/root");
+        payload.extend_from_slice(&9_u16.to_le_bytes());
+        payload.extend_from_slice("caf\u{fffd}.md".as_bytes());
+        payload.push(CONTENT_RAW);
+        payload.extend_from_slice(&2_u16.to_le_bytes());
+        payload.extend_from_slice(b"v5");
+
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(&MAGIC);
+        bytes.push(VERSION_V5);
+        bytes.extend_from_slice(&1_u32.to_le_bytes());
+        bytes.extend_from_slice(&(MAX_CONTENT_LENGTH as u32).to_le_bytes());
+        bytes.extend_from_slice(&crc32(&payload).to_le_bytes());
+        bytes.extend_from_slice(&payload);
+        std::fs::write(&index_path, bytes).unwrap();
+
+        let loaded = SearchEngine::load(&index_path).unwrap();
+        assert_eq!(loaded.documents[0].path, Path::new("caf\u{fffd}.md"));
+        let metadata = loaded.metadata();
+        assert_eq!(metadata.tool_version, "0.9");
+        assert_eq!(metadata.root.as_deref(), Some(Path::new("/root")));
     }
 
     #[test]
-    fn test_load_missing_file() {
+    fn test_load_v6_index_without_filters() {
         let temp_dir = TempDir::new().unwrap();
-        let missing_path = temp_dir.path().join("missing.idx");
+        let index_path = temp_dir.path().join("v6.idx");
 
-        assert!(SearchEngine::load(&missing_path).is_err());
+        let mut payload = Vec::new();
+        payload.extend_from_slice(&7_u64.to_le_bytes());
+        payload.push(3);
+        payload.extend_from_slice(b"1.0");
+        payload.extend_from_slice(&5_u16.to_le_bytes());
+        payload.extend_from_slice(b"/root");
+        payload.extend_from_slice(&5_u16.to_le_bytes());
+        payload.extend_from_slice(b"d.txt");
+        payload.push(CONTENT_RAW);
+        payload.extend_from_slice(&2_u16.to_le_bytes());
+        payload.extend_from_slice(b"v6");
+
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(&MAGIC);
+        bytes.push(VERSION_V6);
+        bytes.extend_from_slice(&1_u32.to_le_bytes());
+        bytes.extend_from_slice(&(MAX_CONTENT_LENGTH as u32).to_le_bytes());
+        bytes.extend_from_slice(&crc32(&payload).to_le_bytes());
+        bytes.extend_from_slice(&payload);
+        std::fs::write(&index_path, bytes).unwrap();
+
+        let loaded = SearchEngine::load(&index_path).unwrap();
+        assert_eq!(String::from_utf8_lossy(&loaded.documents[0].content), "v6");
+        assert!(loaded.documents[0].stamp.is_none());
+        let metadata = loaded.metadata();
+        assert_eq!(metadata.tool_version, "1.0");
+        assert!(metadata.filters.is_none());
     }
 
+    #[cfg(unix)]
     #[test]
-    fn test_save_load_multiple_documents() {
+    fn test_non_utf8_paths_round_trip() {
+        use std::ffi::OsStr;
+        use std::os::unix::ffi::OsStrExt;
+
         let temp_dir = TempDir::new().unwrap();
-        let index_path = temp_dir.path().join("multi.idx");
+        let index_path = temp_dir.path().join("raw.idx");
+        let name = Path::new(OsStr::from_bytes(b"caf\xe9.txt"));
 
         let mut engine = SearchEngine::new();
-        let mut paths = Vec::new();
+        engine.add_document(name, "menu").unwrap();
+        engine.save(&index_path).unwrap();
 
-        for i in 0..5 {
-            let path =
-                create_test_file(&temp_dir, &format!("doc_{i}.txt"), &format!("content {i}"));
-            engine.add_document(&path, &format!("content {i}")).unwrap();
-            paths.push(path);
-        }
+        let loaded = SearchEngine::load(&index_path).unwrap();
+        assert_eq!(loaded.documents[0].path, name);
+        assert_eq!(loaded.search("menu").unwrap()[0].path, name);
+    }
+
+    #[test]
+    fn test_load_v2_index_without_checksum() {
+        let temp_dir = TempDir::new().unwrap();
+        let index_path = temp_dir.path().join("v2.idx");
+
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(&MAGIC);
+        bytes.push(VERSION_V2);
+        bytes.extend_from_slice(&1_u32.to_le_bytes());
+        bytes.extend_from_slice(&5_u16.to_le_bytes());
+        bytes.extend_from_slice(b"b.txt");
+        bytes.push(CONTENT_RAW);
+        bytes.extend_from_slice(&2_u16.to_le_bytes());
+        bytes.extend_from_slice(b"v2");
+        std::fs::write(&index_path, bytes).unwrap();
 
-        engine.save(&index_path).unwrap();
         let loaded = SearchEngine::load(&index_path).unwrap();
+        assert_eq!(String::from_utf8_lossy(&loaded.documents[0].content), "v2");
+    }
 
-        assert_eq!(loaded.documents.len(), 5);
-        for (i, doc) in loaded.documents.iter().enumerate() {
-            assert_eq!(doc.path, paths[i]);
-            assert_eq!(
-                String::from_utf8_lossy(&doc.content),
-                format!("content {i}")
+    #[test]
+    fn test_load_rejects_unknown_versions() {
+        let temp_dir = TempDir::new().unwrap();
+        let index_path = temp_dir.path().join("future.idx");
+
+        for version in [0, VERSION + 1] {
+            let mut bytes = Vec::new();
+            bytes.extend_from_slice(&MAGIC);
+            bytes.push(version);
+            bytes.extend_from_slice(&0_u32.to_le_bytes());
+            std::fs::write(&index_path, bytes).unwrap();
+
+            let err = SearchEngine::load(&index_path).unwrap_err();
+            assert!(
+                err.to_string().contains("Unsupported index version"),
+                "{err}"
             );
         }
     }
 
+    #[test]
+    fn test_has_index_magic() {
+        let temp_dir = TempDir::new().unwrap();
+        let index_path = temp_dir.path().join(INDEX_FILE_NAME);
+        SearchEngine::new().save(&index_path).unwrap();
+        assert!(has_index_magic(&index_path));
+
+        let impostor = create_test_file(&temp_dir, "impostor", "user notes");
+        assert!(!has_index_magic(&impostor));
+        assert!(!has_index_magic(&temp_dir.path().join("missing")));
+    }
+
     #[test]
     fn test_path_length_limit() {
         let temp_dir = TempDir::new().unwrap();
@@ -748,5 +4725,10 @@ mod tests {
         assert!(validate_query(&long_query).is_err());
 
         assert!(validate_query("test\0file").is_err());
+        assert!(validate_query("\u{1f600}").is_err());
+        assert_eq!(
+            validate_query("caf\u{00e9}").is_ok(),
+            cfg!(feature = "unicode")
+        );
     }
 }
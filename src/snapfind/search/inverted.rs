@@ -0,0 +1,275 @@
+use arrayvec::ArrayString;
+
+use super::{MAX_CONTENT_LENGTH, MAX_DOCUMENTS, MAX_PATH_BYTES, MAX_TERM_LENGTH};
+
+#[derive(Debug)]
+struct Posting {
+    term: ArrayString<MAX_TERM_LENGTH>,
+    docs: Vec<u32>,
+    /// Documents with the term in their content rather than only their path.
+    content_docs: usize,
+    last_content_doc: Option<u32>,
+}
+
+/// Postings grow with the text indexed, up to `max_terms` distinct terms.
+#[derive(Debug)]
+pub struct InvertedIndex {
+    terms: Vec<Posting>,
+    max_terms: usize,
+    overflowed: bool,
+    non_ascii_content: bool,
+}
+
+impl Default for InvertedIndex {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Most distinct terms a full engine storing up to `max_content_length`
+/// bytes per document can hold: every term needs a byte and a separator.
+#[must_use]
+pub const fn max_terms_for(max_content_length: usize) -> usize {
+    MAX_DOCUMENTS * (max_content_length + MAX_PATH_BYTES).div_ceil(2)
+}
+
+impl InvertedIndex {
+    /// An index sized for documents of up to [`MAX_CONTENT_LENGTH`] bytes.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::with_max_terms(max_terms_for(MAX_CONTENT_LENGTH))
+    }
+
+    #[must_use]
+    pub const fn with_max_terms(max_terms: usize) -> Self {
+        Self {
+            terms: Vec::new(),
+            max_terms,
+            overflowed: false,
+            non_ascii_content: false,
+        }
+    }
+
+    /// Drops every posting, keeping the term limit.
+    pub fn clear(&mut self) {
+        self.terms.clear();
+        self.overflowed = false;
+        self.non_ascii_content = false;
+    }
+
+    /// Indexes text that is not a document's content, such as its path.
+    pub fn insert_text(&mut self, doc_id: u32, text: &[u8]) {
+        for token in tokens(text) {
+            self.insert_term(doc_id, token, false);
+        }
+    }
+
+    /// Indexes a document's content, counted by [`InvertedIndex::content_frequency`].
+    pub fn insert_content(&mut self, doc_id: u32, content: &[u8]) {
+        self.non_ascii_content |= !content.is_ascii();
+        for token in tokens(content) {
+            self.insert_term(doc_id, token, true);
+        }
+    }
+
+    fn insert_term(&mut self, doc_id: u32, token: &[u8], in_content: bool) {
+        let Some(key) = lowercase_key(token) else {
+            return;
+        };
+
+        let pos = match self.search(&key) {
+            Ok(pos) => pos,
+            Err(pos) => {
+                if self.terms.len() >= self.max_terms {
+                    self.overflowed = true;
+                    return;
+                }
+                let posting = Posting {
+                    term: key,
+                    docs: Vec::new(),
+                    content_docs: 0,
+                    last_content_doc: None,
+                };
+                self.terms.insert(pos, posting);
+                pos
+            }
+        };
+
+        let posting = &mut self.terms[pos];
+        if in_content && posting.last_content_doc != Some(doc_id) {
+            posting.last_content_doc = Some(doc_id);
+            posting.content_docs += 1;
+        }
+        if posting.docs.last() == Some(&doc_id) {
+            return;
+        }
+        if posting.docs.len() >= MAX_DOCUMENTS {
+            self.overflowed = true;
+            return;
+        }
+        posting.docs.push(doc_id);
+    }
+
+    /// Documents with `term` as a word in their path or content.
+    #[must_use]
+    pub fn lookup(&self, term: &[u8]) -> &[u32] {
+        self.posting(term)
+            .map_or(&[], |posting| posting.docs.as_slice())
+    }
+
+    /// Number of documents with `term` as a word in their content.
+    #[must_use]
+    pub fn content_frequency(&self, term: &[u8]) -> usize {
+        self.posting(term).map_or(0, |posting| posting.content_docs)
+    }
+
+    /// Whether any content had non-ASCII bytes, whose words Unicode-aware
+    /// matching may split differently from the ASCII words indexed here.
+    #[must_use]
+    pub const fn has_non_ascii_content(&self) -> bool {
+        self.non_ascii_content
+    }
+
+    fn posting(&self, term: &[u8]) -> Option<&Posting> {
+        let key = lowercase_key(term)?;
+        self.search(&key).ok().map(|pos| &self.terms[pos])
+    }
+
+    /// Returns false once a term or posting list has been dropped for lack of
+    /// capacity, at which point lookups may miss documents.
+    #[must_use]
+    pub const fn is_complete(&self) -> bool {
+        !self.overflowed
+    }
+
+    /// Flags the index as out of date with the documents it was built from.
+    pub const fn mark_incomplete(&mut self) {
+        self.overflowed = true;
+    }
+
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.terms.len()
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.terms.is_empty()
+    }
+
+    fn search(&self, key: &str) -> Result<usize, usize> {
+        self.terms
+            .binary_search_by(|posting| posting.term.as_str().cmp(key))
+    }
+}
+
+/// Splits text into runs of ASCII alphanumerics, matching the word boundaries
+/// used by `SearchEngine::term_matches`.
+pub fn tokens(text: &[u8]) -> impl Iterator<Item = &[u8]> {
+    text.split(|b| !b.is_ascii_alphanumeric())
+        .filter(|token| !token.is_empty())
+}
+
+#[must_use]
+pub fn is_indexable_term(term: &[u8]) -> bool {
+    !term.is_empty() && term.len() <= MAX_TERM_LENGTH && term.iter().all(u8::is_ascii_alphanumeric)
+}
+
+fn lowercase_key(token: &[u8]) -> Option<ArrayString<MAX_TERM_LENGTH>> {
+    if !is_indexable_term(token) {
+        return None;
+    }
+
+    let mut key = ArrayString::new();
+    for &b in token {
+        key.push(char::from(b.to_ascii_lowercase()));
+    }
+    Some(key)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lookup_is_case_insensitive() {
+        let mut index = InvertedIndex::new();
+        index.insert_text(0, b"Hello world, hello again");
+        index.insert_text(1, b"fn main() { world }");
+
+        assert_eq!(index.lookup(b"HELLO"), &[0]);
+        assert_eq!(index.lookup(b"world"), &[0, 1]);
+        assert_eq!(index.lookup(b"main"), &[1]);
+        assert!(index.lookup(b"missing").is_empty());
+        assert!(index.lookup(b"main()").is_empty());
+        assert!(index.is_complete());
+    }
+
+    #[test]
+    fn test_content_frequency_ignores_paths() {
+        let mut index = InvertedIndex::new();
+        index.insert_text(0, b"notes/deploy.txt");
+        index.insert_content(0, b"deploy steps, deploy again");
+        index.insert_text(1, b"deploy.md");
+        index.insert_content(1, b"rollback");
+        index.insert_text(2, b"other.txt");
+        index.insert_content(2, b"Deploy later");
+
+        assert_eq!(index.lookup(b"deploy"), &[0, 1, 2]);
+        assert_eq!(index.content_frequency(b"deploy"), 2);
+        assert_eq!(index.content_frequency(b"notes"), 0);
+        assert_eq!(index.content_frequency(b"missing"), 0);
+        assert!(!index.has_non_ascii_content());
+
+        index.insert_content(3, "caf\u{e9}".as_bytes());
+        assert!(index.has_non_ascii_content());
+    }
+
+    #[test]
+    fn test_terms_stay_sorted() {
+        let mut index = InvertedIndex::new();
+        index.insert_text(0, b"zeta alpha mu beta alpha");
+
+        let terms: Vec<_> = index.terms.iter().map(|p| p.term.as_str()).collect();
+        assert_eq!(terms, vec!["alpha", "beta", "mu", "zeta"]);
+        assert_eq!(index.len(), 4);
+    }
+
+    #[test]
+    fn test_overflow_marks_incomplete() {
+        let mut index = InvertedIndex::with_max_terms(8);
+        for i in 0..=8 {
+            index.insert_text(0, format!("term{i}").as_bytes());
+        }
+
+        assert_eq!(index.len(), 8);
+        assert!(!index.is_complete());
+
+        index.clear();
+        assert!(index.is_empty());
+        assert!(index.is_complete());
+    }
+
+    #[test]
+    fn test_default_limit_holds_a_full_engine_vocabulary() {
+        let mut index = InvertedIndex::new();
+        for doc_id in 0..MAX_DOCUMENTS {
+            let words: String = (0..200).map(|i| format!("w{doc_id}x{i} ")).collect();
+            index.insert_text(u32::try_from(doc_id).unwrap(), words.as_bytes());
+        }
+
+        assert_eq!(index.len(), MAX_DOCUMENTS * 200);
+        assert!(index.is_complete());
+        assert_eq!(index.lookup(b"W99X199"), &[99]);
+    }
+
+    #[test]
+    fn test_long_tokens_are_skipped() {
+        let mut index = InvertedIndex::new();
+        let long = "x".repeat(MAX_TERM_LENGTH + 1);
+        index.insert_text(0, long.as_bytes());
+
+        assert!(index.is_empty());
+        assert!(index.is_complete());
+    }
+}
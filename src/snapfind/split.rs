@@ -0,0 +1,237 @@
+use std::str::FromStr;
+
+use super::error::{ErrorCode, SnapError};
+use super::search::MAX_CONTENT_LIMIT;
+
+pub const ERROR_INVALID_SPLIT: i32 = ErrorCode::InvalidSplit.code();
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SplitMode {
+    Lines,
+    Paragraphs,
+    Bytes(usize),
+}
+
+/// A slice of the input that becomes one pseudo-document.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Chunk<'a> {
+    pub text: &'a str,
+    pub offset: usize,
+    pub line: usize,
+}
+
+impl FromStr for SplitMode {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid =
+            |msg: String| anyhow::Error::from(SnapError::with_code(msg, ERROR_INVALID_SPLIT));
+
+        match s {
+            "lines" => Ok(Self::Lines),
+            "paragraphs" => Ok(Self::Paragraphs),
+            _ => {
+                let size = s.strip_prefix("bytes:").ok_or_else(|| {
+                    invalid(format!(
+                        "Unknown split mode '{s}', expected lines, paragraphs or bytes:N"
+                    ))
+                })?;
+                match size.parse::<usize>() {
+                    Ok(n) if (1..=MAX_CONTENT_LIMIT).contains(&n) => Ok(Self::Bytes(n)),
+                    _ => Err(invalid(format!(
+                        "Chunk size must be between 1 and {MAX_CONTENT_LIMIT}, got '{size}'"
+                    ))),
+                }
+            }
+        }
+    }
+}
+
+/// Splits `text` into chunks, dropping any that are only whitespace.
+#[must_use]
+pub fn split(text: &str, mode: SplitMode) -> Vec<Chunk<'_>> {
+    let mut chunks = match mode {
+        SplitMode::Lines => split_lines(text),
+        SplitMode::Paragraphs => split_paragraphs(text),
+        SplitMode::Bytes(size) => split_bytes(text, size),
+    };
+    chunks.retain(|chunk| !chunk.text.trim().is_empty());
+    chunks
+}
+
+fn lines_with_offsets(text: &str) -> impl Iterator<Item = (usize, &str)> {
+    let mut offset = 0;
+    text.split_inclusive('\n').map(move |raw| {
+        let start = offset;
+        offset += raw.len();
+        let line = raw.strip_suffix('\n').unwrap_or(raw);
+        (start, line.strip_suffix('\r').unwrap_or(line))
+    })
+}
+
+fn split_lines(text: &str) -> Vec<Chunk<'_>> {
+    lines_with_offsets(text)
+        .enumerate()
+        .map(|(idx, (offset, line))| Chunk {
+            text: line,
+            offset,
+            line: idx + 1,
+        })
+        .collect()
+}
+
+fn split_paragraphs(text: &str) -> Vec<Chunk<'_>> {
+    let mut chunks = Vec::new();
+    let mut current: Option<(usize, usize, usize)> = None;
+
+    for (idx, (offset, line)) in lines_with_offsets(text).enumerate() {
+        if line.trim().is_empty() {
+            if let Some((start, end, first_line)) = current.take() {
+                chunks.push(Chunk {
+                    text: &text[start..end],
+                    offset: start,
+                    line: first_line,
+                });
+            }
+        } else {
+            let end = offset + line.len();
+            current = Some(current.map_or((offset, end, idx + 1), |(start, _, first)| {
+                (start, end, first)
+            }));
+        }
+    }
+
+    if let Some((start, end, first_line)) = current {
+        chunks.push(Chunk {
+            text: &text[start..end],
+            offset: start,
+            line: first_line,
+        });
+    }
+
+    chunks
+}
+
+fn split_bytes(text: &str, size: usize) -> Vec<Chunk<'_>> {
+    assert!(size > 0, "Chunk size must be positive");
+
+    let mut chunks = Vec::new();
+    let mut start = 0;
+    let mut line = 1;
+
+    while start < text.len() {
+        let mut end = (start + size).min(text.len());
+        while !text.is_char_boundary(end) {
+            end -= 1;
+        }
+        if end == start {
+            // A single character wider than the chunk size still gets its own chunk
+            end = start + 1;
+            while !text.is_char_boundary(end) {
+                end += 1;
+            }
+        }
+
+        let piece = &text[start..end];
+        chunks.push(Chunk {
+            text: piece,
+            offset: start,
+            line,
+        });
+        line += piece.bytes().filter(|&b| b == b'\n').count();
+        start = end;
+    }
+
+    chunks
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_split_mode() {
+        assert_eq!("lines".parse::<SplitMode>().unwrap(), SplitMode::Lines);
+        assert_eq!(
+            "paragraphs".parse::<SplitMode>().unwrap(),
+            SplitMode::Paragraphs
+        );
+        assert_eq!(
+            "bytes:64".parse::<SplitMode>().unwrap(),
+            SplitMode::Bytes(64)
+        );
+
+        for bad in ["words", "bytes:", "bytes:0", "bytes:abc", "bytes:70000"] {
+            let err = bad.parse::<SplitMode>().unwrap_err();
+            assert_eq!(
+                err.downcast_ref::<SnapError>().unwrap().code(),
+                ERROR_INVALID_SPLIT
+            );
+        }
+    }
+
+    #[test]
+    fn test_split_lines() {
+        let text = "first line\r\n\nthird line\n   \nfifth";
+        let chunks = split(text, SplitMode::Lines);
+
+        assert_eq!(chunks.len(), 3);
+        assert_eq!(
+            chunks[0],
+            Chunk {
+                text: "first line",
+                offset: 0,
+                line: 1,
+            }
+        );
+        assert_eq!(
+            chunks[1],
+            Chunk {
+                text: "third line",
+                offset: 13,
+                line: 3,
+            }
+        );
+        assert_eq!(chunks[2].text, "fifth");
+        assert_eq!(chunks[2].line, 5);
+        assert_eq!(&text[chunks[2].offset..], "fifth");
+    }
+
+    #[test]
+    fn test_split_paragraphs() {
+        let text = "intro line one\nintro line two\n\n\nsecond para\n  \nthird\n";
+        let chunks = split(text, SplitMode::Paragraphs);
+
+        assert_eq!(chunks.len(), 3);
+        assert_eq!(chunks[0].text, "intro line one\nintro line two");
+        assert_eq!(chunks[0].line, 1);
+        assert_eq!(chunks[1].text, "second para");
+        assert_eq!(chunks[1].line, 5);
+        assert_eq!(chunks[2].text, "third");
+        assert_eq!(chunks[2].line, 7);
+        assert_eq!(&text[chunks[2].offset..chunks[2].offset + 5], "third");
+    }
+
+    #[test]
+    fn test_split_bytes() {
+        let text = "abcdef\nghij";
+        let chunks = split(text, SplitMode::Bytes(4));
+
+        let texts: Vec<_> = chunks.iter().map(|c| c.text).collect();
+        assert_eq!(texts, vec!["abcd", "ef\ng", "hij"]);
+        let offsets: Vec<_> = chunks.iter().map(|c| c.offset).collect();
+        assert_eq!(offsets, vec![0, 4, 8]);
+        let lines: Vec<_> = chunks.iter().map(|c| c.line).collect();
+        assert_eq!(lines, vec![1, 1, 2]);
+    }
+
+    #[test]
+    fn test_split_bytes_respects_char_boundaries() {
+        let text = "aé€b";
+        let chunks = split(text, SplitMode::Bytes(2));
+
+        let texts: Vec<_> = chunks.iter().map(|c| c.text).collect();
+        assert_eq!(texts, vec!["a", "é", "€", "b"]);
+        assert_eq!(texts.concat(), text);
+    }
+}
@@ -1,41 +1,155 @@
+use std::fs::File;
+use std::io::{self, Read};
+use std::path::Path;
+
+use arrayvec::ArrayString;
+
 pub const TEXT_SAMPLE_SIZE: usize = 512;
+pub const MAX_TEXT_SAMPLE_SIZE: usize = 8 * 1024;
+/// Starting confidence for a known text extension. It matches the start for
+/// content alone, so an extension never lowers confidence; it raises it by
+/// excusing the single-line penalty, since one-line `.csv` or `.md` files
+/// are normal.
+pub const EXTENSION_CONFIDENCE_BASELINE: u8 = 100;
+/// Content shorter than this is not penalized for lacking line breaks.
+pub const SHORT_LINE_LENGTH: usize = 80;
+pub const MAX_INTERPRETER_LENGTH: usize = 64;
+
+const KNOWN_TEXT_EXTENSIONS: &[(&str, TextMimeType)] = &[
+    ("txt", TextMimeType::Plain),
+    ("md", TextMimeType::Markdown),
+    ("rs", TextMimeType::Source),
+    ("toml", TextMimeType::Toml),
+    ("json", TextMimeType::Json),
+    ("yaml", TextMimeType::Yaml),
+    ("yml", TextMimeType::Yaml),
+    ("csv", TextMimeType::Plain),
+    ("ini", TextMimeType::Config),
+    ("cfg", TextMimeType::Config),
+    ("sh", TextMimeType::Script),
+    ("py", TextMimeType::Script),
+    ("rb", TextMimeType::Script),
+    ("pl", TextMimeType::Script),
+    ("js", TextMimeType::Source),
+    ("ts", TextMimeType::Source),
+    ("html", TextMimeType::Source),
+    ("xml", TextMimeType::Source),
+];
+
+const SCRIPT_INTERPRETERS: &[&[u8]] =
+    &[b"sh", b"bash", b"zsh", b"python", b"ruby", b"perl", b"node"];
+
+const BINARY_SIGNATURES: &[(&[u8], &str)] = &[
+    (b"PK\x03\x04", "ZIP"),
+    (b"\x7FELF", "ELF"),
+    (b"\x89PNG", "PNG"),
+    (b"%PDF", "PDF"),
+    (b"GIF87a", "GIF"),
+    (b"GIF89a", "GIF"),
+    (b"\xFF\xD8\xFF", "JPEG"),
+    (b"\xCE\xFA\xED\xFE", "Mach-O"),
+    (b"\xCF\xFA\xED\xFE", "Mach-O"),
+    (b"\xFE\xED\xFA\xCE", "Mach-O"),
+    (b"\xFE\xED\xFA\xCF", "Mach-O"),
+    (b"\xCA\xFE\xBA\xBE", "Mach-O"),
+    (b"SQLite format 3\0", "SQLite"),
+    (b"\x1F\x8B", "gzip"),
+    (b"BZh", "bzip2"),
+    (b"\x28\xB5\x2F\xFD", "zstd"),
+    (b"\xFD7zXZ\0", "xz"),
+    (b"\0asm", "WASM"),
+];
+
+/// Sizes of the BMP info headers in use, from `BITMAPCOREHEADER` to
+/// `BITMAPV5HEADER`.
+const BMP_INFO_HEADER_SIZES: &[u32] = &[12, 40, 52, 56, 64, 108, 124];
+
+/// RIFF form types, found at offset 8 of a RIFF file.
+const RIFF_FORMS: &[(&[u8; 4], &str)] = &[(b"WEBP", "WebP"), (b"WAVE", "WAV"), (b"AVI ", "AVI")];
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum TextEncoding {
     Utf8,
+    Utf16Le,
+    Utf16Be,
     Unknown,
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LineEnding {
+    Lf,
+    CrLf,
+    Cr,
+    Mixed,
+    None,
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum TextMimeType {
     Plain,
     Markdown,
     Source,
+    Script,
     Config,
+    Json,
+    Yaml,
+    Toml,
     Unknown,
 }
 
 #[derive(Debug)]
 pub struct TextValidation {
     confidence: u8,
-    #[allow(dead_code)]
     encoding: TextEncoding,
     mime_type: TextMimeType,
+    stats: TextStats,
+    interpreter: Option<ArrayString<MAX_INTERPRETER_LENGTH>>,
+    binary_format: Option<&'static str>,
 }
 
-#[derive(Debug)]
+/// Byte-level counters gathered from a content sample.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct TextStats {
     null_bytes: u16,
     control_chars: u16,
     utf8_errors: u16,
     line_breaks: u16,
+    carriage_returns: u16,
+    crlf_pairs: u16,
     ascii_ratio: u8,
+    byte_counts: [u16; 256],
+    /// The last byte fed was a carriage return, so a line feed completes a pair.
+    after_cr: bool,
+    /// Continuation bytes still expected by the current UTF-8 sequence.
+    utf8_pending: u8,
+    /// Range the next continuation byte must fall in.
+    utf8_next: (u8, u8),
 }
 
 #[derive(Debug)]
 pub struct TextDetector {
     stats: TextStats,
-    sample_buf: [u8; TEXT_SAMPLE_SIZE],
+    sample_buf: [u8; MAX_TEXT_SAMPLE_SIZE],
+    sample_size: usize,
+    sample_len: usize,
+    encoding: TextEncoding,
+    has_bom: bool,
+}
+
+/// A file whose leading sample has been read for validation.
+#[derive(Debug)]
+pub struct SampledFile<R = File> {
+    sample: [u8; MAX_TEXT_SAMPLE_SIZE],
+    sample_len: usize,
+    reader: R,
+    len: u64,
+}
+
+impl TextMimeType {
+    #[must_use]
+    pub const fn is_structured(self) -> bool {
+        matches!(self, Self::Json | Self::Yaml | Self::Toml)
+    }
 }
 
 impl TextValidation {
@@ -45,6 +159,17 @@ impl TextValidation {
             confidence: 0,
             encoding: TextEncoding::Unknown,
             mime_type: TextMimeType::Unknown,
+            stats: TextStats::new(),
+            interpreter: None,
+            binary_format: None,
+        }
+    }
+
+    const fn binary_with_stats(stats: TextStats, binary_format: Option<&'static str>) -> Self {
+        Self {
+            stats,
+            binary_format,
+            ..Self::binary()
         }
     }
 
@@ -58,7 +183,6 @@ impl TextValidation {
         self.confidence
     }
 
-    #[cfg(test)]
     #[must_use]
     pub const fn encoding(&self) -> TextEncoding {
         self.encoding
@@ -68,16 +192,192 @@ impl TextValidation {
     pub const fn mime_type(&self) -> TextMimeType {
         self.mime_type
     }
+
+    #[must_use]
+    pub const fn stats(&self) -> TextStats {
+        self.stats
+    }
+
+    #[must_use]
+    pub const fn line_ending(&self) -> LineEnding {
+        self.stats.line_ending()
+    }
+
+    #[must_use]
+    pub const fn interpreter(&self) -> Option<ArrayString<MAX_INTERPRETER_LENGTH>> {
+        self.interpreter
+    }
+
+    /// Name of the file format whose magic number opened the sample, if any.
+    #[must_use]
+    pub const fn binary_format(&self) -> Option<&'static str> {
+        self.binary_format
+    }
+
+    #[must_use]
+    pub fn decode(&self, content: &[u8]) -> Option<String> {
+        match self.encoding {
+            TextEncoding::Utf8 => std::str::from_utf8(content).ok().map(str::to_owned),
+            TextEncoding::Utf16Le | TextEncoding::Utf16Be => {
+                let body = match content {
+                    [0xFF, 0xFE, rest @ ..] | [0xFE, 0xFF, rest @ ..] => rest,
+                    _ => content,
+                };
+                let units = body.chunks_exact(2).map(|pair| {
+                    if self.encoding == TextEncoding::Utf16Le {
+                        u16::from_le_bytes([pair[0], pair[1]])
+                    } else {
+                        u16::from_be_bytes([pair[0], pair[1]])
+                    }
+                });
+                char::decode_utf16(units)
+                    .collect::<Result<String, _>>()
+                    .ok()
+            }
+            TextEncoding::Unknown => None,
+        }
+    }
+
+    /// Like [`TextValidation::decode`], but replaces invalid sequences with
+    /// U+FFFD instead of failing, so the valid parts of a file with a stray
+    /// byte stay searchable. Content of unknown encoding is read as UTF-8.
+    #[must_use]
+    pub fn decode_lossy(&self, content: &[u8]) -> String {
+        match self.encoding {
+            TextEncoding::Utf8 | TextEncoding::Unknown => {
+                String::from_utf8_lossy(content).into_owned()
+            }
+            TextEncoding::Utf16Le | TextEncoding::Utf16Be => {
+                let body = match content {
+                    [0xFF, 0xFE, rest @ ..] | [0xFE, 0xFF, rest @ ..] => rest,
+                    _ => content,
+                };
+                let units = body.chunks_exact(2).map(|pair| {
+                    if self.encoding == TextEncoding::Utf16Le {
+                        u16::from_le_bytes([pair[0], pair[1]])
+                    } else {
+                        u16::from_be_bytes([pair[0], pair[1]])
+                    }
+                });
+                char::decode_utf16(units)
+                    .map(|c| c.unwrap_or(char::REPLACEMENT_CHARACTER))
+                    .collect()
+            }
+        }
+    }
+}
+
+impl<R: Read> SampledFile<R> {
+    #[must_use]
+    pub fn sample(&self) -> &[u8] {
+        &self.sample[..self.sample_len]
+    }
+
+    /// Reads the whole content: the sample followed by the unread remainder.
+    ///
+    /// Never reads past the length the file had when it was sampled.
+    pub fn read_content(mut self) -> io::Result<Vec<u8>> {
+        let capacity = usize::try_from(self.len).unwrap_or(self.sample_len);
+        let mut content = Vec::with_capacity(capacity.max(self.sample_len));
+        content.extend_from_slice(self.sample());
+
+        let remaining = self.len.saturating_sub(self.sample_len as u64);
+        (&mut self.reader)
+            .take(remaining)
+            .read_to_end(&mut content)?;
+        Ok(content)
+    }
+
+    fn fill_sample(&mut self, sample_size: usize) -> io::Result<()> {
+        let want = sample_size.min(usize::try_from(self.len).unwrap_or(usize::MAX));
+        while self.sample_len < want {
+            match self.reader.read(&mut self.sample[self.sample_len..want]) {
+                Ok(0) => break,
+                Ok(n) => self.sample_len += n,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Default for TextStats {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl TextStats {
-    const fn new() -> Self {
+    #[must_use]
+    pub const fn new() -> Self {
         Self {
             null_bytes: 0,
             control_chars: 0,
             utf8_errors: 0,
             line_breaks: 0,
+            carriage_returns: 0,
+            crlf_pairs: 0,
             ascii_ratio: 0,
+            byte_counts: [0; 256],
+            after_cr: false,
+            utf8_pending: 0,
+            utf8_next: (0, 0),
+        }
+    }
+
+    #[must_use]
+    pub const fn null_bytes(&self) -> u16 {
+        self.null_bytes
+    }
+
+    #[must_use]
+    pub const fn control_chars(&self) -> u16 {
+        self.control_chars
+    }
+
+    #[must_use]
+    pub const fn utf8_errors(&self) -> u16 {
+        self.utf8_errors
+    }
+
+    #[must_use]
+    pub const fn line_breaks(&self) -> u16 {
+        self.line_breaks
+    }
+
+    #[must_use]
+    pub const fn carriage_returns(&self) -> u16 {
+        self.carriage_returns
+    }
+
+    #[must_use]
+    pub const fn crlf_pairs(&self) -> u16 {
+        self.crlf_pairs
+    }
+
+    #[must_use]
+    pub const fn ascii_ratio(&self) -> u8 {
+        self.ascii_ratio
+    }
+
+    /// How many times each byte value was fed.
+    #[must_use]
+    pub const fn byte_counts(&self) -> &[u16; 256] {
+        &self.byte_counts
+    }
+
+    #[must_use]
+    pub const fn line_ending(&self) -> LineEnding {
+        let lf = self.line_breaks > self.crlf_pairs;
+        let crlf = self.crlf_pairs > 0;
+        let cr = self.carriage_returns > self.crlf_pairs;
+        match (lf, crlf, cr) {
+            (false, false, false) => LineEnding::None,
+            (true, false, false) => LineEnding::Lf,
+            (false, true, false) => LineEnding::CrLf,
+            (false, false, true) => LineEnding::Cr,
+            _ => LineEnding::Mixed,
         }
     }
 
@@ -85,28 +385,107 @@ impl TextStats {
         *self = Self::new();
     }
 
-    fn update(&mut self, byte: u8) {
+    /// Shannon entropy of the fed bytes in bits per byte, from 0.0 to 8.0.
+    ///
+    /// Text usually lands well below 6; compressed and encrypted data sit close to 8.
+    #[must_use]
+    pub fn entropy(&self) -> f32 {
+        let total: u32 = self.byte_counts.iter().map(|&c| u32::from(c)).sum();
+        if total == 0 {
+            return 0.0;
+        }
+
+        #[allow(clippy::cast_precision_loss)]
+        let total = total as f32;
+        let entropy: f32 = self
+            .byte_counts
+            .iter()
+            .filter(|&&count| count > 0)
+            .map(|&count| {
+                let p = f32::from(count) / total;
+                -p * p.log2()
+            })
+            .sum();
+        // Rounding in the sum can land just outside the possible range
+        entropy.clamp(0.0, 8.0)
+    }
+
+    pub fn feed(&mut self, byte: u8) {
         if byte == 0 {
-            assert!(self.null_bytes < u16::try_from(TEXT_SAMPLE_SIZE).unwrap());
-            self.null_bytes += 1;
+            self.null_bytes = self.null_bytes.saturating_add(1);
         }
 
         if byte < 32 && !matches!(byte, b'\n' | b'\r' | b'\t') {
-            assert!(self.control_chars < u16::try_from(TEXT_SAMPLE_SIZE).unwrap());
-            self.control_chars += 1;
+            self.control_chars = self.control_chars.saturating_add(1);
         }
 
         if byte == b'\n' {
-            assert!(self.line_breaks < u16::try_from(TEXT_SAMPLE_SIZE).unwrap());
-            self.line_breaks += 1;
+            self.line_breaks = self.line_breaks.saturating_add(1);
         }
 
-        if byte < 128 {
-            self.ascii_ratio =
-                u8::try_from((u16::from(self.ascii_ratio) * 99 + 100) / 100).unwrap();
-        } else {
-            self.ascii_ratio = u8::try_from((u16::from(self.ascii_ratio) * 99) / 100).unwrap();
+        if byte == b'\r' {
+            self.carriage_returns = self.carriage_returns.saturating_add(1);
+        }
+
+        if byte == b'\n' && self.after_cr {
+            self.crlf_pairs = self.crlf_pairs.saturating_add(1);
+        }
+        self.after_cr = byte == b'\r';
+
+        self.feed_utf8(byte);
+
+        let count = &mut self.byte_counts[usize::from(byte)];
+        *count = count.saturating_add(1);
+    }
+
+    /// Counts invalid UTF-8 sequences the way `str::from_utf8` splits them. A
+    /// sequence still incomplete when feeding stops is not counted, since the
+    /// sample may cut a character in two.
+    fn feed_utf8(&mut self, byte: u8) {
+        if self.utf8_pending > 0 {
+            let (low, high) = self.utf8_next;
+            if (low..=high).contains(&byte) {
+                self.utf8_pending -= 1;
+                self.utf8_next = (0x80, 0xBF);
+                return;
+            }
+            // The byte ends the broken sequence and may start the next one
+            self.utf8_pending = 0;
+            self.utf8_errors = self.utf8_errors.saturating_add(1);
+        }
+
+        (self.utf8_pending, self.utf8_next) = match byte {
+            0x00..=0x7F => (0, (0, 0)),
+            0xC2..=0xDF => (1, (0x80, 0xBF)),
+            0xE0 => (2, (0xA0, 0xBF)),
+            0xE1..=0xEC | 0xEE..=0xEF => (2, (0x80, 0xBF)),
+            0xED => (2, (0x80, 0x9F)),
+            0xF0 => (3, (0x90, 0xBF)),
+            0xF1..=0xF3 => (3, (0x80, 0xBF)),
+            0xF4 => (3, (0x80, 0x8F)),
+            _ => {
+                self.utf8_errors = self.utf8_errors.saturating_add(1);
+                (0, (0, 0))
+            }
+        };
+    }
+
+    pub fn feed_slice(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.feed(byte);
         }
+    }
+
+    /// Sets `ascii_ratio` from the bytes fed so far. Called once the whole
+    /// sample has been fed.
+    pub fn update_ascii_ratio(&mut self) {
+        let total: u32 = self.byte_counts.iter().map(|&c| u32::from(c)).sum();
+        if total == 0 {
+            self.ascii_ratio = 0;
+            return;
+        }
+        let ascii: u32 = self.byte_counts[..128].iter().map(|&c| u32::from(c)).sum();
+        self.ascii_ratio = u8::try_from(ascii * 100 / total).unwrap();
         assert!(self.ascii_ratio <= 100);
     }
 }
@@ -122,29 +501,121 @@ impl TextDetector {
     pub const fn new() -> Self {
         Self {
             stats: TextStats::new(),
-            sample_buf: [0; TEXT_SAMPLE_SIZE],
+            sample_buf: [0; MAX_TEXT_SAMPLE_SIZE],
+            sample_size: TEXT_SAMPLE_SIZE,
+            sample_len: 0,
+            encoding: TextEncoding::Utf8,
+            has_bom: false,
         }
     }
 
+    #[must_use]
+    pub const fn with_sample_size(mut self, size: usize) -> Self {
+        self.sample_size = if size == 0 {
+            1
+        } else if size > MAX_TEXT_SAMPLE_SIZE {
+            MAX_TEXT_SAMPLE_SIZE
+        } else {
+            size
+        };
+        self
+    }
+
+    #[must_use]
+    pub const fn sample_size(&self) -> usize {
+        self.sample_size
+    }
+
+    /// Stats gathered by the most recent `validate` call.
+    #[must_use]
+    pub const fn last_stats(&self) -> &TextStats {
+        &self.stats
+    }
+
     #[must_use]
     pub fn validate(&mut self, content: &[u8]) -> TextValidation {
-        if !Self::check_basic_validity(content) {
-            return TextValidation::binary();
+        let sample = &content[..content.len().min(self.sample_size)];
+        self.validate_sample(sample, content.len(), None)
+    }
+
+    #[must_use]
+    pub fn validate_with_extension(&mut self, content: &[u8], ext: &str) -> TextValidation {
+        let ext = ext.strip_prefix('.').unwrap_or(ext);
+        let Some(&(_, hint)) = KNOWN_TEXT_EXTENSIONS
+            .iter()
+            .find(|(known, _)| known.eq_ignore_ascii_case(ext))
+        else {
+            return self.validate(content);
+        };
+
+        let sample = &content[..content.len().min(self.sample_size)];
+        self.validate_sample(sample, content.len(), Some(hint))
+    }
+
+    /// Validates a file from its leading sample without reading the rest of it.
+    ///
+    /// The returned [`SampledFile`] holds the sample and the open file, so callers
+    /// only pay for reading the remainder when the file turns out to be text.
+    pub fn validate_file(&mut self, path: &Path) -> io::Result<(TextValidation, SampledFile)> {
+        let file = File::open(path)?;
+        let len = file.metadata()?.len();
+        self.validate_reader(file, len)
+    }
+
+    /// Like [`TextDetector::validate_file`] for any reader of `len` bytes.
+    pub fn validate_reader<R: Read>(
+        &mut self,
+        reader: R,
+        len: u64,
+    ) -> io::Result<(TextValidation, SampledFile<R>)> {
+        let content_len = usize::try_from(len).unwrap_or(usize::MAX);
+        let mut sampled = SampledFile {
+            sample: [0; MAX_TEXT_SAMPLE_SIZE],
+            sample_len: 0,
+            reader,
+            len,
+        };
+
+        // Oversized content is rejected on its length alone, so skip the read
+        if Self::check_basic_validity(content_len) {
+            sampled.fill_sample(self.sample_size)?;
         }
 
-        if !self.analyze_content(content) {
+        let validation = self.validate_sample(sampled.sample(), content_len, None);
+        Ok((validation, sampled))
+    }
+
+    fn validate_sample(
+        &mut self,
+        sample: &[u8],
+        content_len: usize,
+        hint: Option<TextMimeType>,
+    ) -> TextValidation {
+        assert!(
+            sample.len() <= self.sample_size,
+            "Sample exceeds sample size"
+        );
+
+        self.sample_len = 0;
+        self.stats.reset();
+        if !Self::check_basic_validity(content_len) || sample.is_empty() {
             return TextValidation::binary();
         }
 
-        self.determine_result()
+        if !self.analyze_content(sample) {
+            return TextValidation::binary_with_stats(self.stats, self.detected_binary_format());
+        }
+
+        self.count_line_endings(content_len);
+        self.determine_result(content_len, hint)
     }
 
-    const fn check_basic_validity(content: &[u8]) -> bool {
-        if content.is_empty() {
+    const fn check_basic_validity(content_len: usize) -> bool {
+        if content_len == 0 {
             return false;
         }
 
-        if content.len() > TEXT_SAMPLE_SIZE * 1024 {
+        if content_len > TEXT_SAMPLE_SIZE * 1024 {
             return false;
         }
 
@@ -154,33 +625,112 @@ impl TextDetector {
     fn analyze_content(&mut self, content: &[u8]) -> bool {
         self.stats.reset();
 
-        let sample_size = content.len().min(TEXT_SAMPLE_SIZE);
-        self.sample_buf[..sample_size].copy_from_slice(&content[..sample_size]);
+        let sample_size = content.len().min(self.sample_size);
+        (self.encoding, self.has_bom) = Self::detect_encoding(&content[..sample_size]);
 
-        for &byte in &content[..sample_size] {
-            self.stats.update(byte);
+        if matches!(self.encoding, TextEncoding::Utf16Le | TextEncoding::Utf16Be) {
+            return self.analyze_utf16(&content[..sample_size]);
         }
 
+        self.sample_buf[..sample_size].copy_from_slice(&content[..sample_size]);
+        self.sample_buf[sample_size..].fill(0);
+        self.sample_len = sample_size;
+
+        self.stats.feed_slice(&content[..sample_size]);
+
         if self.stats.null_bytes > u16::try_from(sample_size).unwrap_or(u16::MAX) / 10 {
             return false;
         }
 
-        let ascii_count = content[..sample_size].iter().filter(|&&b| b < 128).count();
-        self.stats.ascii_ratio = u8::try_from((ascii_count * 100) / sample_size).unwrap();
+        self.stats.update_ascii_ratio();
+
+        true
+    }
+
+    fn count_line_endings(&mut self, content_len: usize) {
+        let sample = &self.sample_buf[..self.sample_len];
+        if content_len > self.sample_size && sample.ends_with(b"\r") {
+            self.stats.carriage_returns = self.stats.carriage_returns.saturating_sub(1);
+        }
+    }
+
+    fn analyze_utf16(&mut self, sample: &[u8]) -> bool {
+        let body = if self.has_bom { &sample[2..] } else { sample };
+        let is_le = self.encoding == TextEncoding::Utf16Le;
+        let units = body.chunks_exact(2).map(|pair| {
+            if is_le {
+                u16::from_le_bytes([pair[0], pair[1]])
+            } else {
+                u16::from_be_bytes([pair[0], pair[1]])
+            }
+        });
+
+        let mut unit_count = 0_usize;
+        for (i, unit) in units.clone().enumerate() {
+            let byte = u8::try_from(unit).unwrap_or(u8::MAX);
+            self.sample_buf[i] = byte;
+            self.stats.feed(byte);
+            unit_count += 1;
+        }
+        self.sample_buf[unit_count..].fill(0);
+        self.sample_len = unit_count;
 
-        if let Err(e) = std::str::from_utf8(&content[..sample_size]) {
-            self.stats.utf8_errors = u16::try_from(e.valid_up_to()).unwrap();
+        if unit_count == 0 {
+            return false;
         }
 
+        if self.stats.null_bytes > u16::try_from(unit_count).unwrap_or(u16::MAX) / 10 {
+            return false;
+        }
+
+        self.stats.update_ascii_ratio();
+
+        let errors = char::decode_utf16(units).filter(Result::is_err).count();
+        self.stats.utf8_errors = u16::try_from(errors).unwrap_or(u16::MAX);
+
         true
     }
 
-    fn determine_result(&self) -> TextValidation {
-        if self.is_binary_header() || self.stats.null_bytes > 0 {
-            return TextValidation::binary();
+    fn detect_encoding(sample: &[u8]) -> (TextEncoding, bool) {
+        match sample {
+            [0xFF, 0xFE, ..] => return (TextEncoding::Utf16Le, true),
+            [0xFE, 0xFF, ..] => return (TextEncoding::Utf16Be, true),
+            _ => {}
+        }
+
+        let pairs = sample.len() / 2;
+        if pairs < 2 {
+            return (TextEncoding::Utf8, false);
+        }
+
+        let even_nulls = sample.iter().step_by(2).filter(|&&b| b == 0).count();
+        let odd_nulls = sample
+            .iter()
+            .skip(1)
+            .step_by(2)
+            .filter(|&&b| b == 0)
+            .count();
+
+        if odd_nulls * 10 >= pairs * 9 && even_nulls * 10 <= pairs {
+            (TextEncoding::Utf16Le, false)
+        } else if even_nulls * 10 >= pairs * 9 && odd_nulls * 10 <= pairs {
+            (TextEncoding::Utf16Be, false)
+        } else {
+            (TextEncoding::Utf8, false)
+        }
+    }
+
+    fn determine_result(&self, content_len: usize, hint: Option<TextMimeType>) -> TextValidation {
+        let binary_format = self.detected_binary_format();
+        if binary_format.is_some() || self.stats.null_bytes > 0 {
+            return TextValidation::binary_with_stats(self.stats, binary_format);
         }
 
-        let mut confidence = 100_u8;
+        let mut confidence = if hint.is_some() {
+            EXTENSION_CONFIDENCE_BASELINE
+        } else {
+            100_u8
+        };
 
         if self.stats.control_chars > 0 {
             confidence =
@@ -192,18 +742,46 @@ impl TextDetector {
                 confidence.saturating_sub(u8::try_from(self.stats.utf8_errors * 10).unwrap_or(100));
         }
 
-        if self.stats.line_breaks < 2 {
-            confidence = confidence.saturating_sub(20);
+        if self.stats.line_breaks < 2 && hint.is_none() {
+            confidence = confidence.saturating_sub(single_line_penalty(content_len));
         }
 
         if self.stats.ascii_ratio < 90 {
             confidence = confidence.saturating_sub(90_u8.saturating_sub(self.stats.ascii_ratio));
         }
 
-        let mime_type = if self.stats.line_breaks == 0 {
+        if self.encoding != TextEncoding::Utf8 && !self.has_bom {
+            confidence = confidence.saturating_sub(10);
+        }
+
+        TextValidation {
+            confidence: confidence.min(100),
+            encoding: self.encoding,
+            mime_type: hint.unwrap_or_else(|| self.detect_mime_type()),
+            stats: self.stats,
+            interpreter: Self::parse_interpreter(self.sample()),
+            binary_format: None,
+        }
+    }
+
+    fn detect_mime_type(&self) -> TextMimeType {
+        let sample = self.sample();
+        if self.stats.line_breaks == 0 {
             TextMimeType::Plain
-        } else if self.sample_buf.starts_with(b"#!") || self.sample_buf.starts_with(b"<?") {
+        } else if self.sample_buf.starts_with(b"#!") {
+            if Self::is_script_shebang(sample) {
+                TextMimeType::Script
+            } else {
+                TextMimeType::Source
+            }
+        } else if self.sample_buf.starts_with(b"<?") {
             TextMimeType::Source
+        } else if Self::looks_like_toml(sample) {
+            TextMimeType::Toml
+        } else if Self::looks_like_json(sample) {
+            TextMimeType::Json
+        } else if self.sample_buf.starts_with(b"---") || Self::looks_like_yaml(sample) {
+            TextMimeType::Yaml
         } else if self.sample_buf.starts_with(b"[")
             || (self.sample_buf.starts_with(b"# ") && self.sample_buf.contains(&b'['))
         {
@@ -225,23 +803,180 @@ impl TextDetector {
             TextMimeType::Source
         } else {
             TextMimeType::Plain
+        }
+    }
+
+    fn sample(&self) -> &[u8] {
+        &self.sample_buf[..self.sample_len]
+    }
+
+    fn parse_interpreter(sample: &[u8]) -> Option<ArrayString<MAX_INTERPRETER_LENGTH>> {
+        let rest = sample.strip_prefix(b"#!")?;
+        let end = rest.iter().position(|&b| b == b'\n')?;
+        let line = &rest[..end];
+        let line = std::str::from_utf8(line).ok()?;
+
+        let mut tokens = line.split_ascii_whitespace();
+        let program = tokens.next()?;
+        let mut name = program.rsplit('/').next().unwrap_or(program);
+        if name == "env" {
+            name = tokens.find(|t| !t.starts_with('-') && !t.contains('='))?;
+        }
+
+        ArrayString::from(name).ok()
+    }
+
+    /// Whether the shebang names a known interpreter, ignoring a version
+    /// suffix such as `python3.12`.
+    fn is_script_shebang(sample: &[u8]) -> bool {
+        Self::parse_interpreter(sample).is_some_and(|name| {
+            let name = name.trim_end_matches(|c: char| c.is_ascii_digit() || c == '.');
+            SCRIPT_INTERPRETERS.contains(&name.as_bytes())
+        })
+    }
+
+    fn looks_like_json(sample: &[u8]) -> bool {
+        let trimmed = sample.trim_ascii_start();
+        if !(trimmed.starts_with(b"{") || trimmed.starts_with(b"["))
+            || !trimmed.contains(&b':')
+            || !trimmed.contains(&b'"')
+        {
+            return false;
+        }
+
+        let mut depth = 0_usize;
+        let mut in_string = false;
+        let mut escaped = false;
+        for &b in trimmed {
+            match (in_string, escaped, b) {
+                (true, true, _) => escaped = false,
+                (true, false, b'\\') => escaped = true,
+                (true, false, b'"') | (false, _, b'"') => in_string = !in_string,
+                (false, _, b'{' | b'[') => depth += 1,
+                (false, _, b'}' | b']') => match depth.checked_sub(1) {
+                    Some(d) => depth = d,
+                    None => return false,
+                },
+                _ => {}
+            }
+        }
+        true
+    }
+
+    fn looks_like_toml(sample: &[u8]) -> bool {
+        let is_bare_key = |key: &[u8]| {
+            !key.is_empty()
+                && key
+                    .iter()
+                    .all(|&b| b.is_ascii_alphanumeric() || matches!(b, b'_' | b'-' | b'.'))
         };
 
-        TextValidation {
-            confidence: confidence.min(100),
-            encoding: TextEncoding::Utf8,
-            mime_type,
+        let mut has_table = false;
+        let mut has_string_pair = false;
+        for line in sample.split(|&b| b == b'\n').map(<[u8]>::trim_ascii) {
+            if let Some(header) = line.strip_prefix(b"[").and_then(|l| l.strip_suffix(b"]")) {
+                let header = header
+                    .strip_prefix(b"[")
+                    .and_then(|h| h.strip_suffix(b"]"))
+                    .unwrap_or(header);
+                has_table |= is_bare_key(header.trim_ascii());
+            } else if let Some(eq) = line.iter().position(|&b| b == b'=') {
+                let value = line[eq + 1..].trim_ascii_start();
+                has_string_pair |= is_bare_key(line[..eq].trim_ascii())
+                    && (value.starts_with(b"\"") || value.starts_with(b"'"));
+            }
         }
+        has_table && has_string_pair
     }
 
-    fn is_binary_header(&self) -> bool {
-        let sample = &self.sample_buf[..4];
-        matches!(sample, b"PK\x03\x04" | b"\x7FELF" | b"\x89PNG")
+    fn looks_like_yaml(sample: &[u8]) -> bool {
+        if sample
+            .iter()
+            .any(|b| matches!(b, b'{' | b'}' | b';' | b'='))
+        {
+            return false;
+        }
+
+        let line_count = sample.split(|&b| b == b'\n').count();
+        let complete_lines = line_count.saturating_sub(1).max(1);
+
+        let mut has_key = false;
+        for line in sample.split(|&b| b == b'\n').take(complete_lines) {
+            let rest = line.trim_ascii();
+            if rest.is_empty() || rest.starts_with(b"#") || rest == b"---" {
+                continue;
+            }
+            if rest.starts_with(b"- ") {
+                continue;
+            }
+
+            let key_len = rest
+                .iter()
+                .take_while(|&&b| b.is_ascii_alphanumeric() || matches!(b, b'_' | b'-'))
+                .count();
+            let after = &rest[key_len..];
+            if key_len == 0 || !(after == b":" || after.starts_with(b": ")) {
+                return false;
+            }
+            has_key = true;
+        }
+        has_key
+    }
+
+    #[must_use]
+    pub fn detected_binary_format(&self) -> Option<&'static str> {
+        let sample = self.sample();
+        BINARY_SIGNATURES
+            .iter()
+            .find(|(signature, _)| sample.starts_with(signature))
+            .map(|(_, name)| *name)
+            .or_else(|| Self::structured_format(sample))
+    }
+
+    /// Formats whose first bytes alone are too short or too common to tell
+    /// them from text, so a second header field must agree: "BM" needs a
+    /// known info header size, "RIFF" a form type and "MZ" a PE header.
+    fn structured_format(sample: &[u8]) -> Option<&'static str> {
+        let u32_at = |offset: usize| {
+            sample
+                .get(offset..offset + 4)
+                .map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+        };
+
+        if sample.starts_with(b"BM")
+            && sample.get(6..10) == Some(&[0; 4])
+            && u32_at(14).is_some_and(|size| BMP_INFO_HEADER_SIZES.contains(&size))
+        {
+            return Some("BMP");
+        }
+        if sample.starts_with(b"RIFF") {
+            let form = sample.get(8..12)?;
+            return RIFF_FORMS
+                .iter()
+                .find(|(tag, _)| form == *tag)
+                .map(|(_, name)| *name);
+        }
+        if sample.starts_with(b"MZ") {
+            let pe_offset = usize::try_from(u32_at(0x3C)?).ok()?;
+            return (sample.get(pe_offset..pe_offset.checked_add(4)?) == Some(b"PE\0\0"))
+                .then_some("PE");
+        }
+        None
     }
 }
 
+/// Confidence lost by content with fewer than two line breaks. Short files
+/// like `version.txt` are often a single line, so the penalty grows with
+/// length and reaches its maximum at a few lines' worth of bytes.
+fn single_line_penalty(content_len: usize) -> u8 {
+    let lines_worth = content_len / SHORT_LINE_LENGTH;
+    u8::try_from(lines_worth * 5).unwrap_or(u8::MAX).min(20)
+}
+
 #[cfg(test)]
 mod tests {
+    use std::cell::Cell;
+
     use super::*;
 
     #[test]
@@ -294,6 +1029,52 @@ mod tests {
         assert_eq!(result.mime_type(), TextMimeType::Config);
     }
 
+    #[test]
+    fn test_json_file() {
+        let mut detector = TextDetector::new();
+        let content = b"{\n  \"name\": \"snapfind\",\n  \"version\": 1\n}\n";
+        let result = detector.validate(content);
+        assert!(result.is_valid_text());
+        assert_eq!(result.mime_type(), TextMimeType::Json);
+        assert!(result.mime_type().is_structured());
+
+        let content = b"[\n  {\"id\": 1},\n  {\"id\": 2}\n]\n";
+        assert_eq!(detector.validate(content).mime_type(), TextMimeType::Json);
+
+        let content = b"{\"a\": \"}}\"}\n}\n}\n";
+        assert_ne!(detector.validate(content).mime_type(), TextMimeType::Json);
+    }
+
+    #[test]
+    fn test_yaml_file() {
+        let mut detector = TextDetector::new();
+        let content = b"---\nname: snapfind\nversion: 1\n";
+        let result = detector.validate(content);
+        assert!(result.is_valid_text());
+        assert_eq!(result.mime_type(), TextMimeType::Yaml);
+
+        let content = b"jobs:\n  build:\n    runs-on: ubuntu-latest\n";
+        assert_eq!(detector.validate(content).mime_type(), TextMimeType::Yaml);
+
+        let content = b"name: snapfind\nkeywords:\n  - search\n  - files\n";
+        assert_eq!(detector.validate(content).mime_type(), TextMimeType::Yaml);
+
+        let content = b"Dear team:\nthe build is green again.\n";
+        assert_ne!(detector.validate(content).mime_type(), TextMimeType::Yaml);
+    }
+
+    #[test]
+    fn test_toml_file() {
+        let mut detector = TextDetector::new();
+        let content = b"[package]\nname = \"snapfind\"\nurl = \"https://example.com\"\n";
+        let result = detector.validate(content);
+        assert!(result.is_valid_text());
+        assert_eq!(result.mime_type(), TextMimeType::Toml);
+
+        let content = b"[[bin]]\nname = 'snap'\n";
+        assert_eq!(detector.validate(content).mime_type(), TextMimeType::Toml);
+    }
+
     #[test]
     fn test_binary_content() {
         let mut detector = TextDetector::new();
@@ -305,6 +1086,493 @@ mod tests {
         assert_eq!(result.mime_type(), TextMimeType::Unknown);
     }
 
+    #[test]
+    fn test_binary_signatures() {
+        let mut detector = TextDetector::new();
+        let cases: &[(&[u8], &str)] = &[
+            (b"%PDF-1.7\n%\xE2\xE3\xCF\xD3\n", "PDF"),
+            (b"GIF89a\x01\x01\x01\x01", "GIF"),
+            (b"\xFF\xD8\xFF\xE0JFIF", "JPEG"),
+            (b"BM\x36\x10\x0E\x01\0\0\0\0\x36\0\0\0\x28\0\0\0", "BMP"),
+            (b"RIFF\x24\x08\x01\x01WEBPVP8 ", "WebP"),
+            (b"RIFF\x24\x08\x01\x01WAVEfmt ", "WAV"),
+            (b"\xCF\xFA\xED\xFE\x07\x01\x01\x01", "Mach-O"),
+            (b"\xCA\xFE\xBA\xBE\x01\x01\x01\x02", "Mach-O"),
+            (&pe_header(), "PE"),
+            (b"SQLite format 3\0\x10\x01\x01\x01", "SQLite"),
+        ];
+
+        for &(content, name) in cases {
+            let result = detector.validate(content);
+            assert!(!result.is_valid_text(), "{name} should be binary");
+            assert_eq!(detector.detected_binary_format(), Some(name));
+        }
+
+        let result = detector.validate(b"Just text\nwith lines\n");
+        assert!(result.is_valid_text());
+        assert_eq!(detector.detected_binary_format(), None);
+    }
+
+    /// A DOS stub whose header points at a PE signature.
+    fn pe_header() -> Vec<u8> {
+        let mut header = vec![0x01; 0x84];
+        header[..2].copy_from_slice(b"MZ");
+        header[0x3C..0x40].copy_from_slice(&0x80_u32.to_le_bytes());
+        header[0x80..].copy_from_slice(b"PE\0\0");
+        header
+    }
+
+    #[test]
+    fn test_short_magic_alone_is_not_binary() {
+        let mut detector = TextDetector::new();
+        for content in [
+            "BMI calculator\nEnter your weight and height.\n",
+            "MZ-80K notes\nSharp's home computer from 1978.\n",
+            "RIFF ideas for the chorus\nkeep it simple\n",
+        ] {
+            let result = detector.validate(content.as_bytes());
+            assert!(result.is_valid_text(), "{content:?} should be text");
+            assert_eq!(detector.detected_binary_format(), None);
+        }
+    }
+
+    fn assert_binary_signature(content: &[u8], name: &str) {
+        let mut detector = TextDetector::new();
+        let result = detector.validate(content);
+        assert!(!result.is_valid_text(), "{name} should be binary");
+        assert_eq!(result.confidence(), 0);
+        assert_eq!(result.binary_format(), Some(name));
+        assert_eq!(detector.detected_binary_format(), Some(name));
+    }
+
+    #[test]
+    fn test_jpeg_signature() {
+        assert_binary_signature(b"\xFF\xD8\xFF\xDB\x43\x01\x02\x03\n\n", "JPEG");
+    }
+
+    #[test]
+    fn test_gif_signature() {
+        assert_binary_signature(b"GIF87a\n\x01\x01\n", "GIF");
+    }
+
+    #[test]
+    fn test_pdf_signature() {
+        assert_binary_signature(b"%PDF-1.4\n1 0 obj\n<< /Type /Catalog >>\n", "PDF");
+    }
+
+    #[test]
+    fn test_mach_o_signatures() {
+        assert_binary_signature(b"\xCA\xFE\xBA\xBE\x01\x02\n\n", "Mach-O");
+        assert_binary_signature(b"\xCF\xFA\xED\xFE\x07\x01\n\n", "Mach-O");
+    }
+
+    #[test]
+    fn test_gzip_signature() {
+        assert_binary_signature(b"\x1F\x8B\x08\x08\x01\x02\n\n", "gzip");
+    }
+
+    #[test]
+    fn test_bzip2_signature() {
+        assert_binary_signature(b"BZh91AY&SY\x01\x02\n\n", "bzip2");
+    }
+
+    #[test]
+    fn test_zstd_signature() {
+        assert_binary_signature(b"\x28\xB5\x2F\xFD\x24\x05\n\n", "zstd");
+    }
+
+    #[test]
+    fn test_xz_signature() {
+        assert_binary_signature(b"\xFD7zXZ\0\0\x04\xE6\xD6\xB4\x46", "xz");
+    }
+
+    #[test]
+    fn test_wasm_signature() {
+        assert_binary_signature(b"\0asm\x01\0\0\0\x01\x07", "WASM");
+    }
+
+    #[test]
+    fn test_big_endian_mach_o_signatures() {
+        assert_binary_signature(b"\xFE\xED\xFA\xCE\x01\x02\n\n", "Mach-O");
+        assert_binary_signature(b"\xFE\xED\xFA\xCF\x01\x02\n\n", "Mach-O");
+    }
+
+    #[test]
+    fn test_gif_requires_full_version() {
+        let mut detector = TextDetector::new();
+        let result = detector.validate(b"GIF8 is not a version\nsecond line\n");
+        assert!(result.is_valid_text());
+        assert_eq!(result.binary_format(), None);
+    }
+
+    #[test]
+    fn test_validation_reports_binary_format() {
+        let mut detector = TextDetector::new();
+        // Plenty of nulls: rejected before the header check in determine_result
+        let result = detector.validate(b"PK\x03\x04\0\0\0\0\0\0\0\0");
+        assert_eq!(result.binary_format(), Some("ZIP"));
+
+        let result = detector.validate(b"%PDF-1.7\nno nulls here\n");
+        assert_eq!(result.binary_format(), Some("PDF"));
+
+        let result = detector.validate(b"\x01\0\0\0\0\0\0\0\0\0");
+        assert!(!result.is_valid_text());
+        assert_eq!(result.binary_format(), None);
+    }
+
+    #[test]
+    fn test_signature_longer_than_content() {
+        let mut detector = TextDetector::new();
+        let result = detector.validate(b"SQLite format");
+        assert!(result.is_valid_text());
+        assert_eq!(detector.detected_binary_format(), None);
+    }
+
+    struct CountingReader<'a> {
+        inner: io::Cursor<Vec<u8>>,
+        bytes_read: &'a Cell<usize>,
+    }
+
+    impl Read for CountingReader<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            let n = self.inner.read(buf)?;
+            self.bytes_read.set(self.bytes_read.get() + n);
+            Ok(n)
+        }
+    }
+
+    fn counting_reader(content: Vec<u8>, bytes_read: &Cell<usize>) -> CountingReader<'_> {
+        CountingReader {
+            inner: io::Cursor::new(content),
+            bytes_read,
+        }
+    }
+
+    #[test]
+    fn test_validate_reader_only_reads_sample_of_binary() {
+        let mut content = b"\x7FELF\x02\x01\x01\x00".to_vec();
+        content.resize(400 * 1024, 0xAB);
+        let len = content.len() as u64;
+
+        let bytes_read = Cell::new(0);
+        let mut detector = TextDetector::new();
+        let (validation, sampled) = detector
+            .validate_reader(counting_reader(content, &bytes_read), len)
+            .unwrap();
+        assert!(!validation.is_valid_text());
+        assert_eq!(sampled.sample().len(), TEXT_SAMPLE_SIZE);
+        assert_eq!(bytes_read.get(), TEXT_SAMPLE_SIZE);
+
+        let bytes_read = Cell::new(0);
+        let oversized = vec![b'a'; TEXT_SAMPLE_SIZE * 1024 + 1];
+        let len = oversized.len() as u64;
+        let (validation, _) = detector
+            .validate_reader(counting_reader(oversized, &bytes_read), len)
+            .unwrap();
+        assert!(!validation.is_valid_text());
+        assert_eq!(bytes_read.get(), 0);
+    }
+
+    #[test]
+    fn test_validate_reader_reads_remaining_text() {
+        let content = "line of plain text\n".repeat(100).into_bytes();
+        let len = content.len() as u64;
+
+        let bytes_read = Cell::new(0);
+        let mut detector = TextDetector::new();
+        let (validation, sampled) = detector
+            .validate_reader(counting_reader(content.clone(), &bytes_read), len)
+            .unwrap();
+        assert!(validation.is_valid_text());
+        assert_eq!(validation.stats(), detector.validate(&content).stats());
+        assert_eq!(bytes_read.get(), TEXT_SAMPLE_SIZE);
+
+        assert_eq!(sampled.read_content().unwrap(), content);
+        assert_eq!(bytes_read.get(), content.len());
+    }
+
+    #[test]
+    fn test_validate_file() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let path = temp_dir.path().join("notes.txt");
+        std::fs::write(&path, "first line\nsecond line\n").unwrap();
+
+        let mut detector = TextDetector::new();
+        let (validation, sampled) = detector.validate_file(&path).unwrap();
+        assert!(validation.is_valid_text());
+        assert_eq!(
+            sampled.read_content().unwrap(),
+            b"first line\nsecond line\n"
+        );
+
+        let empty = temp_dir.path().join("empty.txt");
+        std::fs::write(&empty, "").unwrap();
+        let (validation, sampled) = detector.validate_file(&empty).unwrap();
+        assert!(!validation.is_valid_text());
+        assert!(sampled.sample().is_empty());
+
+        assert!(
+            detector
+                .validate_file(&temp_dir.path().join("missing"))
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_stats_accessors() {
+        let mut detector = TextDetector::new();
+        let result = detector.validate(b"caf\xC3\xA9\tbar\x07\nline two\n");
+        let stats = result.stats();
+        assert_eq!(stats.null_bytes(), 0);
+        assert_eq!(stats.control_chars(), 1);
+        assert_eq!(stats.utf8_errors(), 0);
+        assert_eq!(stats.line_breaks(), 2);
+        assert_eq!(stats.ascii_ratio(), 90);
+
+        let result = detector.validate(b"bin\0ary\0\0\0\0");
+        assert!(!result.is_valid_text());
+        assert_eq!(result.stats().null_bytes(), 5);
+    }
+
+    #[test]
+    fn test_last_stats_and_feed() {
+        let mut detector = TextDetector::new();
+        let result = detector.validate(b"alpha\r\nbeta\r\n");
+        assert_eq!(*detector.last_stats(), result.stats());
+        assert_eq!(detector.last_stats().crlf_pairs(), 2);
+
+        let _ = detector.validate(b"");
+        assert_eq!(*detector.last_stats(), TextStats::new());
+
+        let mut stats = TextStats::new();
+        stats.feed_slice(b"a\0\n");
+        stats.feed(b'\r');
+        assert_eq!(stats.null_bytes(), 1);
+        assert_eq!(stats.line_breaks(), 1);
+        assert_eq!(stats.carriage_returns(), 1);
+        assert_eq!(stats.byte_counts()[usize::from(b'a')], 1);
+    }
+
+    #[test]
+    fn test_feed_counts_pairs_across_chunks() {
+        let mut stats = TextStats::new();
+        stats.feed_slice(b"one\r\ntwo\r");
+        stats.feed_slice(b"\nthree\r");
+        stats.feed(b'\n');
+        assert_eq!(stats.crlf_pairs(), 3);
+        assert_eq!(stats.carriage_returns(), 3);
+        assert_eq!(stats.line_ending(), LineEnding::CrLf);
+
+        let mut stats = TextStats::new();
+        stats.feed_slice(b"\r\r\n\n");
+        assert_eq!(stats.crlf_pairs(), 1);
+    }
+
+    #[test]
+    fn test_entropy() {
+        let mut stats = TextStats::new();
+        assert!(stats.entropy().abs() < f32::EPSILON);
+
+        stats.feed_slice(&[b'a'; 64]);
+        assert!(stats.entropy().abs() < f32::EPSILON);
+
+        stats.feed_slice(&[b'b'; 64]);
+        assert!((stats.entropy() - 1.0).abs() < 1e-6);
+
+        let mut stats = TextStats::new();
+        let uniform: Vec<u8> = (0..=255).collect();
+        stats.feed_slice(&uniform);
+        assert!((stats.entropy() - 8.0).abs() < 1e-4);
+
+        // Rounds to just over 8 before clamping
+        let mut stats = TextStats::new();
+        for _ in 0..12 {
+            stats.feed_slice(&uniform);
+        }
+        stats.feed(0);
+        let entropy = stats.entropy();
+        assert!(entropy <= 8.0 && (entropy - 8.0).abs() < 1e-4);
+
+        let mut detector = TextDetector::new();
+        let _ =
+            detector.validate(b"The quick brown fox jumps over the lazy dog.\nAgain and again.\n");
+        let text_entropy = detector.last_stats().entropy();
+        assert!(text_entropy > 3.0 && text_entropy < 5.0);
+    }
+
+    #[test]
+    fn test_one_line_files_are_text() {
+        let mut detector = TextDetector::new();
+        for content in [&b"1.4.2"[..], b"1.4.2\n", b"v2\r\n", b"x"] {
+            let result = detector.validate(content);
+            assert!(result.is_valid_text(), "{content:?}");
+            assert_eq!(result.confidence(), 100, "{content:?}");
+            assert_eq!(result.stats().ascii_ratio(), 100);
+        }
+
+        let result = detector.validate_with_extension(b"1.4.2", "");
+        assert!(result.is_valid_text());
+        assert_eq!(result.confidence(), 100);
+    }
+
+    #[test]
+    fn test_single_line_penalty_scales_with_length() {
+        assert_eq!(single_line_penalty(0), 0);
+        assert_eq!(single_line_penalty(SHORT_LINE_LENGTH - 1), 0);
+        assert_eq!(single_line_penalty(SHORT_LINE_LENGTH), 5);
+        assert_eq!(single_line_penalty(SHORT_LINE_LENGTH * 3), 15);
+        assert_eq!(single_line_penalty(SHORT_LINE_LENGTH * 100), 20);
+    }
+
+    #[test]
+    fn test_content_exactly_sample_size() {
+        let mut detector = TextDetector::new();
+        let mut content = "abcdefg\n".repeat(TEXT_SAMPLE_SIZE / 8).into_bytes();
+        content[TEXT_SAMPLE_SIZE - 2] = 0xC3;
+        content[TEXT_SAMPLE_SIZE - 1] = 0xA9;
+        assert_eq!(content.len(), TEXT_SAMPLE_SIZE);
+
+        let result = detector.validate(&content);
+        assert!(result.is_valid_text());
+        assert_eq!(result.stats().utf8_errors(), 0);
+        assert_eq!(result.stats().ascii_ratio(), 99);
+        assert_eq!(
+            detector
+                .last_stats()
+                .byte_counts
+                .iter()
+                .map(|&c| usize::from(c))
+                .sum::<usize>(),
+            TEXT_SAMPLE_SIZE
+        );
+    }
+
+    #[test]
+    fn test_update_ascii_ratio() {
+        let mut stats = TextStats::new();
+        stats.update_ascii_ratio();
+        assert_eq!(stats.ascii_ratio(), 0);
+
+        stats.feed_slice(b"abc\xC3");
+        stats.update_ascii_ratio();
+        assert_eq!(stats.ascii_ratio(), 75);
+    }
+
+    #[test]
+    fn test_validate_with_known_extension() {
+        let mut detector = TextDetector::new();
+        let content = b"name,count\nalpha,1\nbeta,2\n";
+
+        let result = detector.validate_with_extension(content, "csv");
+        assert!(result.is_valid_text());
+        assert_eq!(result.confidence(), EXTENSION_CONFIDENCE_BASELINE);
+        assert_eq!(result.mime_type(), TextMimeType::Plain);
+
+        let result = detector.validate_with_extension(b"[core]\nkey=value\n", ".INI");
+        assert_eq!(result.mime_type(), TextMimeType::Config);
+
+        let result = detector.validate_with_extension(b"single line", "md");
+        assert_eq!(result.confidence(), EXTENSION_CONFIDENCE_BASELINE);
+        assert_eq!(result.mime_type(), TextMimeType::Markdown);
+
+        let long_line = "word ".repeat(100);
+        let hinted = detector.validate_with_extension(long_line.as_bytes(), "md");
+        assert_eq!(hinted.confidence(), EXTENSION_CONFIDENCE_BASELINE);
+        let plain = detector.validate(long_line.as_bytes());
+        assert_eq!(plain.confidence(), 80);
+
+        // A known extension never lowers confidence
+        for content in [&b"plain words\n"[..], b"a\x01b\nc\n", b"caf\xE9 menu\n"] {
+            let hinted = detector
+                .validate_with_extension(content, "txt")
+                .confidence();
+            assert!(hinted >= detector.validate(content).confidence());
+        }
+
+        let result = detector.validate_with_extension(b"\x7FELF\x02\x01\x01", "txt");
+        assert!(!result.is_valid_text());
+    }
+
+    #[test]
+    fn test_validate_with_unknown_extension() {
+        let mut detector = TextDetector::new();
+        let content = b"fn main() {\n    run();\n}\n";
+
+        let hinted = detector.validate_with_extension(content, "xyz");
+        let plain = detector.validate(content);
+        assert_eq!(hinted.confidence(), plain.confidence());
+        assert_eq!(hinted.mime_type(), plain.mime_type());
+    }
+
+    #[test]
+    fn test_sample_size_bounds() {
+        assert_eq!(TextDetector::new().sample_size(), TEXT_SAMPLE_SIZE);
+        assert_eq!(TextDetector::new().with_sample_size(0).sample_size(), 1);
+        assert_eq!(
+            TextDetector::new()
+                .with_sample_size(MAX_TEXT_SAMPLE_SIZE + 1)
+                .sample_size(),
+            MAX_TEXT_SAMPLE_SIZE
+        );
+    }
+
+    #[test]
+    fn test_larger_sample_sees_past_header() {
+        let mut content = b"Licensed under the MIT license\n".repeat(20);
+        assert!(content.len() > TEXT_SAMPLE_SIZE);
+        content.extend_from_slice(b"fn main() {\n    let x = 1;\n}\n");
+
+        let mut detector = TextDetector::new();
+        assert_eq!(detector.validate(&content).mime_type(), TextMimeType::Plain);
+
+        let mut detector = TextDetector::new().with_sample_size(2048);
+        let result = detector.validate(&content);
+        assert!(result.is_valid_text());
+        assert_eq!(result.mime_type(), TextMimeType::Source);
+    }
+
+    #[test]
+    fn test_line_ending_lf() {
+        let mut detector = TextDetector::new();
+        let result = detector.validate(b"one\ntwo\nthree\n");
+        assert_eq!(result.line_ending(), LineEnding::Lf);
+        assert_eq!(result.stats().crlf_pairs(), 0);
+    }
+
+    #[test]
+    fn test_line_ending_crlf() {
+        let mut detector = TextDetector::new();
+        let result = detector.validate(b"one\r\ntwo\r\nthree\r\n");
+        assert!(result.is_valid_text());
+        assert_eq!(result.line_ending(), LineEnding::CrLf);
+        assert_eq!(result.stats().crlf_pairs(), 3);
+        assert_eq!(result.stats().carriage_returns(), 3);
+    }
+
+    #[test]
+    fn test_line_ending_mixed_and_none() {
+        let mut detector = TextDetector::new();
+        let result = detector.validate(b"one\r\ntwo\nthree\r\n");
+        assert_eq!(result.line_ending(), LineEnding::Mixed);
+
+        let result = detector.validate(b"one\rtwo\rthree\r");
+        assert_eq!(result.line_ending(), LineEnding::Cr);
+
+        let result = detector.validate(b"single line");
+        assert_eq!(result.line_ending(), LineEnding::None);
+    }
+
+    #[test]
+    fn test_line_ending_ignores_split_crlf_at_sample_edge() {
+        let mut content = b"ab\r\n".repeat(127);
+        content.extend_from_slice(b"xyz\r\nmore\r\n");
+        assert_eq!(&content[TEXT_SAMPLE_SIZE - 1..=TEXT_SAMPLE_SIZE], b"\r\n");
+
+        let mut detector = TextDetector::new();
+        let result = detector.validate(&content);
+        assert_eq!(result.line_ending(), LineEnding::CrLf);
+    }
+
     #[test]
     fn test_high_confidence_text() {
         let mut detector = TextDetector::new();
@@ -314,6 +1582,50 @@ mod tests {
         assert_eq!(result.encoding(), TextEncoding::Utf8);
     }
 
+    #[test]
+    fn test_stray_latin1_byte_stays_text() {
+        let mut detector = TextDetector::new();
+        let content = b"quarterly report for the caf\xE9 downtown\nrevenue summary\n";
+        let result = detector.validate(content);
+        assert_eq!(result.stats().utf8_errors(), 1);
+        assert!(result.is_valid_text());
+        assert_eq!(
+            result.decode_lossy(content),
+            "quarterly report for the caf\u{FFFD} downtown\nrevenue summary\n"
+        );
+        assert_eq!(result.decode(content), None);
+    }
+
+    #[test]
+    fn test_feed_counts_utf8_errors() {
+        let count = |chunks: &[&[u8]]| {
+            let mut stats = TextStats::new();
+            for chunk in chunks {
+                stats.feed_slice(chunk);
+            }
+            stats.utf8_errors()
+        };
+        assert_eq!(count(&[b"plain ascii"]), 0);
+        assert_eq!(count(&[b"a\xFFb\xFEc"]), 2);
+        // A character split across chunks is still valid
+        assert_eq!(count(&[b"caf\xC3", b"\xA9 \xE2\x82", b"\xAC"]), 0);
+        // A character cut off by the end of the sample is not an error
+        assert_eq!(count(&["caf\u{E9}".as_bytes().split_last().unwrap().1]), 0);
+
+        // Matches how the standard library splits invalid input
+        for bytes in [
+            &b"\xE2\x82x"[..],
+            b"\xED\xA0\x80",
+            b"\xF0\x80\x80\x80",
+            b"\xC0\xAF",
+            b"\xF4\x90\x80\x80\xE2\x28\xA1",
+            b"\x80\x80",
+        ] {
+            let expected = String::from_utf8_lossy(bytes).matches('\u{FFFD}').count();
+            assert_eq!(usize::from(count(&[bytes])), expected, "{bytes:?}");
+        }
+    }
+
     #[test]
     fn test_low_confidence_text() {
         let mut detector = TextDetector::new();
@@ -326,6 +1638,74 @@ mod tests {
         assert_eq!(result.encoding(), TextEncoding::Unknown);
     }
 
+    fn utf16(text: &str, bom: Option<[u8; 2]>, le: bool) -> Vec<u8> {
+        let mut bytes = bom.map_or_else(Vec::new, |b| b.to_vec());
+        for unit in text.encode_utf16() {
+            if le {
+                bytes.extend_from_slice(&unit.to_le_bytes());
+            } else {
+                bytes.extend_from_slice(&unit.to_be_bytes());
+            }
+        }
+        bytes
+    }
+
+    #[test]
+    fn test_utf16_le_with_bom() {
+        let mut detector = TextDetector::new();
+        let content = utf16("fn main() {\n    run();\n}\n", Some([0xFF, 0xFE]), true);
+        let result = detector.validate(&content);
+        assert!(result.is_valid_text());
+        assert_eq!(result.encoding(), TextEncoding::Utf16Le);
+        assert_eq!(result.mime_type(), TextMimeType::Source);
+        assert_eq!(result.confidence(), 100);
+        assert_eq!(
+            result.decode(&content).as_deref(),
+            Some("fn main() {\n    run();\n}\n")
+        );
+    }
+
+    #[test]
+    fn test_utf16_be_with_bom() {
+        let mut detector = TextDetector::new();
+        let content = utf16("Hello, world!\nSecond line\n", Some([0xFE, 0xFF]), false);
+        let result = detector.validate(&content);
+        assert!(result.is_valid_text());
+        assert_eq!(result.encoding(), TextEncoding::Utf16Be);
+        assert_eq!(
+            result.decode(&content).as_deref(),
+            Some("Hello, world!\nSecond line\n")
+        );
+    }
+
+    #[test]
+    fn test_utf16_bom_hello() {
+        let mut detector = TextDetector::new();
+        for (bom, le, encoding) in [
+            ([0xFF, 0xFE], true, TextEncoding::Utf16Le),
+            ([0xFE, 0xFF], false, TextEncoding::Utf16Be),
+        ] {
+            let content = utf16("Hello", Some(bom), le);
+            let result = detector.validate(&content);
+            assert!(result.is_valid_text());
+            assert_eq!(result.encoding(), encoding);
+            assert_eq!(result.decode(&content).as_deref(), Some("Hello"));
+        }
+    }
+
+    #[test]
+    fn test_utf16_without_bom_has_lower_confidence() {
+        let mut detector = TextDetector::new();
+        let text = "Plain words here\nand more words\n";
+
+        let with_bom = detector.validate(&utf16(text, Some([0xFF, 0xFE]), true));
+        let without_bom = detector.validate(&utf16(text, None, true));
+
+        assert!(without_bom.is_valid_text());
+        assert_eq!(without_bom.encoding(), TextEncoding::Utf16Le);
+        assert!(without_bom.confidence() < with_bom.confidence());
+    }
+
     #[test]
     fn test_ascii_text() {
         let mut detector = TextDetector::new();
@@ -335,4 +1715,65 @@ mod tests {
         assert_eq!(result.encoding(), TextEncoding::Utf8);
         assert!(result.confidence() > 90);
     }
+
+    #[test]
+    fn test_shebang_interpreter() {
+        let mut detector = TextDetector::new();
+        let result = detector.validate(b"#!/bin/sh\necho hello\n");
+        assert_eq!(result.mime_type(), TextMimeType::Script);
+        assert_eq!(result.interpreter().as_deref(), Some("sh"));
+
+        let result = detector.validate(b"#!/usr/bin/env python3\r\nprint('hi')\n");
+        assert_eq!(result.interpreter().as_deref(), Some("python3"));
+
+        let result = detector.validate(b"#!/usr/bin/env -S node --harmony\nconsole.log(1)\n");
+        assert_eq!(result.interpreter().as_deref(), Some("node"));
+
+        let result = detector.validate(b"plain text\nwith lines\n");
+        assert!(result.interpreter().is_none());
+    }
+
+    #[test]
+    fn test_shebang_capped_at_sample() {
+        let mut detector = TextDetector::new().with_sample_size(8);
+        let result = detector.validate(b"#!/bin/bash\necho hi\n");
+        assert!(result.interpreter().is_none());
+    }
+
+    #[test]
+    fn test_script_detection() {
+        let mut detector = TextDetector::new();
+        for content in [
+            &b"#!/bin/bash\nset -e\n"[..],
+            b"#!/usr/bin/env python3\nprint('hi')\n",
+            b"#!/usr/bin/ruby\nputs 1\n",
+            b"#!/usr/bin/perl -w\nprint 1;\n",
+            b"#!/usr/bin/env node\nconsole.log(1);\n",
+            b"#!/usr/bin/python3.12\nprint('hi')\n",
+        ] {
+            let result = detector.validate(content);
+            assert_eq!(result.mime_type(), TextMimeType::Script, "{content:?}");
+        }
+
+        for content in [
+            &b"#!/usr/bin/env rdmd\nvoid main() {}\n"[..],
+            b"#!/usr/bin/fish\necho hi;\n",
+        ] {
+            let result = detector.validate(content);
+            assert_eq!(result.mime_type(), TextMimeType::Source, "{content:?}");
+        }
+
+        // Keywords shared with other languages are not evidence of a script
+        for content in [
+            &b"\n\nimport os\nprint(os.name)\n"[..],
+            b"import java.util.List;\nclass A {}\n",
+            b"def main():\n    pass\n",
+        ] {
+            let result = detector.validate(content);
+            assert_ne!(result.mime_type(), TextMimeType::Script, "{content:?}");
+        }
+
+        let result = detector.validate_with_extension(b"puts 'hello'\nputs 'bye'\n", "rb");
+        assert_eq!(result.mime_type(), TextMimeType::Script);
+    }
 }
@@ -0,0 +1,274 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime};
+
+use arrayvec::ArrayVec;
+
+use super::crawler::{Crawler, CrawlerConfig, ERROR_TOO_MANY_EXCLUSIONS, MAX_EXCLUDED_NAMES};
+use super::error::{SnapError, SnapResult};
+
+pub const POLL_INTERVAL: Duration = Duration::from_millis(500);
+pub const DEFAULT_DEBOUNCE: Duration = Duration::from_secs(1);
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Change {
+    Added(PathBuf),
+    Modified(PathBuf),
+    Removed(PathBuf),
+}
+
+impl Change {
+    #[must_use]
+    pub fn path(&self) -> &Path {
+        match self {
+            Self::Added(path) | Self::Modified(path) | Self::Removed(path) => path,
+        }
+    }
+}
+
+/// Size and modification time, used to tell whether a file changed between
+/// two scans.
+type Stamp = (SystemTime, u64);
+
+/// Detects changes under a directory by rescanning it and comparing file
+/// stamps, so it works on every platform without OS notification support.
+///
+/// Changes are held back until the tree has been quiet for the debounce
+/// interval, and changes to the same file in the meantime are merged into one.
+#[derive(Debug)]
+pub struct Watcher {
+    root: PathBuf,
+    config: CrawlerConfig,
+    excluded_names: ArrayVec<String, MAX_EXCLUDED_NAMES>,
+    debounce: Duration,
+    snapshot: Vec<(PathBuf, Stamp)>,
+    pending: Vec<Change>,
+    last_change: Option<Instant>,
+}
+
+impl Watcher {
+    /// Takes the initial snapshot; files already present are not reported.
+    pub fn new(root: &Path, config: CrawlerConfig, excluded_names: &[&str]) -> SnapResult<Self> {
+        let mut names = ArrayVec::new();
+        for name in excluded_names {
+            names.try_push((*name).to_string()).map_err(|_| {
+                anyhow::Error::from(SnapError::with_code(
+                    format!("Maximum of {MAX_EXCLUDED_NAMES} excluded names exceeded"),
+                    ERROR_TOO_MANY_EXCLUSIONS,
+                ))
+            })?;
+        }
+
+        let mut watcher = Self {
+            root: root.to_path_buf(),
+            config,
+            excluded_names: names,
+            debounce: DEFAULT_DEBOUNCE,
+            snapshot: Vec::new(),
+            pending: Vec::new(),
+            last_change: None,
+        };
+        watcher.snapshot = watcher.scan()?;
+        Ok(watcher)
+    }
+
+    #[must_use]
+    pub const fn with_debounce(mut self, debounce: Duration) -> Self {
+        self.debounce = debounce;
+        self
+    }
+
+    #[must_use]
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Rescans the tree and queues whatever changed since the last scan.
+    pub fn poll(&mut self, now: Instant) -> SnapResult<()> {
+        let current = self.scan()?;
+        let mut changed = false;
+
+        for (path, stamp) in &current {
+            match self.stamp_of(path) {
+                None => changed |= self.record(Change::Added(path.clone())),
+                Some(old) if old != *stamp => {
+                    changed |= self.record(Change::Modified(path.clone()));
+                }
+                Some(_) => {}
+            }
+        }
+        let removed: Vec<_> = self
+            .snapshot
+            .iter()
+            .filter(|(path, _)| current.binary_search_by(|(p, _)| p.cmp(path)).is_err())
+            .map(|(path, _)| path.clone())
+            .collect();
+        for path in removed {
+            changed |= self.record(Change::Removed(path));
+        }
+
+        self.snapshot = current;
+        if changed {
+            self.last_change = Some(now);
+        }
+        Ok(())
+    }
+
+    /// Returns the queued changes once nothing has changed for the debounce
+    /// interval, and nothing before that.
+    pub fn take_ready(&mut self, now: Instant) -> Vec<Change> {
+        let quiet = self
+            .last_change
+            .is_some_and(|last| now.duration_since(last) >= self.debounce);
+        if quiet {
+            self.take_pending()
+        } else {
+            Vec::new()
+        }
+    }
+
+    /// Returns every queued change regardless of the debounce interval.
+    pub fn take_pending(&mut self) -> Vec<Change> {
+        self.last_change = None;
+        std::mem::take(&mut self.pending)
+    }
+
+    fn stamp_of(&self, path: &Path) -> Option<Stamp> {
+        self.snapshot
+            .binary_search_by(|(p, _)| p.as_path().cmp(path))
+            .ok()
+            .map(|i| self.snapshot[i].1)
+    }
+
+    /// Queues a change, merging it with one already queued for the same file.
+    /// Returns whether the queue changed.
+    fn record(&mut self, change: Change) -> bool {
+        let Some(i) = self.pending.iter().position(|c| c.path() == change.path()) else {
+            self.pending.push(change);
+            return true;
+        };
+
+        match (&self.pending[i], change) {
+            // Still new as far as the index is concerned
+            (Change::Added(_), Change::Modified(_)) => {}
+            // Created and deleted within one window: nothing to apply
+            (Change::Added(_), Change::Removed(_)) => {
+                self.pending.remove(i);
+            }
+            (Change::Removed(_), Change::Added(path)) => self.pending[i] = Change::Modified(path),
+            (_, change) => self.pending[i] = change,
+        }
+        true
+    }
+
+    fn scan(&self) -> SnapResult<Vec<(PathBuf, Stamp)>> {
+        let mut crawler = Crawler::with_config(&self.root, self.config)?.with_error_recovery(true);
+        for name in &self.excluded_names {
+            crawler.exclude_name(name)?;
+        }
+
+        let mut files = Vec::new();
+        for path in crawler.collect_all()? {
+            // A file can vanish between listing and stat; the next scan sees it gone
+            if let Ok(metadata) = fs::metadata(&path) {
+                let modified = metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH);
+                files.push((path, (modified, metadata.len())));
+            }
+        }
+        files.sort_unstable_by(|a, b| a.0.cmp(&b.0));
+        Ok(files)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tempfile::TempDir;
+
+    use super::*;
+
+    fn watcher(root: &Path) -> Watcher {
+        Watcher::new(root, CrawlerConfig::default(), &[".snapfind_index"])
+            .unwrap()
+            .with_debounce(Duration::from_secs(1))
+    }
+
+    #[test]
+    fn test_detects_added_modified_and_removed() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        fs::write(root.join("keep.txt"), "a").unwrap();
+        fs::write(root.join("edit.txt"), "a").unwrap();
+        fs::write(root.join("gone.txt"), "a").unwrap();
+        let mut watcher = watcher(root);
+
+        fs::write(root.join("new.txt"), "a").unwrap();
+        fs::write(root.join("edit.txt"), "longer").unwrap();
+        fs::remove_file(root.join("gone.txt")).unwrap();
+        fs::write(root.join(".snapfind_index"), "ignored").unwrap();
+
+        let start = Instant::now();
+        watcher.poll(start).unwrap();
+        assert!(watcher.take_ready(start).is_empty());
+
+        let mut changes = watcher.take_ready(start + Duration::from_secs(1));
+        changes.sort_by(|a, b| a.path().cmp(b.path()));
+        assert_eq!(
+            changes,
+            vec![
+                Change::Modified(root.join("edit.txt")),
+                Change::Removed(root.join("gone.txt")),
+                Change::Added(root.join("new.txt")),
+            ]
+        );
+        assert!(watcher.take_pending().is_empty());
+    }
+
+    #[test]
+    fn test_rapid_writes_coalesce() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        fs::write(root.join("log.txt"), "a").unwrap();
+        let mut watcher = watcher(root);
+
+        let start = Instant::now();
+        for (i, content) in ["ab", "abc", "abcd"].iter().enumerate() {
+            fs::write(root.join("log.txt"), content).unwrap();
+            fs::write(root.join("tmp.txt"), content).unwrap();
+            watcher
+                .poll(start + Duration::from_millis(100 * i as u64))
+                .unwrap();
+        }
+        fs::remove_file(root.join("tmp.txt")).unwrap();
+        watcher.poll(start + Duration::from_millis(300)).unwrap();
+
+        // Each poll restarts the quiet period
+        assert!(
+            watcher
+                .take_ready(start + Duration::from_millis(1200))
+                .is_empty()
+        );
+        assert_eq!(
+            watcher.take_ready(start + Duration::from_millis(1300)),
+            vec![Change::Modified(root.join("log.txt"))]
+        );
+    }
+
+    #[test]
+    fn test_recreated_file_is_modified() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        fs::write(root.join("notes.txt"), "a").unwrap();
+        let mut watcher = watcher(root);
+
+        let start = Instant::now();
+        fs::remove_file(root.join("notes.txt")).unwrap();
+        watcher.poll(start).unwrap();
+        fs::write(root.join("notes.txt"), "b").unwrap();
+        watcher.poll(start).unwrap();
+
+        assert_eq!(
+            watcher.take_pending(),
+            vec![Change::Modified(root.join("notes.txt"))]
+        );
+    }
+}
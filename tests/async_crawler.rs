@@ -0,0 +1,96 @@
+#![cfg(feature = "async")]
+
+use std::fs;
+
+use futures_util::{FutureExt, StreamExt};
+use snapfind::crawler::{AsyncCrawler, Crawler, CrawlerConfig, ERROR_FILE_COUNT_EXCEEDED};
+use snapfind::error::SnapError;
+use tempfile::TempDir;
+
+fn tree() -> TempDir {
+    let temp_dir = TempDir::new().unwrap();
+    fs::create_dir(temp_dir.path().join("sub")).unwrap();
+    for name in ["a.txt", "b.txt", "sub/c.txt"] {
+        fs::write(temp_dir.path().join(name), name).unwrap();
+    }
+    temp_dir
+}
+
+#[tokio::test]
+async fn test_process_next_matches_sync_crawler() {
+    let temp_dir = tree();
+    let expected = Crawler::new(temp_dir.path())
+        .unwrap()
+        .collect_all()
+        .unwrap();
+
+    let mut crawler = AsyncCrawler::new(Crawler::new(temp_dir.path()).unwrap());
+    let mut found = Vec::new();
+    while let Some(batch) = crawler.process_next().await.unwrap() {
+        found.extend(batch);
+    }
+
+    assert_eq!(found, expected);
+    assert_eq!(crawler.stats().files_processed, 3);
+    assert!(crawler.into_inner().is_some());
+}
+
+#[tokio::test]
+async fn test_cancelled_batch_is_not_lost() {
+    let temp_dir = tree();
+    let expected = Crawler::new(temp_dir.path())
+        .unwrap()
+        .collect_all()
+        .unwrap();
+
+    let mut crawler = AsyncCrawler::new(Crawler::new(temp_dir.path()).unwrap());
+    let mut found = Vec::new();
+    // Polled once and dropped while the batch is still being crawled
+    if let Some(batch) = crawler.process_next().now_or_never() {
+        found.extend(batch.unwrap().unwrap_or_default());
+    }
+    while let Some(batch) = crawler.process_next().await.unwrap() {
+        found.extend(batch);
+    }
+
+    assert_eq!(found, expected);
+}
+
+#[tokio::test]
+async fn test_stream_yields_every_path() {
+    let temp_dir = tree();
+    let mut crawler = AsyncCrawler::new(Crawler::new(temp_dir.path()).unwrap());
+
+    let mut paths: Vec<_> = crawler
+        .process_next_stream()
+        .map(Result::unwrap)
+        .collect()
+        .await;
+    paths.sort();
+    assert_eq!(
+        paths,
+        vec![
+            temp_dir.path().join("a.txt"),
+            temp_dir.path().join("b.txt"),
+            temp_dir.path().join("sub/c.txt"),
+        ]
+    );
+}
+
+#[tokio::test]
+async fn test_stream_ends_after_error() {
+    let temp_dir = tree();
+    let config = CrawlerConfig {
+        max_files: 1,
+        ..CrawlerConfig::default()
+    };
+    let mut crawler = AsyncCrawler::new(Crawler::with_config(temp_dir.path(), config).unwrap());
+
+    let items: Vec<_> = crawler.process_next_stream().collect().await;
+    let err = items.last().unwrap().as_ref().unwrap_err();
+    assert_eq!(
+        err.downcast_ref::<SnapError>().unwrap().code(),
+        ERROR_FILE_COUNT_EXCEEDED
+    );
+    assert_eq!(items.iter().filter(|item| item.is_err()).count(), 1);
+}
@@ -0,0 +1,63 @@
+#![cfg(feature = "async")]
+
+use std::path::Path;
+
+use snapfind::error::SnapError;
+use snapfind::search::{AsyncSearchEngine, ERROR_INVALID_INDEX, INDEX_FILE_NAME, SearchEngine};
+use tempfile::TempDir;
+
+fn engine() -> SearchEngine {
+    let mut engine = SearchEngine::new();
+    engine
+        .add_document(Path::new("notes/plan.txt"), "quarterly deploy plan")
+        .unwrap();
+    engine
+        .add_document(Path::new("src/main.rs"), "fn main() {}")
+        .unwrap();
+    engine
+}
+
+#[tokio::test]
+async fn test_save_and_load_round_trip() {
+    let temp_dir = TempDir::new().unwrap();
+    let path = temp_dir.path().join(INDEX_FILE_NAME);
+
+    let saved = AsyncSearchEngine::new(engine());
+    saved.save(&path).await.unwrap();
+    let loaded = AsyncSearchEngine::load(&path).await.unwrap();
+
+    assert_eq!(loaded.engine().document_count(), 2);
+    assert_eq!(
+        loaded.search("deploy").unwrap(),
+        saved.search("deploy").unwrap()
+    );
+}
+
+#[tokio::test]
+async fn test_interoperates_with_sync_engine() {
+    let temp_dir = TempDir::new().unwrap();
+    let sync_path = temp_dir.path().join("sync");
+    let async_path = temp_dir.path().join("async");
+
+    engine().save(&sync_path).unwrap();
+    let loaded = AsyncSearchEngine::load(&sync_path).await.unwrap();
+    loaded.save(&async_path).await.unwrap();
+
+    let reloaded = SearchEngine::load(&async_path).unwrap();
+    assert_eq!(reloaded.search("deploy").unwrap().len(), 1);
+    assert!(!temp_dir.path().join("async.tmp").exists());
+    assert!(!temp_dir.path().join("async.lock").exists());
+}
+
+#[tokio::test]
+async fn test_load_missing_index() {
+    let temp_dir = TempDir::new().unwrap();
+
+    let err = AsyncSearchEngine::load(&temp_dir.path().join(INDEX_FILE_NAME))
+        .await
+        .unwrap_err();
+    assert_eq!(
+        err.downcast_ref::<SnapError>().unwrap().code(),
+        ERROR_INVALID_INDEX
+    );
+}
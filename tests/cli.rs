@@ -0,0 +1,502 @@
+use std::io::Write;
+use std::process::{Command, Output, Stdio};
+use std::time::{Duration, UNIX_EPOCH};
+
+use snapfind::error::ErrorCode;
+use tempfile::TempDir;
+
+fn snap(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_snap"))
+        .args(args)
+        .env("NO_COLOR", "1")
+        .output()
+        .unwrap()
+}
+
+fn snap_stdin(args: &[&str], input: &[u8]) -> Output {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_snap"))
+        .args(args)
+        .env("NO_COLOR", "1")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child.stdin.take().unwrap().write_all(input).unwrap();
+    child.wait_with_output().unwrap()
+}
+
+#[test]
+fn test_missing_directory_exit_code() {
+    let temp_dir = TempDir::new().unwrap();
+    let missing = temp_dir.path().join("missing");
+
+    let output = snap(&["index", missing.to_str().unwrap()]);
+    assert_eq!(
+        output.status.code(),
+        Some(ErrorCode::InvalidIndex.exit_code())
+    );
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("Directory not found"), "{stderr}");
+    assert!(stderr.contains("(error 302)"), "{stderr}");
+}
+
+#[test]
+fn test_invalid_query_exit_code() {
+    let temp_dir = TempDir::new().unwrap();
+    let dir = temp_dir.path().to_str().unwrap();
+
+    for query in ["", &"x".repeat(200)] {
+        let output = snap(&["search", query, dir]);
+        assert_eq!(
+            output.status.code(),
+            Some(ErrorCode::InvalidQuery.exit_code())
+        );
+        assert!(String::from_utf8_lossy(&output.stderr).contains("(error 301)"));
+        // Rejected before any search output
+        assert!(output.stdout.is_empty());
+    }
+}
+
+#[test]
+fn test_success_exit_code() {
+    let temp_dir = TempDir::new().unwrap();
+    std::fs::write(temp_dir.path().join("notes.txt"), "quarterly report").unwrap();
+    let dir = temp_dir.path().to_str().unwrap();
+
+    assert_eq!(snap(&["index", dir]).status.code(), Some(0));
+    let output = snap(&["search", "-0", "report", dir]);
+    assert_eq!(output.status.code(), Some(0));
+    assert!(output.stdout.ends_with(b"notes.txt\0"));
+}
+
+#[test]
+fn test_stats_without_index_succeeds() {
+    let temp_dir = TempDir::new().unwrap();
+
+    let output = snap(&["stats", temp_dir.path().to_str().unwrap()]);
+    assert_eq!(output.status.code(), Some(0));
+    assert!(String::from_utf8_lossy(&output.stdout).starts_with("No index found"));
+}
+
+#[test]
+fn test_index_jobs_store_the_same_documents() {
+    let temp_dir = TempDir::new().unwrap();
+    for i in 0..20 {
+        let dir = temp_dir.path().join(format!("dir_{}", i % 3));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join(format!("file_{i}.txt")), format!("entry {i}\n")).unwrap();
+    }
+    let dir = temp_dir.path().to_str().unwrap();
+
+    let mut listings = Vec::new();
+    for jobs in ["1", "4"] {
+        assert_eq!(snap(&["index", dir, "--jobs", jobs]).status.code(), Some(0));
+        listings.push(snap(&["list", dir, "--long"]).stdout);
+    }
+    assert_eq!(listings[0], listings[1]);
+    assert_eq!(listings[0].iter().filter(|&&b| b == b'\n').count(), 20);
+}
+
+#[test]
+fn test_live_search_matches_indexed_search() {
+    let temp_dir = TempDir::new().unwrap();
+    std::fs::write(
+        temp_dir.path().join("latin1.txt"),
+        b"caf\xE9 report\nnotes\n",
+    )
+    .unwrap();
+    std::fs::write(temp_dir.path().join("doc.pdf"), b"%PDF-1.7\nreport\n").unwrap();
+    let dir = temp_dir.path().to_str().unwrap();
+
+    let live = snap(&["search", "-0", "report", dir]);
+    assert_eq!(live.status.code(), Some(0));
+    let stderr = String::from_utf8_lossy(&live.stderr);
+    assert!(
+        stderr.contains("No index found, searching live (slower)"),
+        "{stderr}"
+    );
+    assert!(live.stdout.ends_with(b"latin1.txt\0"));
+
+    assert_eq!(snap(&["index", dir]).status.code(), Some(0));
+    let indexed = snap(&["search", "-0", "report", dir]);
+    assert!(!String::from_utf8_lossy(&indexed.stderr).contains("No index found"));
+    assert_eq!(live.stdout, indexed.stdout);
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn test_export_import_round_trip() {
+    let temp_dir = TempDir::new().unwrap();
+    let source = temp_dir.path().join("source");
+    let target = temp_dir.path().join("target");
+    std::fs::create_dir_all(&source).unwrap();
+    std::fs::create_dir_all(&target).unwrap();
+    std::fs::write(source.join("notes.txt"), "quarterly report").unwrap();
+    std::fs::write(source.join("plan.md"), "deploy plan").unwrap();
+    let json = temp_dir.path().join("index.json");
+
+    let source = source.to_str().unwrap();
+    let target = target.to_str().unwrap();
+    let json = json.to_str().unwrap();
+    assert_eq!(snap(&["index", source]).status.code(), Some(0));
+    assert_eq!(
+        snap(&["export", source, "--out", json]).status.code(),
+        Some(0)
+    );
+    let output = snap(&["import", json, target]);
+    assert_eq!(output.status.code(), Some(0));
+    assert!(String::from_utf8_lossy(&output.stdout).contains("Imported 2 documents"));
+
+    assert_eq!(
+        snap(&["list", source, "--long"]).stdout,
+        snap(&["list", target, "--long"]).stdout
+    );
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn test_import_reports_rejected_records() {
+    let temp_dir = TempDir::new().unwrap();
+    let json = temp_dir.path().join("index.json");
+    let big = "a".repeat(11);
+    std::fs::write(
+        &json,
+        format!(
+            r#"{{"max_content_length":10,"documents":[
+                {{"path":"ok.txt","content":"fine","content_length":4}},
+                {{"path":"big.txt","content":"{big}","content_length":11}}
+            ]}}"#
+        ),
+    )
+    .unwrap();
+
+    let output = snap(&[
+        "import",
+        json.to_str().unwrap(),
+        temp_dir.path().to_str().unwrap(),
+    ]);
+    assert_eq!(output.status.code(), Some(0));
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("Imported 1 documents"), "{stdout}");
+    assert!(stdout.contains("(1 rejected)"), "{stdout}");
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("rejected record 1 (big.txt)"), "{stderr}");
+    assert!(
+        stderr.contains(&format!("(error {})", ErrorCode::ContentTooLarge.code())),
+        "{stderr}"
+    );
+    assert!(!stderr.contains("ok.txt"), "{stderr}");
+}
+
+#[cfg(not(feature = "serde"))]
+#[test]
+fn test_export_requires_serde() {
+    let temp_dir = TempDir::new().unwrap();
+
+    let output = snap(&["export", temp_dir.path().to_str().unwrap()]);
+    assert_eq!(
+        output.status.code(),
+        Some(ErrorCode::JsonUnsupported.exit_code())
+    );
+    assert!(String::from_utf8_lossy(&output.stderr).contains("--features serde"));
+}
+
+#[test]
+fn test_index_survives_directory_rename() {
+    let parent = TempDir::new().unwrap();
+    let original = parent.path().join("project");
+    std::fs::create_dir_all(original.join("docs")).unwrap();
+    std::fs::write(original.join("docs/guide.md"), "# Guide\nportable index\n").unwrap();
+    assert_eq!(
+        snap(&["index", original.to_str().unwrap()]).status.code(),
+        Some(0)
+    );
+
+    let moved = parent.path().join("moved");
+    std::fs::rename(&original, &moved).unwrap();
+
+    let output = snap(&["search", "-0", "portable", moved.to_str().unwrap()]);
+    assert_eq!(output.status.code(), Some(0));
+    let mut expected = moved.join("docs").join("guide.md").into_os_string();
+    expected.push("\0");
+    assert_eq!(output.stdout, expected.into_encoded_bytes());
+}
+
+#[test]
+fn test_grep_scores_only_stdin_content() {
+    let output = snap_stdin(&["grep", "stdin"], b"a\nb\n");
+    assert_eq!(output.status.code(), Some(0));
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.starts_with("No matches found"), "{stdout}");
+
+    let output = snap_stdin(&["grep", "beta"], b"alpha\nbeta\n");
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("<stdin>:2"), "{stdout}");
+    assert!(!stdout.contains("<stdin>:1"), "{stdout}");
+}
+
+#[test]
+fn test_oversized_crawl_limits_are_rejected() {
+    let temp_dir = TempDir::new().unwrap();
+    let dir = temp_dir.path().to_str().unwrap();
+
+    for (flag, value) in [
+        ("--max-files", "1000000000"),
+        ("--max-files", "18446744073709551615"),
+        ("--max-depth", "1000000000"),
+    ] {
+        let output = snap(&["index", flag, value, dir]);
+        assert_eq!(
+            output.status.code(),
+            Some(ErrorCode::InvalidCrawlConfig.exit_code()),
+            "{flag} {value}"
+        );
+        assert!(String::from_utf8_lossy(&output.stderr).contains("(error 206)"));
+    }
+}
+
+#[test]
+fn test_completions_cover_cli_surface() {
+    let subcommands = [
+        "index",
+        "search",
+        "explain",
+        "grep",
+        "watch",
+        "update",
+        "stats",
+        "list",
+        "export",
+        "import",
+        "completions",
+        "clean",
+    ];
+    let flags = [
+        "max-content",
+        "max-files",
+        "max-file-size",
+        "max-depth",
+        "fuzzy",
+        "glob",
+        "page-size",
+        "print0",
+        "recursive",
+        "dry-run",
+        "split",
+    ];
+    for shell in ["bash", "zsh", "fish", "powershell", "elvish"] {
+        let output = snap(&["completions", shell]);
+        assert_eq!(output.status.code(), Some(0), "{shell}");
+        let script = String::from_utf8(output.stdout).unwrap();
+        assert!(script.contains("snap"), "{shell} does not name the binary");
+        for word in subcommands.iter().chain(&flags) {
+            assert!(script.contains(word), "{shell} is missing {word}");
+        }
+    }
+
+    let output = snap(&["completions", "tcsh"]);
+    assert_ne!(output.status.code(), Some(0));
+}
+
+#[cfg(unix)]
+#[test]
+fn test_non_utf8_file_name_survives_the_index() {
+    use std::ffi::OsStr;
+    use std::os::unix::ffi::OsStrExt;
+
+    let temp_dir = TempDir::new().unwrap();
+    let name = OsStr::from_bytes(b"caf\xe9.txt");
+    std::fs::write(temp_dir.path().join(name), "opening hours").unwrap();
+    let dir = temp_dir.path().to_str().unwrap();
+
+    assert_eq!(snap(&["index", dir]).status.code(), Some(0));
+    let output = snap(&["search", "--print0", "opening", dir]);
+    assert_eq!(output.status.code(), Some(0));
+    assert!(output.stdout.ends_with(b"/caf\xe9.txt\0"), "{output:?}");
+}
+
+#[test]
+fn test_print0_respects_page_size() {
+    let temp_dir = TempDir::new().unwrap();
+    for name in ["a.log", "b.log", "c.log"] {
+        std::fs::write(temp_dir.path().join(name), "log entry").unwrap();
+    }
+    let dir = temp_dir.path().to_str().unwrap();
+    assert_eq!(snap(&["index", dir]).status.code(), Some(0));
+
+    let output = snap(&["search", "-0", "--page-size", "2", "entry", dir]);
+    assert_eq!(output.status.code(), Some(0));
+    // Only the paths, each ended by NUL: no headers, scores or newlines
+    let paths: Vec<_> = output.stdout.split(|&b| b == 0).collect();
+    assert_eq!(paths.len(), 3, "{output:?}");
+    assert!(paths[2].is_empty(), "{output:?}");
+    for path in &paths[..2] {
+        let path = std::path::Path::new(std::str::from_utf8(path).unwrap());
+        assert!(path.is_file(), "{path:?}");
+        assert!(path.starts_with(temp_dir.path()), "{path:?}");
+    }
+}
+
+#[test]
+fn test_source_date_epoch_sets_index_creation_time() {
+    let temp_dir = TempDir::new().unwrap();
+    std::fs::write(temp_dir.path().join("notes.txt"), "quarterly report").unwrap();
+    let dir = temp_dir.path().to_str().unwrap();
+    let index_path = temp_dir.path().join(snapfind::search::INDEX_FILE_NAME);
+
+    let mut indexes = Vec::new();
+    for _ in 0..2 {
+        let output = Command::new(env!("CARGO_BIN_EXE_snap"))
+            .args(["index", dir])
+            .env("SOURCE_DATE_EPOCH", "1700000000")
+            .output()
+            .unwrap();
+        assert_eq!(output.status.code(), Some(0));
+        indexes.push(std::fs::read(&index_path).unwrap());
+    }
+    assert_eq!(indexes[0], indexes[1]);
+
+    let engine = snapfind::search::SearchEngine::load(&index_path).unwrap();
+    assert_eq!(
+        engine.metadata().created,
+        Some(UNIX_EPOCH + Duration::from_secs(1_700_000_000))
+    );
+}
+
+#[test]
+fn test_update_keeps_index_filters() {
+    let temp_dir = TempDir::new().unwrap();
+    let root = temp_dir.path();
+    std::fs::write(root.join("a.txt"), "alpha report").unwrap();
+    let dir = root.to_str().unwrap();
+
+    let output = snap(&["index", "--ext", "txt", "--min-size", "4", dir]);
+    assert_eq!(output.status.code(), Some(0));
+
+    std::fs::write(root.join("b.txt"), "beta report").unwrap();
+    std::fs::write(root.join("c.md"), "gamma report").unwrap();
+    std::fs::write(root.join(".d.txt"), "delta report").unwrap();
+    std::fs::write(root.join("e.txt"), "e").unwrap();
+    let output = snap(&["update", dir]);
+    assert_eq!(output.status.code(), Some(0));
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("Added 1,"), "{stdout}");
+
+    let listing = String::from_utf8(snap(&["list", dir]).stdout).unwrap();
+    assert!(listing.contains("b.txt"), "{listing}");
+    for skipped in ["c.md", ".d.txt", "e.txt"] {
+        assert!(!listing.contains(skipped), "{listing}");
+    }
+}
+
+#[test]
+fn test_clean_recursive_removes_nested_indexes() {
+    let temp_dir = TempDir::new().unwrap();
+    let nested = temp_dir.path().join("docs");
+    std::fs::create_dir(&nested).unwrap();
+    std::fs::write(nested.join("notes.txt"), "quarterly report").unwrap();
+    let index_path = nested.join(snapfind::search::INDEX_FILE_NAME);
+
+    let output = snap(&["index", nested.to_str().unwrap()]);
+    assert_eq!(output.status.code(), Some(0));
+    assert!(index_path.is_file());
+
+    let output = snap(&["clean", "--recursive", temp_dir.path().to_str().unwrap()]);
+    assert_eq!(output.status.code(), Some(0));
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("Removed:"), "{stdout}");
+    assert!(!index_path.exists());
+    assert!(nested.join("notes.txt").is_file());
+}
+
+#[test]
+fn test_clean_recursive_walks_more_files_than_a_crawl() {
+    let temp_dir = TempDir::new().unwrap();
+    let root = temp_dir.path();
+    let nested = root.join("docs");
+    std::fs::create_dir(&nested).unwrap();
+    std::fs::write(nested.join("notes.txt"), "quarterly report").unwrap();
+    assert_eq!(
+        snap(&["index", nested.to_str().unwrap()]).status.code(),
+        Some(0)
+    );
+    for i in 0..=snapfind::crawler::MAX_FILES {
+        std::fs::write(root.join(format!("{i}.txt")), "x").unwrap();
+    }
+
+    let output = snap(&["clean", "-r", "--dry-run", root.to_str().unwrap()]);
+    assert_eq!(output.status.code(), Some(0));
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("Dry run: 1 index files"), "{stdout}");
+}
+
+#[test]
+fn test_clean_recursive_finds_indexes_in_ignored_directories() {
+    let temp_dir = TempDir::new().unwrap();
+    let root = temp_dir.path();
+    let ignored = root.join("sub");
+    std::fs::create_dir(&ignored).unwrap();
+    std::fs::write(ignored.join("notes.txt"), "quarterly report").unwrap();
+    std::fs::write(root.join(snapfind::crawler::IGNORE_FILE_NAME), "sub/\n").unwrap();
+    assert_eq!(
+        snap(&["index", ignored.to_str().unwrap()]).status.code(),
+        Some(0)
+    );
+
+    let output = snap(&["clean", "-r", "--dry-run", root.to_str().unwrap()]);
+    assert_eq!(output.status.code(), Some(0));
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("Dry run: 1 index files"), "{stdout}");
+    assert!(ignored.join(snapfind::search::INDEX_FILE_NAME).is_file());
+}
+
+#[test]
+fn test_update_reads_files_replaced_with_older_copies() {
+    let temp_dir = TempDir::new().unwrap();
+    let notes = temp_dir.path().join("notes.txt");
+    std::fs::write(&notes, "quarterly report").unwrap();
+    let dir = temp_dir.path().to_str().unwrap();
+    assert_eq!(snap(&["index", dir]).status.code(), Some(0));
+
+    // As `cp -p` or `tar x` would leave it: new content, an old mtime
+    std::fs::write(&notes, "annual budget").unwrap();
+    std::fs::File::options()
+        .write(true)
+        .open(&notes)
+        .unwrap()
+        .set_modified(UNIX_EPOCH + Duration::from_secs(1_000_000_000))
+        .unwrap();
+    let output = snap(&["update", dir]);
+    assert_eq!(output.status.code(), Some(0));
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("updated 1"), "{stdout}");
+
+    let output = snap(&["search", "budget", dir]);
+    assert!(String::from_utf8_lossy(&output.stdout).contains("notes.txt"));
+    let output = snap(&["update", dir]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("Added 0, updated 0, removed 0"), "{stdout}");
+}
+
+#[test]
+fn test_list_long_shows_size_and_modification_time() {
+    let temp_dir = TempDir::new().unwrap();
+    let notes = temp_dir.path().join("notes.txt");
+    std::fs::write(&notes, "quarterly report").unwrap();
+    std::fs::File::options()
+        .write(true)
+        .open(&notes)
+        .unwrap()
+        .set_modified(UNIX_EPOCH + Duration::from_secs(1_700_000_000))
+        .unwrap();
+    let dir = temp_dir.path().to_str().unwrap();
+    assert_eq!(snap(&["index", dir]).status.code(), Some(0));
+
+    let output = snap(&["list", "--long", dir]);
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        "      16 2023-11-14 22:13 notes.txt\n"
+    );
+}
@@ -0,0 +1,121 @@
+use std::fs;
+use std::path::Path;
+
+use snapfind::crawler::CrawlerConfig;
+use snapfind::indexer::{IndexConfig, index_directory};
+use snapfind::search::{INDEX_FILE_NAME, SearchEngine};
+use tempfile::TempDir;
+
+/// Text files at three depths, two binaries and one oversized file.
+fn fixture() -> TempDir {
+    let temp_dir = TempDir::new().unwrap();
+    let root = temp_dir.path();
+    fs::create_dir_all(root.join("docs/guides")).unwrap();
+    fs::write(root.join("readme.txt"), "project overview\nsee docs\n").unwrap();
+    fs::write(root.join("docs/notes.md"), "# Notes\ndeploy steps\n").unwrap();
+    fs::write(
+        root.join("docs/guides/setup.txt"),
+        "install\nconfigure deploy\n",
+    )
+    .unwrap();
+    fs::write(root.join("logo.png"), b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR").unwrap();
+    fs::write(root.join("docs/bundle.gz"), b"\x1F\x8B\x08\0deploy\n").unwrap();
+    fs::write(root.join("big.log"), "x".repeat(2048)).unwrap();
+    temp_dir
+}
+
+fn config() -> IndexConfig {
+    IndexConfig {
+        crawler: CrawlerConfig {
+            max_file_size: 1024,
+            ..CrawlerConfig::default()
+        },
+        ..IndexConfig::default()
+    }
+}
+
+#[test]
+fn test_indexes_text_files_in_nested_dirs() {
+    let temp_dir = fixture();
+    let root = temp_dir.path();
+
+    let report = index_directory(root, &config()).unwrap();
+    assert_eq!(report.files_indexed, 3);
+    assert_eq!(report.directories, 3);
+    assert_eq!(report.max_depth, 2);
+    assert_eq!(report.skipped_non_text, 2);
+    assert_eq!(report.skipped_too_large, 1);
+    assert_eq!(report.read_errors, 0);
+
+    let index_path = root.join(INDEX_FILE_NAME);
+    assert_eq!(report.index_path.as_deref(), Some(index_path.as_path()));
+    let engine = SearchEngine::load(&index_path).unwrap();
+    assert_eq!(engine.document_count(), 3);
+    let paths: Vec<_> = engine
+        .search("deploy")
+        .unwrap()
+        .into_iter()
+        .map(|result| result.path)
+        .collect();
+    assert_eq!(paths.len(), 2);
+    assert!(paths.contains(&Path::new("docs/notes.md").to_path_buf()));
+    assert!(paths.contains(&Path::new("docs/guides/setup.txt").to_path_buf()));
+}
+
+#[test]
+fn test_reindexing_skips_existing_index() {
+    let temp_dir = fixture();
+
+    let first = index_directory(temp_dir.path(), &config()).unwrap();
+    let second = index_directory(temp_dir.path(), &config()).unwrap();
+    assert_eq!(first.files_indexed, second.files_indexed);
+    assert_eq!(first.files_found, second.files_found);
+}
+
+#[test]
+fn test_custom_index_path() {
+    let temp_dir = fixture();
+    let out_dir = TempDir::new().unwrap();
+    let index_path = out_dir.path().join("custom.idx");
+
+    let config = IndexConfig {
+        index_path: Some(index_path.clone()),
+        ..config()
+    };
+    let report = index_directory(temp_dir.path(), &config).unwrap();
+    assert_eq!(report.index_path, Some(index_path.clone()));
+    assert!(!temp_dir.path().join(INDEX_FILE_NAME).exists());
+    assert_eq!(SearchEngine::load(&index_path).unwrap().document_count(), 3);
+}
+
+#[test]
+fn test_nothing_saved_without_text_files() {
+    let temp_dir = TempDir::new().unwrap();
+    fs::write(temp_dir.path().join("image.png"), b"\x89PNG\r\n\x1a\n\0\0").unwrap();
+
+    let report = index_directory(temp_dir.path(), &IndexConfig::default()).unwrap();
+    assert_eq!(report.files_indexed, 0);
+    assert_eq!(report.index_path, None);
+    assert!(!temp_dir.path().join(INDEX_FILE_NAME).exists());
+}
+
+#[cfg(unix)]
+#[test]
+fn test_unreadable_file_is_counted() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let temp_dir = fixture();
+    let secret = temp_dir.path().join("docs/secret.txt");
+    fs::write(&secret, "hidden\ntext\n").unwrap();
+    fs::set_permissions(&secret, fs::Permissions::from_mode(0o000)).unwrap();
+    // Privileged users read it anyway
+    let readable = fs::read(&secret).is_ok();
+
+    let report = index_directory(temp_dir.path(), &config()).unwrap();
+    if readable {
+        assert_eq!(report.files_indexed, 4);
+    } else {
+        assert_eq!(report.files_indexed, 3);
+        assert_eq!(report.read_errors, 1);
+    }
+}